@@ -1 +1 @@
-pub mod setting;
\ No newline at end of file
+pub mod setting;
@@ -1,5 +1,9 @@
 use std::path::PathBuf;
+use std::str::FromStr;
 use structopt_derive::StructOpt;
+use crate::compiler::optimization::OptimizationLevel;
+use crate::compiler::semantics::warnings::WarningFlag;
+use crate::compiler::standard::Standard;
 
 #[derive(StructOpt, Debug)]
 #[structopt(name = "MDC", about = "My Dummy Compiler Driver")]
@@ -28,6 +32,12 @@ pub struct Settings {
     )]
     pub validate: bool,
 
+    #[structopt(
+        long = "lint",
+        help = "Directs it to run the tokenizer, parser and semantics validation followed by extra checks for suspicious code, also accepted as `mdc lint <file>`"
+    )]
+    pub lint: bool,
+
     #[structopt(
         short = "t",
         long = "tacky",
@@ -42,10 +52,149 @@ pub struct Settings {
     )]
     pub codegen: bool,
 
+    #[structopt(
+        short = "O",
+        default_value = "0",
+        help = "Optimization level: -O0 optimizes nothing, -O1 folds constants, simplifies algebra, removes unreachable code and reduces strength, -O2 also propagates constants and copies, eliminates common subexpressions and removes dead stores"
+    )]
+    pub optimization_level: OptimizationLevel,
+
+    #[structopt(
+        long = "fold-constants",
+        help = "Evaluates TACKY instructions whose operands are all constants"
+    )]
+    pub fold_constants: bool,
+
+    #[structopt(
+        long = "simplify-algebra",
+        help = "Rewrites TACKY instructions whose result follows from identities like x + 0 or -(-x)"
+    )]
+    pub simplify_algebra: bool,
+
+    #[structopt(
+        long = "propagate-constants",
+        help = "Replaces TACKY variables that hold the same constant on every path, and removes branches that can't be taken"
+    )]
+    pub propagate_constants: bool,
+
+    #[structopt(
+        long = "fold-ranges",
+        help = "Folds TACKY comparisons and branches that the ranges of their operands decide, and drops sanitizer checks that can't fail"
+    )]
+    pub fold_ranges: bool,
+
+    #[structopt(
+        long = "eliminate-unreachable-code",
+        help = "Removes TACKY that can never run, and the jumps and labels that become useless"
+    )]
+    pub eliminate_unreachable_code: bool,
+
+    #[structopt(
+        long = "propagate-copies",
+        help = "Replaces TACKY variables with the value they were copied from"
+    )]
+    pub propagate_copies: bool,
+
+    #[structopt(
+        long = "eliminate-common-subexpressions",
+        help = "Reuses the result of TACKY computations that were already done with the same operands"
+    )]
+    pub eliminate_common_subexpressions: bool,
+
+    #[structopt(
+        long = "eliminate-dead-stores",
+        help = "Removes TACKY instructions whose result is never read"
+    )]
+    pub eliminate_dead_stores: bool,
+
+    #[structopt(
+        long = "reduce-strength",
+        help = "Multiplies by powers of two with shifts and divides by constants without idiv"
+    )]
+    pub reduce_strength: bool,
+
+    #[structopt(
+        long = "allocate-registers",
+        help = "Keeps values in registers instead of on the stack, coloring an interference graph"
+    )]
+    pub allocate_registers: bool,
+
+    #[structopt(
+        long = "stack-report",
+        help = "Prints the size of the stack frame with and without sharing stack slots"
+    )]
+    pub stack_report: bool,
+
+    #[structopt(
+        long = "dump-cfg",
+        help = "Writes the control-flow graph of the TACKY to <file>.dot in Graphviz format"
+    )]
+    pub dump_cfg: bool,
+
     #[structopt(
         short = "S",
         long = "assembly",
         help = "Directs it to emit only an assembly file, but not assemble or link it"
     )]
     pub emit_assembly: bool,
+
+    #[structopt(
+        long = "std",
+        default_value = "c17",
+        help = "Language standard to accept: c89, c99, c11 or c17 (also accepted as -std=<standard>)"
+    )]
+    pub standard: Standard,
+
+    #[structopt(
+        short = "W",
+        number_of_values = 1,
+        help = "Configures warnings: -Wall, -W<name>, -Wno-<name>, -Werror, -Werror=<name> or -Wno-error=<name>"
+    )]
+    pub warnings: Vec<WarningFlag>,
+
+    #[structopt(
+        short = "f",
+        number_of_values = 1,
+        help = "Enables a code generation feature: -fsanitize=undefined"
+    )]
+    pub features: Vec<Feature>,
+}
+
+/// Code generation features enabled with `-f<feature>`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Feature {
+    SanitizeUndefined, // -fsanitize=undefined
+}
+
+impl FromStr for Feature {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sanitize=undefined" => Ok(Feature::SanitizeUndefined),
+            _ => Err(format!("unknown feature '{}'", s)),
+        }
+    }
+}
+
+/// Rewrites gcc-style single dash long options, which structopt doesn't understand,
+/// into their double dash form (`-std=c89` becomes `--std=c89`), and the `lint` command
+/// into the `--lint` flag.
+pub fn normalize_args(args: impl Iterator<Item = String>) -> Vec<String> {
+    let mut args: Vec<String> = args
+        .map(|arg| {
+            if arg.starts_with("-std=") {
+                format!("-{}", arg)
+            } else {
+                arg
+            }
+        })
+        .collect();
+
+    // a lone `lint` is still the file to compile
+    if args.len() > 2 && args[1] == "lint" {
+        args[1] = "--lint".to_string();
+    }
+
+    args
 }
\ No newline at end of file
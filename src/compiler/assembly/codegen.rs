@@ -3,21 +3,33 @@
 use crate::storage::assembly::{AssemblyBinaryOp, AssemblyCondition, AssemblyFunctionDefinition, AssemblyInstruction, AssemblyOperand, AssemblyProgram, AssemblyRegister, AssemblyUnaryOp};
 use crate::storage::tacky::{BinaryOp, FunctionDefinition, Instruction, Program, UnaryOp, Val};
 
+/// Settings that change the generated assembly.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CodegenOptions {
+    /// Multiply by powers of two with shifts and divide by constants with a multiplication by
+    /// a magic number, instead of `imull` and `idivl`.
+    pub reduce_strength: bool,
+}
+
 pub fn gen(program: Program) -> AssemblyProgram {
+    gen_with_options(program, &CodegenOptions::default())
+}
+
+pub fn gen_with_options(program: Program, options: &CodegenOptions) -> AssemblyProgram {
     match program {
         Program::Program(function) => {
-            AssemblyProgram::Program(convert_function(function))
+            AssemblyProgram::Program(convert_function(function, options))
         }
     }
 }
 
-fn convert_function(function: FunctionDefinition) -> AssemblyFunctionDefinition {
+fn convert_function(function: FunctionDefinition, options: &CodegenOptions) -> AssemblyFunctionDefinition {
     match function {
         FunctionDefinition::Function(name, instructions) => {
             let mut res: Vec<AssemblyInstruction> = vec![];
 
             for instruction in instructions {
-                res.append(&mut convert_instruction(instruction))
+                res.append(&mut convert_instruction(instruction, options))
             }
 
             AssemblyFunctionDefinition::Function(name, res)
@@ -25,7 +37,7 @@ fn convert_function(function: FunctionDefinition) -> AssemblyFunctionDefinition
     }
 }
 
-fn convert_instruction(instruction: Instruction) -> Vec<AssemblyInstruction>
+fn convert_instruction(instruction: Instruction, options: &CodegenOptions) -> Vec<AssemblyInstruction>
 {
     match instruction {
         Instruction::Return(val) => {
@@ -41,6 +53,15 @@ fn convert_instruction(instruction: Instruction) -> Vec<AssemblyInstruction>
             vec![AssemblyInstruction::Mov(convert_operand(src), convert_operand(dst.clone())),
                  AssemblyInstruction::Unary(convert_unary_op(un_op), convert_operand(dst))]
         },
+        Instruction::Binary(bin_op @ (BinaryOp::Divide | BinaryOp::Remainder), src1, Val::Constant(divisor), dst)
+            if options.reduce_strength && has_magic_number(divisor) => {
+            convert_division_by_constant(bin_op, src1, divisor, dst)
+        },
+        Instruction::Binary(BinaryOp::Multiply, Val::Constant(factor), src, dst) | Instruction::Binary(BinaryOp::Multiply, src, Val::Constant(factor), dst)
+            if options.reduce_strength && factor > 0 && (factor as u32).is_power_of_two() => {
+            vec![AssemblyInstruction::Mov(convert_operand(src), convert_operand(dst.clone())),
+                 AssemblyInstruction::Binary(AssemblyBinaryOp::Sal, AssemblyOperand::Imm(factor.trailing_zeros() as i32), convert_operand(dst))]
+        },
         Instruction::Binary(bin_op @ (BinaryOp::Divide | BinaryOp::Remainder), src1, src2, dst) => {
             let mut result = vec![AssemblyInstruction::Mov(convert_operand(src1), AssemblyOperand::Reg(AssemblyRegister::AX)),
                  AssemblyInstruction::Cdq,
@@ -55,20 +76,9 @@ fn convert_instruction(instruction: Instruction) -> Vec<AssemblyInstruction>
             result
         },
         Instruction::Binary(bin_op @ (BinaryOp::GreaterThan | BinaryOp::GreaterOrEqual | BinaryOp::LessThan | BinaryOp::LessOrEqual | BinaryOp::Equal | BinaryOp::NotEqual), src1, src2, dst) => {
-            let mut result = vec![AssemblyInstruction::Cmp(convert_operand(src2), convert_operand(src1)),
-                 AssemblyInstruction::Mov(AssemblyOperand::Imm(0), convert_operand(dst.clone()))];
-
-            match bin_op {
-                BinaryOp::GreaterThan => result.push(AssemblyInstruction::SetCC(AssemblyCondition::G, convert_operand(dst))),
-                BinaryOp::GreaterOrEqual => result.push(AssemblyInstruction::SetCC(AssemblyCondition::GE, convert_operand(dst))),
-                BinaryOp::LessThan => result.push(AssemblyInstruction::SetCC(AssemblyCondition::L, convert_operand(dst))),
-                BinaryOp::LessOrEqual => result.push(AssemblyInstruction::SetCC(AssemblyCondition::LE, convert_operand(dst))),
-                BinaryOp::Equal => result.push(AssemblyInstruction::SetCC(AssemblyCondition::E, convert_operand(dst))),
-                BinaryOp::NotEqual => result.push(AssemblyInstruction::SetCC(AssemblyCondition::NE, convert_operand(dst))),
-                _ => unreachable!(),
-            }
-
-            result
+            vec![AssemblyInstruction::Cmp(convert_operand(src2), convert_operand(src1)),
+                 AssemblyInstruction::Mov(AssemblyOperand::Imm(0), convert_operand(dst.clone())),
+                 AssemblyInstruction::SetCC(convert_condition(bin_op), convert_operand(dst))]
         },
         Instruction::Binary(bin_op, src1, src2, dst) => {
             vec![AssemblyInstruction::Mov(convert_operand(src1), convert_operand(dst.clone())),
@@ -82,6 +92,10 @@ fn convert_instruction(instruction: Instruction) -> Vec<AssemblyInstruction>
             vec![AssemblyInstruction::Cmp(AssemblyOperand::Imm(0), convert_operand(val)),
                  AssemblyInstruction::JmpCC(AssemblyCondition::NE, target)]
         },
+        Instruction::JumpIf(bin_op, src1, src2, target) => {
+            vec![AssemblyInstruction::Cmp(convert_operand(src2), convert_operand(src1)),
+                 AssemblyInstruction::JmpCC(convert_condition(bin_op), target)]
+        },
         Instruction::Jump(target) => {
             vec![AssemblyInstruction::Jmp(target)]
         }
@@ -91,7 +105,121 @@ fn convert_instruction(instruction: Instruction) -> Vec<AssemblyInstruction>
         Instruction::Label(identifier) => {
             vec![AssemblyInstruction::Label(identifier)]
         }
+        Instruction::CheckedUnary(un_op, src, dst, error) => {
+            let mut result = convert_instruction(Instruction::Unary(un_op, src, dst), options);
+            result.push(AssemblyInstruction::TrapCC(AssemblyCondition::O, error));
+            result
+        }
+        Instruction::CheckedBinary(bin_op, src1, src2, dst, error) => {
+            // the arithmetic instruction is the last one that touches the flags, and it has to
+            // stay `imull`, a shift doesn't report overflow
+            let mut result = convert_instruction(Instruction::Binary(bin_op, src1, src2, dst), &CodegenOptions::default());
+            result.push(AssemblyInstruction::TrapCC(AssemblyCondition::O, error));
+            result
+        }
+        Instruction::Trap(error) => {
+            vec![AssemblyInstruction::Trap(error)]
+        }
+        Instruction::InlineAsm(template, outputs, inputs, clobbers) => {
+            vec![AssemblyInstruction::InlineAsm(
+                template,
+                outputs.into_iter().map(|(constraint, val)| (constraint, convert_operand(val))).collect(),
+                inputs.into_iter().map(|(constraint, val)| (constraint, convert_operand(val))).collect(),
+                clobbers)]
+        }
+    }
+}
+
+/// `src / divisor` or `src % divisor` without `idivl`.
+///
+/// The high half of `src * magic` is `src * 2^(32 + shift) / divisor` rounded down, so shifting
+/// it right by `shift` gives the quotient, rounded down instead of toward zero. Adding its sign
+/// bit rounds negative quotients up again. The remainder is `src - quotient * divisor`.
+fn convert_division_by_constant(bin_op: BinaryOp, src: Val, divisor: i32, dst: Val) -> Vec<AssemblyInstruction> {
+    let (magic, shift) = magic_number(divisor);
+    let ax = AssemblyOperand::Reg(AssemblyRegister::AX);
+    let dx = AssemblyOperand::Reg(AssemblyRegister::DX);
+
+    let mut result = vec![AssemblyInstruction::Mov(AssemblyOperand::Imm(magic), ax.clone()),
+         AssemblyInstruction::Imul(convert_operand(src.clone()))];
+
+    // a magic number that doesn't fit in 32 bits wrapped around to the other sign
+    if divisor > 0 && magic < 0 {
+        result.push(AssemblyInstruction::Binary(AssemblyBinaryOp::Add, convert_operand(src.clone()), dx.clone()));
+    } else if divisor < 0 && magic > 0 {
+        result.push(AssemblyInstruction::Binary(AssemblyBinaryOp::Sub, convert_operand(src.clone()), dx.clone()));
+    }
+
+    if shift > 0 {
+        result.push(AssemblyInstruction::Binary(AssemblyBinaryOp::Sar, AssemblyOperand::Imm(shift), dx.clone()));
+    }
+
+    result.push(AssemblyInstruction::Mov(dx.clone(), ax.clone()));
+    result.push(AssemblyInstruction::Binary(AssemblyBinaryOp::Shr, AssemblyOperand::Imm(31), ax.clone()));
+    result.push(AssemblyInstruction::Binary(AssemblyBinaryOp::Add, ax.clone(), dx.clone()));
+
+    match bin_op {
+        BinaryOp::Divide => result.push(AssemblyInstruction::Mov(dx, convert_operand(dst))),
+        BinaryOp::Remainder => {
+            result.push(AssemblyInstruction::Binary(AssemblyBinaryOp::Mult, AssemblyOperand::Imm(divisor), dx.clone()));
+            result.push(AssemblyInstruction::Mov(convert_operand(src), ax.clone()));
+            result.push(AssemblyInstruction::Binary(AssemblyBinaryOp::Sub, dx, ax.clone()));
+            result.push(AssemblyInstruction::Mov(ax, convert_operand(dst)));
+        }
+        _ => unreachable!()
+    }
+
+    result
+}
+
+/// Whether dividing by `divisor` can use a magic number. 0 has to fault, and ±1 and `INT_MIN`
+/// are left to `idivl`, which also faults for `INT_MIN / -1` like it should.
+fn has_magic_number(divisor: i32) -> bool {
+    divisor != 0 && divisor != 1 && divisor != -1 && divisor != i32::MIN
+}
+
+/// The magic number and shift of a signed division by `divisor`, from Hacker's Delight 10-1:
+/// the smallest `shift` for which some `magic` makes the high half of `src * magic`, shifted
+/// right by `shift`, the quotient for every `src`.
+fn magic_number(divisor: i32) -> (i32, i32) {
+    const TWO_31: u32 = 1 << 31;
+
+    let abs_divisor = divisor.unsigned_abs();
+    let t = TWO_31 + ((divisor as u32) >> 31);
+    // the largest dividend whose remainder is `abs_divisor - 1`
+    let abs_nc = t - 1 - t % abs_divisor;
+
+    let mut p = 31;
+    let mut q1 = TWO_31 / abs_nc;
+    let mut r1 = TWO_31 - q1 * abs_nc;
+    let mut q2 = TWO_31 / abs_divisor;
+    let mut r2 = TWO_31 - q2 * abs_divisor;
+
+    loop {
+        p += 1;
+
+        q1 = q1.wrapping_mul(2);
+        r1 = r1.wrapping_mul(2);
+        if r1 >= abs_nc {
+            q1 = q1.wrapping_add(1);
+            r1 = r1.wrapping_sub(abs_nc);
+        }
+
+        q2 = q2.wrapping_mul(2);
+        r2 = r2.wrapping_mul(2);
+        if r2 >= abs_divisor {
+            q2 = q2.wrapping_add(1);
+            r2 = r2.wrapping_sub(abs_divisor);
+        }
+
+        let delta = abs_divisor - r2;
+        if !(q1 < delta || (q1 == delta && r1 == 0)) {
+            break;
+        }
     }
+
+    let magic = q2.wrapping_add(1) as i32;
+    (if divisor < 0 { magic.wrapping_neg() } else { magic }, p - 32)
 }
 
 fn convert_unary_op(un_op: UnaryOp) -> AssemblyUnaryOp {
@@ -111,9 +239,102 @@ fn convert_binary_op(bin_op: BinaryOp) -> AssemblyBinaryOp {
     }
 }
 
+/// The condition code under which `cmp src2, src1` leaves `src1 bin_op src2` true.
+fn convert_condition(bin_op: BinaryOp) -> AssemblyCondition {
+    match bin_op {
+        BinaryOp::GreaterThan => AssemblyCondition::G,
+        BinaryOp::GreaterOrEqual => AssemblyCondition::GE,
+        BinaryOp::LessThan => AssemblyCondition::L,
+        BinaryOp::LessOrEqual => AssemblyCondition::LE,
+        BinaryOp::Equal => AssemblyCondition::E,
+        BinaryOp::NotEqual => AssemblyCondition::NE,
+        _ => unreachable!()
+    }
+}
+
 fn convert_operand(operator: Val) -> AssemblyOperand {
     match operator {
         Val::Constant(num) => AssemblyOperand::Imm(num),
         Val::Var(name) => AssemblyOperand::PseudoReg(name)
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use crate::compiler::assembly::codegen::{gen_with_options, has_magic_number, magic_number, CodegenOptions};
+    use crate::storage::assembly::{AssemblyBinaryOp, AssemblyFunctionDefinition, AssemblyInstruction, AssemblyOperand, AssemblyProgram, AssemblyRegister};
+    use crate::storage::tacky::{BinaryOp, FunctionDefinition, Instruction, Program, Val};
+
+    fn gen_reduced(instruction: Instruction) -> Vec<AssemblyInstruction> {
+        let program = Program::Program(FunctionDefinition::Function("main".to_string(), vec![instruction]));
+
+        match gen_with_options(program, &CodegenOptions { reduce_strength: true }) {
+            AssemblyProgram::Program(AssemblyFunctionDefinition::Function(_, instructions)) => instructions,
+        }
+    }
+
+    /// What the instructions `convert_division_by_constant` emits compute.
+    fn divide(src: i32, divisor: i32) -> i32 {
+        let (magic, shift) = magic_number(divisor);
+        let mut high = ((src as i64 * magic as i64) >> 32) as i32;
+
+        if divisor > 0 && magic < 0 {
+            high = high.wrapping_add(src);
+        } else if divisor < 0 && magic > 0 {
+            high = high.wrapping_sub(src);
+        }
+
+        high >>= shift;
+        high + ((high as u32) >> 31) as i32
+    }
+
+    #[test]
+    fn magic_numbers_match_hackers_delight() {
+        assert_eq!(magic_number(3), (0x55555556, 0));
+        assert_eq!(magic_number(5), (0x66666667, 1));
+        assert_eq!(magic_number(7), (0x92492493u32 as i32, 2));
+        assert_eq!(magic_number(-5), (0x99999999u32 as i32, 1));
+        assert_eq!(magic_number(-7), (0x6DB6DB6D, 2));
+    }
+
+    #[test]
+    fn magic_numbers_divide_like_idiv() {
+        let dividends = [0, 1, -1, 2, -2, 6, -6, 7, -7, 100, -100, 12345, -12345, i32::MAX, i32::MIN, i32::MIN + 1, i32::MAX - 1];
+        let divisors = [2, -2, 3, -3, 5, 6, 7, -7, 10, 16, -16, 641, 1 << 30, -(1 << 30), i32::MAX, i32::MIN + 1];
+
+        for &divisor in &divisors {
+            assert!(has_magic_number(divisor));
+            for &src in &dividends {
+                assert_eq!(divide(src, divisor), src.wrapping_div(divisor), "{} / {}", src, divisor);
+            }
+        }
+
+        assert!(!has_magic_number(0) && !has_magic_number(-1) && !has_magic_number(i32::MIN));
+    }
+
+    #[test]
+    fn gen_reduces_strength() {
+        let x = || Val::Var("x.0".to_string());
+        let tmp = || Val::Var("tmp.0".to_string());
+        let pseudo = |name: &str| AssemblyOperand::PseudoReg(name.to_string());
+
+        assert_eq!(gen_reduced(Instruction::Binary(BinaryOp::Multiply, Val::Constant(8), x(), tmp())), vec![
+            AssemblyInstruction::Mov(pseudo("x.0"), pseudo("tmp.0")),
+            AssemblyInstruction::Binary(AssemblyBinaryOp::Sal, AssemblyOperand::Imm(3), pseudo("tmp.0")),
+        ]);
+
+        // / 2 needs no shift, the magic number is 2^31 + 1 and wraps around
+        assert_eq!(gen_reduced(Instruction::Binary(BinaryOp::Divide, x(), Val::Constant(2), tmp())), vec![
+            AssemblyInstruction::Mov(AssemblyOperand::Imm(0x80000001u32 as i32), AssemblyOperand::Reg(AssemblyRegister::AX)),
+            AssemblyInstruction::Imul(pseudo("x.0")),
+            AssemblyInstruction::Binary(AssemblyBinaryOp::Add, pseudo("x.0"), AssemblyOperand::Reg(AssemblyRegister::DX)),
+            AssemblyInstruction::Mov(AssemblyOperand::Reg(AssemblyRegister::DX), AssemblyOperand::Reg(AssemblyRegister::AX)),
+            AssemblyInstruction::Binary(AssemblyBinaryOp::Shr, AssemblyOperand::Imm(31), AssemblyOperand::Reg(AssemblyRegister::AX)),
+            AssemblyInstruction::Binary(AssemblyBinaryOp::Add, AssemblyOperand::Reg(AssemblyRegister::AX), AssemblyOperand::Reg(AssemblyRegister::DX)),
+            AssemblyInstruction::Mov(AssemblyOperand::Reg(AssemblyRegister::DX), pseudo("tmp.0")),
+        ]);
+
+        // checked multiplication has to keep imull for the overflow flag
+        let checked = gen_reduced(Instruction::CheckedBinary(BinaryOp::Multiply, x(), Val::Constant(8), tmp(), "overflow".to_string()));
+        assert!(matches!(checked[1], AssemblyInstruction::Binary(AssemblyBinaryOp::Mult, _, _)));
+    }
+}
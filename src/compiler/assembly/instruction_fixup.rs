@@ -13,18 +13,60 @@ pub fn fixup_program(last_stack_slot: i32, program: AssemblyProgram) -> Assembly
 fn fixup_function(last_stack_slot: i32, function: AssemblyFunctionDefinition) -> AssemblyFunctionDefinition {
     match function {
         AssemblyFunctionDefinition::Function(identifier, instructions) => {
-            let mut fixed_instructions = instructions
-                .into_iter()
-                .flat_map(|i| fixup_instruction(i))
-                .collect::<Vec<_>>();
+            let saved_registers = used_callee_saved_registers(&instructions);
+
+            let mut fixed_instructions = vec![AssemblyInstruction::AllocateStack(-last_stack_slot)];
+            fixed_instructions.extend(saved_registers.iter().map(|register| AssemblyInstruction::Push(*register)));
+
+            for instruction in instructions.into_iter().flat_map(fixup_instruction) {
+                if instruction == AssemblyInstruction::Ret {
+                    fixed_instructions.extend(saved_registers.iter().rev().map(|register| AssemblyInstruction::Pop(*register)));
+                }
+                fixed_instructions.push(instruction);
+            }
 
-            fixed_instructions.insert(0, AssemblyInstruction::AllocateStack(-last_stack_slot));
-            // add allocate stack
             AssemblyFunctionDefinition::Function(identifier, fixed_instructions)
         }
     }
 }
 
+/// The callee-saved registers the function writes to, either because the register allocator
+/// picked them or because inline asm clobbers them.
+fn used_callee_saved_registers(instructions: &[AssemblyInstruction]) -> Vec<AssemblyRegister> {
+    let mut used = vec![];
+    for instruction in instructions {
+        let registers: Vec<AssemblyRegister> = match instruction {
+            AssemblyInstruction::Mov(src, dst)
+            | AssemblyInstruction::Binary(_, src, dst)
+            | AssemblyInstruction::Cmp(src, dst) => registers_of(&[src, dst]),
+            AssemblyInstruction::Unary(_, operand)
+            | AssemblyInstruction::Idiv(operand)
+            | AssemblyInstruction::Imul(operand)
+            | AssemblyInstruction::SetCC(_, operand) => registers_of(&[operand]),
+            AssemblyInstruction::InlineAsm(_, outputs, inputs, clobbers) => {
+                let operands = outputs.iter().chain(inputs).map(|(_, operand)| operand).collect::<Vec<_>>();
+                let mut registers = registers_of(&operands);
+                registers.extend(clobbers.iter().filter_map(|name| AssemblyRegister::from_name(name)));
+                registers
+            }
+            _ => vec![],
+        };
+        used.extend(registers);
+    }
+
+    AssemblyRegister::CALLEE_SAVED.into_iter().filter(|register| used.contains(register)).collect()
+}
+
+fn registers_of(operands: &[&AssemblyOperand]) -> Vec<AssemblyRegister> {
+    operands
+        .iter()
+        .filter_map(|operand| match operand {
+            AssemblyOperand::Reg(register) => Some(*register),
+            _ => None,
+        })
+        .collect()
+}
+
 fn fixup_instruction (instruction: AssemblyInstruction) -> Vec<AssemblyInstruction> {
     match instruction {
         AssemblyInstruction::Mov(AssemblyOperand::Stack(src), AssemblyOperand::Stack(dst)) => {
@@ -46,7 +88,16 @@ fn fixup_instruction (instruction: AssemblyInstruction) -> Vec<AssemblyInstructi
                     AssemblyOperand::Reg(AssemblyRegister::R10))
             ]
         }
-        AssemblyInstruction::Binary(AssemblyBinaryOp::Add, src, dst) => {
+        AssemblyInstruction::Imul(operand @ AssemblyOperand::Imm(_)) => {
+            vec![
+                AssemblyInstruction::Mov(
+                    operand,
+                    AssemblyOperand::Reg(AssemblyRegister::R10)),
+                AssemblyInstruction::Imul(
+                    AssemblyOperand::Reg(AssemblyRegister::R10))
+            ]
+        }
+        AssemblyInstruction::Binary(AssemblyBinaryOp::Add, src @ AssemblyOperand::Stack(_), dst @ AssemblyOperand::Stack(_)) => {
             vec![
                 AssemblyInstruction::Mov(
                     src,
@@ -56,7 +107,7 @@ fn fixup_instruction (instruction: AssemblyInstruction) -> Vec<AssemblyInstructi
                     AssemblyOperand::Reg(AssemblyRegister::R10),
                     dst)]
         }
-        AssemblyInstruction::Binary(AssemblyBinaryOp::Sub, src, dst) => {
+        AssemblyInstruction::Binary(AssemblyBinaryOp::Sub, src @ AssemblyOperand::Stack(_), dst @ AssemblyOperand::Stack(_)) => {
             vec![
                 AssemblyInstruction::Mov(
                     src,
@@ -87,12 +138,97 @@ fn fixup_instruction (instruction: AssemblyInstruction) -> Vec<AssemblyInstructi
                 AssemblyInstruction::Cmp(src, AssemblyOperand::Reg(AssemblyRegister::R11))
             ]
         },
-        AssemblyInstruction::Cmp(src, dst) => {
+        AssemblyInstruction::Cmp(src @ AssemblyOperand::Stack(_), dst @ AssemblyOperand::Stack(_)) => {
             vec![
                 AssemblyInstruction::Mov(src, AssemblyOperand::Reg(AssemblyRegister::R10)),
                 AssemblyInstruction::Cmp(AssemblyOperand::Reg(AssemblyRegister::R10), dst)
             ]
         },
+        AssemblyInstruction::InlineAsm(template, outputs, inputs, clobbers) => {
+            fixup_inline_asm(template, outputs, inputs, clobbers)
+        },
         other => vec![other],
     }
-}
\ No newline at end of file
+}
+
+/// Registers handed out to inline asm operands, in the order they are picked.
+pub const ASM_OPERAND_REGISTERS: [AssemblyRegister; 9] = [
+    AssemblyRegister::AX,
+    AssemblyRegister::CX,
+    AssemblyRegister::DX,
+    AssemblyRegister::SI,
+    AssemblyRegister::DI,
+    AssemblyRegister::R8,
+    AssemblyRegister::R9,
+    AssemblyRegister::R10,
+    AssemblyRegister::R11,
+];
+
+/// Moves the operands whose constraint can't take their current location into registers.
+///
+/// The register allocator keeps values that live across inline asm out of these registers, so
+/// any of them that isn't clobbered and doesn't already hold an operand is free. Each operand
+/// gets its own, which means an output never shares a register with an input, like every
+/// output was marked early-clobber. Semantic analysis already rejected statements with more
+/// operands that take a register than there are registers left.
+fn fixup_inline_asm(
+    template: String,
+    outputs: Vec<(String, AssemblyOperand)>,
+    inputs: Vec<(String, AssemblyOperand)>,
+    clobbers: Vec<String>,
+) -> Vec<AssemblyInstruction> {
+    let clobbered = clobbers.iter().filter_map(|name| AssemblyRegister::from_name(name)).collect::<Vec<_>>();
+    let taken = registers_of(&outputs.iter().chain(&inputs).map(|(_, operand)| operand).collect::<Vec<_>>());
+    let mut free_registers = ASM_OPERAND_REGISTERS.into_iter().filter(|reg| !clobbered.contains(reg) && !taken.contains(reg));
+    let mut next_register = || free_registers
+        .next()
+        .map(AssemblyOperand::Reg)
+        .unwrap_or_else(|| unreachable!("semantic analysis checks that the register operands fit"));
+
+    let mut before = vec![];
+    let mut after = vec![];
+
+    let outputs = outputs
+        .into_iter()
+        .map(|(constraint, operand)| {
+            if fits_constraint(&constraint, &operand) {
+                return (constraint, operand)
+            }
+
+            let register = next_register();
+            if constraint.starts_with('+') {
+                before.push(AssemblyInstruction::Mov(operand.clone(), register.clone()));
+            }
+            after.push(AssemblyInstruction::Mov(register.clone(), operand));
+
+            (constraint, register)
+        })
+        .collect();
+
+    let inputs = inputs
+        .into_iter()
+        .map(|(constraint, operand)| {
+            if fits_constraint(&constraint, &operand) {
+                return (constraint, operand)
+            }
+
+            let register = next_register();
+            before.push(AssemblyInstruction::Mov(operand, register.clone()));
+
+            (constraint, register)
+        })
+        .collect();
+
+    before.push(AssemblyInstruction::InlineAsm(template, outputs, inputs, clobbers));
+    before.append(&mut after);
+    before
+}
+
+/// Whether `constraint` accepts `operand` where it is.
+fn fits_constraint(constraint: &str, operand: &AssemblyOperand) -> bool {
+    match operand {
+        AssemblyOperand::Imm(_) => constraint.contains(['i', 'g']),
+        AssemblyOperand::Reg(_) => constraint.contains(['r', 'g']),
+        _ => constraint.contains(['m', 'g']),
+    }
+}
@@ -1,3 +1,4 @@
 pub mod codegen;
 pub mod instruction_fixup;
+pub mod register_allocation;
 pub mod replace_pseudos;
\ No newline at end of file
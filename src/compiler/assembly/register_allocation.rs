@@ -0,0 +1,536 @@
+//! Register allocation by graph coloring.
+//!
+//! Pseudo registers that are live at the same time interfere and can't share a register. The
+//! hard registers are part of the interference graph too, so a value live across `idivl`,
+//! `cdq` or `imull` stays out of `AX` and `DX`, and a value live across inline asm stays out
+//! of the registers the asm clobbers or borrows for its operands.
+//!
+//! Moves between values that don't interfere are coalesced first, conservatively so a graph
+//! that was colorable stays colorable. Coloring is Chaitin-Briggs: nodes with fewer neighbors
+//! than there are registers are removed one by one, and when none are left the one with the
+//! lowest spill cost is removed optimistically. Whatever doesn't get a color stays a pseudo
+//! register and ends up on the stack.
+
+use std::collections::{BTreeMap, BTreeSet};
+use crate::compiler::assembly::instruction_fixup::ASM_OPERAND_REGISTERS;
+use crate::storage::assembly::{AssemblyFunctionDefinition, AssemblyInstruction, AssemblyOperand, AssemblyProgram, AssemblyRegister};
+
+/// The registers values can be kept in, caller-saved ones first so they are preferred.
+///
+/// `R10` and `R11` are left to instruction fix-up.
+const ALLOCATABLE_REGISTERS: [AssemblyRegister; 12] = [
+    AssemblyRegister::AX,
+    AssemblyRegister::CX,
+    AssemblyRegister::DX,
+    AssemblyRegister::SI,
+    AssemblyRegister::DI,
+    AssemblyRegister::R8,
+    AssemblyRegister::R9,
+    AssemblyRegister::BX,
+    AssemblyRegister::R12,
+    AssemblyRegister::R13,
+    AssemblyRegister::R14,
+    AssemblyRegister::R15,
+];
+
+const K: usize = ALLOCATABLE_REGISTERS.len();
+
+type Graph = BTreeMap<AssemblyOperand, BTreeSet<AssemblyOperand>>;
+
+pub fn allocate_registers(program: AssemblyProgram) -> AssemblyProgram {
+    match program {
+        AssemblyProgram::Program(function) => AssemblyProgram::Program(allocate_function(function)),
+    }
+}
+
+fn allocate_function(function: AssemblyFunctionDefinition) -> AssemblyFunctionDefinition {
+    match function {
+        AssemblyFunctionDefinition::Function(name, instructions) => {
+            let pinned = memory_only_pseudos(&instructions);
+
+            let mut instructions = instructions;
+            let mut graph = build_graph(&instructions, &pinned);
+            loop {
+                let aliases = coalesce(&mut graph, &instructions);
+                if aliases.is_empty() {
+                    break;
+                }
+                instructions = rewrite(instructions, &aliases);
+                graph = build_graph(&instructions, &pinned);
+            }
+
+            let colors = color(&graph, &spill_costs(&instructions))
+                .into_iter()
+                .map(|(pseudo, register)| (pseudo, AssemblyOperand::Reg(register)))
+                .collect();
+
+            AssemblyFunctionDefinition::Function(name, rewrite(instructions, &colors))
+        }
+    }
+}
+
+/// Pseudo registers inline asm needs in memory, they can't be given a register.
+fn memory_only_pseudos(instructions: &[AssemblyInstruction]) -> BTreeSet<AssemblyOperand> {
+    instructions
+        .iter()
+        .filter_map(|instruction| match instruction {
+            AssemblyInstruction::InlineAsm(_, outputs, inputs, _) => Some(outputs.iter().chain(inputs)),
+            _ => None,
+        })
+        .flatten()
+        .filter(|(constraint, operand)| matches!(operand, AssemblyOperand::PseudoReg(_)) && !constraint.contains(['r', 'g']))
+        .map(|(_, operand)| operand.clone())
+        .collect()
+}
+
+fn is_allocatable(operand: &AssemblyOperand, pinned: &BTreeSet<AssemblyOperand>) -> bool {
+    match operand {
+        AssemblyOperand::PseudoReg(_) => !pinned.contains(operand),
+        AssemblyOperand::Reg(register) => ALLOCATABLE_REGISTERS.contains(register),
+        _ => false,
+    }
+}
+
+fn reg(register: AssemblyRegister) -> AssemblyOperand {
+    AssemblyOperand::Reg(register)
+}
+
+/// The operands `instruction` reads and the ones it writes, hard registers included.
+fn uses_and_defs(instruction: &AssemblyInstruction) -> (Vec<AssemblyOperand>, Vec<AssemblyOperand>) {
+    let ax = reg(AssemblyRegister::AX);
+    let dx = reg(AssemblyRegister::DX);
+
+    match instruction {
+        AssemblyInstruction::Mov(src, dst) => (vec![src.clone()], vec![dst.clone()]),
+        AssemblyInstruction::Unary(_, dst) => (vec![dst.clone()], vec![dst.clone()]),
+        AssemblyInstruction::Binary(_, src, dst) => (vec![src.clone(), dst.clone()], vec![dst.clone()]),
+        AssemblyInstruction::Cmp(src, dst) => (vec![src.clone(), dst.clone()], vec![]),
+        AssemblyInstruction::Idiv(src) => (vec![src.clone(), ax.clone(), dx.clone()], vec![ax, dx]),
+        AssemblyInstruction::Imul(src) => (vec![src.clone(), ax.clone()], vec![ax, dx]),
+        AssemblyInstruction::Cdq => (vec![ax], vec![dx]),
+        // only the low byte is written
+        AssemblyInstruction::SetCC(_, dst) => (vec![dst.clone()], vec![dst.clone()]),
+        AssemblyInstruction::Ret => (vec![ax], vec![]),
+        AssemblyInstruction::InlineAsm(_, outputs, inputs, clobbers) => {
+            let uses = inputs
+                .iter()
+                .chain(outputs.iter().filter(|(constraint, _)| constraint.starts_with('+')))
+                .map(|(_, operand)| operand.clone())
+                .collect();
+            let defs = outputs
+                .iter()
+                .map(|(_, operand)| operand.clone())
+                .chain(clobbers.iter().filter_map(|name| AssemblyRegister::from_name(name)).map(reg))
+                .collect();
+            (uses, defs)
+        }
+        _ => (vec![], vec![]),
+    }
+}
+
+/// The registers inline asm may be handed for its operands, whether it uses them or not.
+fn asm_scratch_registers() -> Vec<AssemblyOperand> {
+    ASM_OPERAND_REGISTERS.into_iter().filter(|register| ALLOCATABLE_REGISTERS.contains(register)).map(reg).collect()
+}
+
+fn successors(instructions: &[AssemblyInstruction]) -> Vec<Vec<usize>> {
+    let labels = instructions
+        .iter()
+        .enumerate()
+        .filter_map(|(index, instruction)| match instruction {
+            AssemblyInstruction::Label(label) => Some((label.as_str(), index)),
+            _ => None,
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    instructions
+        .iter()
+        .enumerate()
+        .map(|(index, instruction)| match instruction {
+            AssemblyInstruction::Jmp(target) => vec![labels[target.as_str()]],
+            AssemblyInstruction::JmpCC(_, target) => vec![labels[target.as_str()], index + 1],
+            AssemblyInstruction::Ret | AssemblyInstruction::Trap(_) => vec![],
+            _ if index + 1 < instructions.len() => vec![index + 1],
+            _ => vec![],
+        })
+        .collect()
+}
+
+/// The allocatable operands live after each instruction.
+fn live_out(instructions: &[AssemblyInstruction], pinned: &BTreeSet<AssemblyOperand>) -> Vec<BTreeSet<AssemblyOperand>> {
+    let successors = successors(instructions);
+    let mut live_in = vec![BTreeSet::new(); instructions.len()];
+    let mut live_out = vec![BTreeSet::new(); instructions.len()];
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for index in (0..instructions.len()).rev() {
+            let out = successors[index].iter().flat_map(|successor| live_in[*successor].iter().cloned()).collect::<BTreeSet<_>>();
+
+            let (uses, defs) = uses_and_defs(&instructions[index]);
+            let mut live = out.clone();
+            for def in &defs {
+                live.remove(def);
+            }
+            live.extend(uses.into_iter().filter(|operand| is_allocatable(operand, pinned)));
+
+            if live != live_in[index] {
+                live_in[index] = live;
+                changed = true;
+            }
+            live_out[index] = out;
+        }
+    }
+
+    live_out
+}
+
+fn add_edge(graph: &mut Graph, a: &AssemblyOperand, b: &AssemblyOperand) {
+    if a != b {
+        graph.entry(a.clone()).or_default().insert(b.clone());
+        graph.entry(b.clone()).or_default().insert(a.clone());
+    }
+}
+
+fn build_graph(instructions: &[AssemblyInstruction], pinned: &BTreeSet<AssemblyOperand>) -> Graph {
+    let mut graph = Graph::new();
+
+    for register in ALLOCATABLE_REGISTERS {
+        for other in ALLOCATABLE_REGISTERS {
+            add_edge(&mut graph, &reg(register), &reg(other));
+        }
+    }
+
+    for instruction in instructions {
+        let (uses, defs) = uses_and_defs(instruction);
+        for operand in uses.iter().chain(&defs).filter(|operand| is_allocatable(operand, pinned)) {
+            graph.entry(operand.clone()).or_default();
+        }
+    }
+
+    for (instruction, live) in instructions.iter().zip(live_out(instructions, pinned)) {
+        let (_, defs) = uses_and_defs(instruction);
+        for def in defs.iter().filter(|def| is_allocatable(def, pinned)) {
+            for operand in &live {
+                // the two ends of a move hold the same value, they can share a register
+                if !matches!(instruction, AssemblyInstruction::Mov(src, _) if src == operand) {
+                    add_edge(&mut graph, def, operand);
+                }
+            }
+        }
+
+        if let AssemblyInstruction::InlineAsm(_, outputs, inputs, clobbers) = instruction {
+            let operands = outputs
+                .iter()
+                .chain(inputs)
+                .map(|(_, operand)| operand)
+                .filter(|operand| is_allocatable(operand, pinned))
+                .collect::<Vec<_>>();
+
+            // no two operands share a register, and none of them is in one the asm clobbers
+            for operand in &operands {
+                for other in &operands {
+                    add_edge(&mut graph, operand, other);
+                }
+                for clobber in clobbers.iter().filter_map(|name| AssemblyRegister::from_name(name)) {
+                    if ALLOCATABLE_REGISTERS.contains(&clobber) {
+                        add_edge(&mut graph, operand, &reg(clobber));
+                    }
+                }
+            }
+
+            // what is live across the asm has to stay out of the registers fix-up borrows
+            for scratch in asm_scratch_registers() {
+                for operand in live.iter().filter(|operand| !outputs.iter().any(|(_, output)| output == *operand)) {
+                    add_edge(&mut graph, &scratch, operand);
+                }
+            }
+        }
+    }
+
+    graph
+}
+
+/// The pseudo registers each pseudo register is live at the same time as, so they can't
+/// share a stack slot.
+pub fn interfering_pseudos(instructions: &[AssemblyInstruction]) -> BTreeMap<String, BTreeSet<String>> {
+    build_graph(instructions, &BTreeSet::new())
+        .into_iter()
+        .filter_map(|(node, neighbors)| match node {
+            AssemblyOperand::PseudoReg(name) => Some((
+                name,
+                neighbors
+                    .into_iter()
+                    .filter_map(|neighbor| match neighbor {
+                        AssemblyOperand::PseudoReg(neighbor) => Some(neighbor),
+                        _ => None,
+                    })
+                    .collect(),
+            )),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Merges the ends of moves where that can't make the graph uncolorable, and returns what
+/// each merged pseudo register became.
+fn coalesce(graph: &mut Graph, instructions: &[AssemblyInstruction]) -> BTreeMap<AssemblyOperand, AssemblyOperand> {
+    let mut aliases = BTreeMap::new();
+
+    for instruction in instructions {
+        let AssemblyInstruction::Mov(src, dst) = instruction else { continue };
+        let src = resolve(&aliases, src);
+        let dst = resolve(&aliases, dst);
+
+        if src == dst || !graph.contains_key(&src) || !graph.contains_key(&dst) || graph[&src].contains(&dst) {
+            continue;
+        }
+
+        let (kept, merged) = match (&src, &dst) {
+            (AssemblyOperand::Reg(_), AssemblyOperand::Reg(_)) => continue,
+            (AssemblyOperand::Reg(_), _) if george(graph, &dst, &src) => (src, dst),
+            (_, AssemblyOperand::Reg(_)) if george(graph, &src, &dst) => (dst, src),
+            (AssemblyOperand::PseudoReg(_), AssemblyOperand::PseudoReg(_)) if briggs(graph, &src, &dst) => (dst, src),
+            _ => continue,
+        };
+
+        for neighbor in graph.remove(&merged).unwrap_or_default() {
+            if let Some(edges) = graph.get_mut(&neighbor) {
+                edges.remove(&merged);
+            }
+            add_edge(graph, &kept, &neighbor);
+        }
+        aliases.insert(merged, kept);
+    }
+
+    aliases.keys().map(|pseudo| (pseudo.clone(), resolve(&aliases, pseudo))).collect()
+}
+
+fn resolve(aliases: &BTreeMap<AssemblyOperand, AssemblyOperand>, operand: &AssemblyOperand) -> AssemblyOperand {
+    let mut operand = operand;
+    while let Some(alias) = aliases.get(operand) {
+        operand = alias;
+    }
+    operand.clone()
+}
+
+fn is_significant(graph: &Graph, node: &AssemblyOperand) -> bool {
+    matches!(node, AssemblyOperand::Reg(_)) || graph[node].len() >= K
+}
+
+/// Briggs: the merged node has fewer than K neighbors with K or more neighbors of their own.
+fn briggs(graph: &Graph, a: &AssemblyOperand, b: &AssemblyOperand) -> bool {
+    graph[a].union(&graph[b]).filter(|neighbor| is_significant(graph, neighbor)).count() < K
+}
+
+/// George: every neighbor of `pseudo` already interferes with `register` or is easy to color.
+fn george(graph: &Graph, pseudo: &AssemblyOperand, register: &AssemblyOperand) -> bool {
+    graph[pseudo].iter().all(|neighbor| graph[neighbor].contains(register) || !is_significant(graph, neighbor))
+}
+
+/// How many times each pseudo register is mentioned, every one of them is a memory access
+/// once it is spilled.
+fn spill_costs(instructions: &[AssemblyInstruction]) -> BTreeMap<AssemblyOperand, usize> {
+    let mut costs = BTreeMap::new();
+    for instruction in instructions {
+        let (uses, defs) = uses_and_defs(instruction);
+        for operand in uses.into_iter().chain(defs).filter(|operand| matches!(operand, AssemblyOperand::PseudoReg(_))) {
+            *costs.entry(operand).or_insert(0) += 1;
+        }
+    }
+    costs
+}
+
+fn color(graph: &Graph, costs: &BTreeMap<AssemblyOperand, usize>) -> BTreeMap<AssemblyOperand, AssemblyRegister> {
+    let mut remaining = graph.keys().filter(|node| matches!(node, AssemblyOperand::PseudoReg(_))).cloned().collect::<BTreeSet<_>>();
+    let degree = |node: &AssemblyOperand, remaining: &BTreeSet<AssemblyOperand>| {
+        graph[node].iter().filter(|neighbor| matches!(neighbor, AssemblyOperand::Reg(_)) || remaining.contains(*neighbor)).count()
+    };
+
+    let mut stack = vec![];
+    while !remaining.is_empty() {
+        let node = remaining
+            .iter()
+            .find(|node| degree(node, &remaining) < K)
+            .or_else(|| remaining.iter().min_by(|a, b| {
+                let cost = |node: &AssemblyOperand| costs.get(node).copied().unwrap_or(0) as f64 / degree(node, &remaining) as f64;
+                cost(a).total_cmp(&cost(b))
+            }))
+            .cloned()
+            .unwrap();
+
+        remaining.remove(&node);
+        stack.push(node);
+    }
+
+    let mut colors = BTreeMap::new();
+    while let Some(node) = stack.pop() {
+        let taken = graph[&node]
+            .iter()
+            .filter_map(|neighbor| match neighbor {
+                AssemblyOperand::Reg(register) => Some(*register),
+                _ => colors.get(neighbor).copied(),
+            })
+            .collect::<Vec<_>>();
+
+        if let Some(register) = ALLOCATABLE_REGISTERS.into_iter().find(|register| !taken.contains(register)) {
+            colors.insert(node, register);
+        }
+    }
+
+    colors
+}
+
+/// Replaces the operands in `replacements`, and drops the moves that end up copying a value
+/// onto itself.
+fn rewrite(instructions: Vec<AssemblyInstruction>, replacements: &BTreeMap<AssemblyOperand, AssemblyOperand>) -> Vec<AssemblyInstruction> {
+    let replace = |operand: AssemblyOperand| replacements.get(&operand).cloned().unwrap_or(operand);
+    let replace_all = |operands: Vec<(String, AssemblyOperand)>| {
+        operands.into_iter().map(|(constraint, operand)| (constraint, replace(operand))).collect()
+    };
+
+    instructions
+        .into_iter()
+        .map(|instruction| match instruction {
+            AssemblyInstruction::Mov(src, dst) => AssemblyInstruction::Mov(replace(src), replace(dst)),
+            AssemblyInstruction::Unary(un_op, dst) => AssemblyInstruction::Unary(un_op, replace(dst)),
+            AssemblyInstruction::Binary(bin_op, src, dst) => AssemblyInstruction::Binary(bin_op, replace(src), replace(dst)),
+            AssemblyInstruction::Cmp(src, dst) => AssemblyInstruction::Cmp(replace(src), replace(dst)),
+            AssemblyInstruction::Idiv(src) => AssemblyInstruction::Idiv(replace(src)),
+            AssemblyInstruction::Imul(src) => AssemblyInstruction::Imul(replace(src)),
+            AssemblyInstruction::SetCC(condition, dst) => AssemblyInstruction::SetCC(condition, replace(dst)),
+            AssemblyInstruction::InlineAsm(template, outputs, inputs, clobbers) => {
+                AssemblyInstruction::InlineAsm(template, replace_all(outputs), replace_all(inputs), clobbers)
+            }
+            other => other,
+        })
+        .filter(|instruction| !matches!(instruction, AssemblyInstruction::Mov(src, dst) if src == dst))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::assembly::register_allocation::allocate_registers;
+    use crate::storage::assembly::{AssemblyBinaryOp, AssemblyCondition, AssemblyFunctionDefinition, AssemblyInstruction, AssemblyOperand, AssemblyProgram, AssemblyRegister};
+
+    fn allocate(instructions: Vec<AssemblyInstruction>) -> Vec<AssemblyInstruction> {
+        match allocate_registers(AssemblyProgram::Program(AssemblyFunctionDefinition::Function("main".to_string(), instructions))) {
+            AssemblyProgram::Program(AssemblyFunctionDefinition::Function(_, instructions)) => instructions,
+        }
+    }
+
+    fn pseudo(name: &str) -> AssemblyOperand {
+        AssemblyOperand::PseudoReg(name.to_string())
+    }
+
+    fn reg(register: AssemblyRegister) -> AssemblyOperand {
+        AssemblyOperand::Reg(register)
+    }
+
+    #[test]
+    fn coalesce_moves_into_registers() {
+        // return (a + 2) * a, everything fits in registers and the copy into AX goes away
+        assert_eq!(
+            allocate(vec![
+                AssemblyInstruction::Mov(AssemblyOperand::Imm(3), pseudo("a.0")),
+                AssemblyInstruction::Mov(pseudo("a.0"), pseudo("tmp.0")),
+                AssemblyInstruction::Binary(AssemblyBinaryOp::Add, AssemblyOperand::Imm(2), pseudo("tmp.0")),
+                AssemblyInstruction::Mov(pseudo("tmp.0"), pseudo("tmp.1")),
+                AssemblyInstruction::Binary(AssemblyBinaryOp::Mult, pseudo("a.0"), pseudo("tmp.1")),
+                AssemblyInstruction::Mov(pseudo("tmp.1"), reg(AssemblyRegister::AX)),
+                AssemblyInstruction::Ret,
+            ]),
+            vec![
+                AssemblyInstruction::Mov(AssemblyOperand::Imm(3), reg(AssemblyRegister::CX)),
+                AssemblyInstruction::Mov(reg(AssemblyRegister::CX), reg(AssemblyRegister::AX)),
+                AssemblyInstruction::Binary(AssemblyBinaryOp::Add, AssemblyOperand::Imm(2), reg(AssemblyRegister::AX)),
+                AssemblyInstruction::Binary(AssemblyBinaryOp::Mult, reg(AssemblyRegister::CX), reg(AssemblyRegister::AX)),
+                AssemblyInstruction::Ret,
+            ]
+        );
+    }
+
+    #[test]
+    fn keep_values_live_across_division_out_of_ax_and_dx() {
+        let allocated = allocate(vec![
+            AssemblyInstruction::Mov(AssemblyOperand::Imm(7), pseudo("a.0")),
+            AssemblyInstruction::Mov(AssemblyOperand::Imm(2), pseudo("b.1")),
+            AssemblyInstruction::Mov(pseudo("a.0"), reg(AssemblyRegister::AX)),
+            AssemblyInstruction::Cdq,
+            AssemblyInstruction::Idiv(pseudo("b.1")),
+            AssemblyInstruction::Mov(reg(AssemblyRegister::AX), pseudo("tmp.0")),
+            AssemblyInstruction::Mov(pseudo("tmp.0"), reg(AssemblyRegister::AX)),
+            AssemblyInstruction::Binary(AssemblyBinaryOp::Add, pseudo("a.0"), reg(AssemblyRegister::AX)),
+            AssemblyInstruction::Ret,
+        ]);
+
+        let divisor = allocated.iter().find_map(|instruction| match instruction {
+            AssemblyInstruction::Idiv(src) => Some(src.clone()),
+            _ => None,
+        });
+        let dividend = allocated.iter().find_map(|instruction| match instruction {
+            AssemblyInstruction::Binary(AssemblyBinaryOp::Add, src, _) => Some(src.clone()),
+            _ => None,
+        });
+
+        for operand in [divisor.unwrap(), dividend.unwrap()] {
+            assert!(matches!(operand, AssemblyOperand::Reg(_)), "{:?}", operand);
+            assert_ne!(operand, reg(AssemblyRegister::AX));
+            assert_ne!(operand, reg(AssemblyRegister::DX));
+        }
+    }
+
+    #[test]
+    fn spill_when_registers_run_out() {
+        // the thirteen values are live at once and so is AX, which holds the sum, leaving eleven
+        // registers for thirteen values
+        let names = (0..13).map(|n| format!("x.{}", n)).collect::<Vec<_>>();
+        let mut instructions = names
+            .iter()
+            .enumerate()
+            .map(|(n, name)| AssemblyInstruction::Mov(AssemblyOperand::Imm(n as i32), pseudo(name)))
+            .collect::<Vec<_>>();
+        instructions.push(AssemblyInstruction::Mov(AssemblyOperand::Imm(0), reg(AssemblyRegister::AX)));
+        for name in &names {
+            instructions.push(AssemblyInstruction::Binary(AssemblyBinaryOp::Add, pseudo(name), reg(AssemblyRegister::AX)));
+        }
+        instructions.push(AssemblyInstruction::Ret);
+
+        let allocated = allocate(instructions);
+        let spilled = allocated
+            .iter()
+            .filter(|instruction| matches!(instruction, AssemblyInstruction::Mov(_, AssemblyOperand::PseudoReg(_))))
+            .count();
+        assert_eq!(spilled, 2, "{:?}", allocated);
+    }
+
+    #[test]
+    fn keep_inline_asm_operands_apart() {
+        let allocated = allocate(vec![
+            AssemblyInstruction::Mov(AssemblyOperand::Imm(1), pseudo("x.0")),
+            AssemblyInstruction::Mov(AssemblyOperand::Imm(2), pseudo("y.1")),
+            AssemblyInstruction::InlineAsm(
+                "movl %1, %0".to_string(),
+                vec![("=r".to_string(), pseudo("z.2"))],
+                vec![("r".to_string(), pseudo("x.0")), ("m".to_string(), pseudo("y.1"))],
+                vec!["rcx".to_string()],
+            ),
+            AssemblyInstruction::Cmp(pseudo("x.0"), pseudo("z.2")),
+            AssemblyInstruction::SetCC(AssemblyCondition::E, pseudo("z.2")),
+            AssemblyInstruction::Mov(pseudo("z.2"), reg(AssemblyRegister::AX)),
+            AssemblyInstruction::Ret,
+        ]);
+
+        let Some(AssemblyInstruction::InlineAsm(_, outputs, inputs, _)) = allocated.iter().find(|instruction| matches!(instruction, AssemblyInstruction::InlineAsm(..))) else {
+            panic!("{:?}", allocated)
+        };
+
+        // x stays live after the asm, so it can't be in a register fix-up might borrow
+        assert!(matches!(inputs[0].1, AssemblyOperand::Reg(AssemblyRegister::BX | AssemblyRegister::R12 | AssemblyRegister::R13 | AssemblyRegister::R14 | AssemblyRegister::R15)), "{:?}", inputs);
+        assert_eq!(inputs[1].1, pseudo("y.1"));
+        assert!(matches!(outputs[0].1, AssemblyOperand::Reg(_)), "{:?}", outputs);
+        assert_ne!(outputs[0].1, inputs[0].1);
+        assert_ne!(outputs[0].1, reg(AssemblyRegister::CX));
+    }
+}
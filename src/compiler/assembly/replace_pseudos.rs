@@ -1,6 +1,11 @@
 //! Replacing pseudo registers
+//!
+//! Pseudo registers that are never live at the same time share a stack slot, so the frame
+//! doesn't grow with every temporary.
 
 use std::collections::HashMap;
+use std::fmt;
+use crate::compiler::assembly::register_allocation::interfering_pseudos;
 use crate::storage::assembly::{AssemblyFunctionDefinition, AssemblyInstruction, AssemblyOperand, AssemblyProgram};
 
 /// Structure to keep tack of what stack slots we've assigned so far
@@ -13,40 +18,104 @@ struct ReplacementState {
 }
 
 impl ReplacementState {
-    fn new() -> Self {
-        ReplacementState { current_offset: 0, offset_map: HashMap::new() }
+    fn new(offset_map: HashMap<String, i32>) -> Self {
+        ReplacementState { current_offset: 0, offset_map }
     }
 }
 
-pub fn replace_pseudos(program: AssemblyProgram) -> (AssemblyProgram, i32) {
+/// How big the stack frame is with a slot for every pseudo register and with shared slots.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StackFrameStatistics {
+    /// Pseudo registers that ended up on the stack.
+    pub pseudos: usize,
+    pub unshared_size: i32,
+    pub frame_size: i32,
+}
+
+impl fmt::Display for StackFrameStatistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} pseudo register(s) on the stack", self.pseudos)?;
+        writeln!(f, "  frame size without sharing slots: {} byte(s)", self.unshared_size)?;
+        writeln!(f, "  frame size with shared slots: {} byte(s)", self.frame_size)
+    }
+}
+
+pub fn replace_pseudos(program: AssemblyProgram) -> (AssemblyProgram, StackFrameStatistics) {
     match program {
         AssemblyProgram::Program(function) => {
-            let (fixed_def, last_stack_slot) = replace_pseudos_in_function(function);
-            (AssemblyProgram::Program(fixed_def), last_stack_slot as i32)
+            let (fixed_def, statistics) = replace_pseudos_in_function(function);
+            (AssemblyProgram::Program(fixed_def), statistics)
         }
     }
 }
 
-fn replace_pseudos_in_function(function: AssemblyFunctionDefinition) -> (AssemblyFunctionDefinition, i32)
+fn replace_pseudos_in_function(function: AssemblyFunctionDefinition) -> (AssemblyFunctionDefinition, StackFrameStatistics)
 {
     match function {
         AssemblyFunctionDefinition::Function(identifier, instructions) => {
+            let offset_map = assign_stack_slots(&instructions);
+            let pseudos = offset_map.len();
+
             let (final_state, final_instructions) = instructions
                 .into_iter()
                 .fold(
-                    (ReplacementState::new(), vec![]),
+                    (ReplacementState::new(offset_map), vec![]),
                     move |(mut state, mut new_instructions), instruction| {
                     let result = replace_pseudos_in_instruction(state, instruction);
                     state = result.0;
-                    new_instructions.push(result.1);
+                    // pseudos copied into each other can end up sharing a slot
+                    if !matches!(&result.1, AssemblyInstruction::Mov(src, dst) if src == dst) {
+                        new_instructions.push(result.1);
+                    }
                     (state, new_instructions)
                 });
 
-            (AssemblyFunctionDefinition::Function(identifier, final_instructions), final_state.current_offset)
+            let statistics = StackFrameStatistics {
+                pseudos,
+                unshared_size: 4 * pseudos as i32,
+                frame_size: -final_state.current_offset,
+            };
+
+            (AssemblyFunctionDefinition::Function(identifier, final_instructions), statistics)
         }
     }
 }
 
+/// Gives every pseudo register, in the order they first appear, the first slot none of the
+/// pseudo registers live at the same time already has.
+fn assign_stack_slots(instructions: &[AssemblyInstruction]) -> HashMap<String, i32> {
+    let interference = interfering_pseudos(instructions);
+    let mut first_appearance = interference.keys().map(|pseudo| (first_appearance(instructions, pseudo), pseudo)).collect::<Vec<_>>();
+    first_appearance.sort();
+
+    let mut offset_map: HashMap<String, i32> = HashMap::new();
+    for (_, pseudo) in first_appearance {
+        let taken = interference[pseudo].iter().filter_map(|other| offset_map.get(other)).collect::<Vec<_>>();
+        let offset = (1..).map(|slot| -4 * slot).find(|offset| !taken.contains(&offset)).unwrap();
+        offset_map.insert(pseudo.clone(), offset);
+    }
+
+    offset_map
+}
+
+fn first_appearance(instructions: &[AssemblyInstruction], pseudo: &str) -> usize {
+    let pseudo = AssemblyOperand::PseudoReg(pseudo.to_string());
+    instructions
+        .iter()
+        .position(|instruction| match instruction {
+            AssemblyInstruction::Mov(src, dst)
+            | AssemblyInstruction::Binary(_, src, dst)
+            | AssemblyInstruction::Cmp(src, dst) => *src == pseudo || *dst == pseudo,
+            AssemblyInstruction::Unary(_, operand)
+            | AssemblyInstruction::Idiv(operand)
+            | AssemblyInstruction::Imul(operand)
+            | AssemblyInstruction::SetCC(_, operand) => *operand == pseudo,
+            AssemblyInstruction::InlineAsm(_, outputs, inputs, _) => outputs.iter().chain(inputs).any(|(_, operand)| *operand == pseudo),
+            _ => false,
+        })
+        .unwrap_or(instructions.len())
+}
+
 fn replace_pseudos_in_instruction(mut state: ReplacementState, instruction: AssemblyInstruction) -> (ReplacementState, AssemblyInstruction) {
     match instruction {
         AssemblyInstruction::Mov(src, dst ) => {
@@ -67,6 +136,10 @@ fn replace_pseudos_in_instruction(mut state: ReplacementState, instruction: Asse
             let new_src = replace_operand(&mut state, src);
             (state, AssemblyInstruction::Idiv(new_src))
         }
+        AssemblyInstruction::Imul(src) => {
+            let new_src = replace_operand(&mut state, src);
+            (state, AssemblyInstruction::Imul(new_src))
+        }
         AssemblyInstruction::Ret => {
             (state, AssemblyInstruction::Ret)
         }
@@ -91,6 +164,31 @@ fn replace_pseudos_in_instruction(mut state: ReplacementState, instruction: Asse
         AssemblyInstruction::Jmp(target) => {
             (state, AssemblyInstruction::Jmp(target))
         },
+        AssemblyInstruction::Trap(error) => {
+            (state, AssemblyInstruction::Trap(error))
+        },
+        AssemblyInstruction::TrapCC(condition, error) => {
+            (state, AssemblyInstruction::TrapCC(condition, error))
+        },
+        AssemblyInstruction::Push(register) => {
+            (state, AssemblyInstruction::Push(register))
+        },
+        AssemblyInstruction::Pop(register) => {
+            (state, AssemblyInstruction::Pop(register))
+        },
+        AssemblyInstruction::InlineAsm(template, outputs, inputs, clobbers) => {
+            // pseudos without a register live on the stack, `instruction_fixup` moves the ones
+            // whose constraint doesn't allow memory into registers
+            let new_outputs = outputs
+                .into_iter()
+                .map(|(constraint, operand)| (constraint, replace_operand(&mut state, operand)))
+                .collect();
+            let new_inputs = inputs
+                .into_iter()
+                .map(|(constraint, operand)| (constraint, replace_operand(&mut state, operand)))
+                .collect();
+            (state, AssemblyInstruction::InlineAsm(template, new_outputs, new_inputs, clobbers))
+        },
         AssemblyInstruction::AllocateStack(_) => {
             panic!("Internal error: AllocateStack shouldn't be present at this point")
         }
@@ -101,18 +199,57 @@ fn replace_pseudos_in_instruction(mut state: ReplacementState, instruction: Asse
 fn replace_operand(state: &mut ReplacementState, operand: AssemblyOperand) -> AssemblyOperand {
     match operand {
         AssemblyOperand::PseudoReg(name) => {
-            match state.offset_map.get(&name) {
-                Some(val) => {
-                    AssemblyOperand::Stack(val.clone())
-                }
-                None => {
-                    state.current_offset = state.current_offset - 4;
-                    state.offset_map.insert(name, state.current_offset);
-
-                    AssemblyOperand::Stack(state.current_offset)
-                }
-            }
+            let offset = state.offset_map[&name];
+            state.current_offset = state.current_offset.min(offset);
+
+            AssemblyOperand::Stack(offset)
         }
         other => other
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::assembly::replace_pseudos::{replace_pseudos, StackFrameStatistics};
+    use crate::storage::assembly::{AssemblyBinaryOp, AssemblyFunctionDefinition, AssemblyInstruction, AssemblyOperand, AssemblyProgram, AssemblyRegister};
+
+    fn pseudo(name: &str) -> AssemblyOperand {
+        AssemblyOperand::PseudoReg(name.to_string())
+    }
+
+    #[test]
+    fn share_stack_slots_between_pseudos_that_are_not_live_together() {
+        // return (a + 1) + (a * 2), tmp.0 is still live when tmp.1 is computed but a isn't
+        let (program, statistics) = replace_pseudos(AssemblyProgram::Program(AssemblyFunctionDefinition::Function(
+            "main".to_string(),
+            vec![
+                AssemblyInstruction::Mov(AssemblyOperand::Imm(3), pseudo("a.0")),
+                AssemblyInstruction::Mov(pseudo("a.0"), pseudo("tmp.0")),
+                AssemblyInstruction::Binary(AssemblyBinaryOp::Add, AssemblyOperand::Imm(1), pseudo("tmp.0")),
+                AssemblyInstruction::Mov(pseudo("a.0"), pseudo("tmp.1")),
+                AssemblyInstruction::Binary(AssemblyBinaryOp::Mult, AssemblyOperand::Imm(2), pseudo("tmp.1")),
+                AssemblyInstruction::Mov(pseudo("tmp.0"), pseudo("tmp.2")),
+                AssemblyInstruction::Binary(AssemblyBinaryOp::Add, pseudo("tmp.1"), pseudo("tmp.2")),
+                AssemblyInstruction::Mov(pseudo("tmp.2"), AssemblyOperand::Reg(AssemblyRegister::AX)),
+                AssemblyInstruction::Ret,
+            ],
+        )));
+
+        assert_eq!(
+            program,
+            AssemblyProgram::Program(AssemblyFunctionDefinition::Function(
+                "main".to_string(),
+                vec![
+                    AssemblyInstruction::Mov(AssemblyOperand::Imm(3), AssemblyOperand::Stack(-4)),
+                    AssemblyInstruction::Mov(AssemblyOperand::Stack(-4), AssemblyOperand::Stack(-8)),
+                    AssemblyInstruction::Binary(AssemblyBinaryOp::Add, AssemblyOperand::Imm(1), AssemblyOperand::Stack(-8)),
+                    AssemblyInstruction::Binary(AssemblyBinaryOp::Mult, AssemblyOperand::Imm(2), AssemblyOperand::Stack(-4)),
+                    AssemblyInstruction::Binary(AssemblyBinaryOp::Add, AssemblyOperand::Stack(-4), AssemblyOperand::Stack(-8)),
+                    AssemblyInstruction::Mov(AssemblyOperand::Stack(-8), AssemblyOperand::Reg(AssemblyRegister::AX)),
+                    AssemblyInstruction::Ret,
+                ],
+            ))
+        );
+        assert_eq!(statistics, StackFrameStatistics { pseudos: 4, unshared_size: 16, frame_size: 8 });
+    }
+}
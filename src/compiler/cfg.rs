@@ -0,0 +1,317 @@
+//! Control-flow graph of a TACKY function.
+//!
+//! The body is split into basic blocks, straight runs of instructions that are only entered at
+//! the top and only left at the bottom. A block starts at a `Label` and ends after a jump,
+//! `Return` or `Trap`. Two extra nodes, entry and exit, stand for entering and leaving the
+//! function, so every block has its predecessors and successors spelled out.
+
+use std::collections::{BTreeMap, HashMap};
+use crate::storage::tacky::{FunctionDefinition, Instruction};
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
+pub enum NodeId {
+    Entry,
+    Block(usize),
+    Exit,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct BasicBlock {
+    pub instructions: Vec<Instruction>,
+    pub predecessors: Vec<NodeId>,
+    pub successors: Vec<NodeId>,
+}
+
+/// The blocks are numbered in the order they appear in the function, turning the graph back
+/// into instructions lays them out in that order again.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Cfg {
+    pub function_name: String,
+    pub entry_successors: Vec<NodeId>,
+    pub exit_predecessors: Vec<NodeId>,
+    pub blocks: BTreeMap<usize, BasicBlock>,
+}
+
+impl Cfg {
+    pub fn from_function(function: FunctionDefinition) -> Self {
+        match function {
+            FunctionDefinition::Function(name, instructions) => {
+                let mut cfg = Cfg {
+                    function_name: name,
+                    entry_successors: vec![],
+                    exit_predecessors: vec![],
+                    blocks: partition_into_blocks(instructions)
+                        .into_iter()
+                        .map(|instructions| BasicBlock { instructions, predecessors: vec![], successors: vec![] })
+                        .enumerate()
+                        .collect(),
+                };
+                cfg.add_all_edges();
+                cfg
+            }
+        }
+    }
+
+    pub fn into_function(self) -> FunctionDefinition {
+        let instructions = self.blocks.into_values().flat_map(|block| block.instructions).collect();
+
+        FunctionDefinition::Function(self.function_name, instructions)
+    }
+
+    pub fn successors(&self, node: NodeId) -> &[NodeId] {
+        match node {
+            NodeId::Entry => &self.entry_successors,
+            NodeId::Block(id) => &self.blocks[&id].successors,
+            NodeId::Exit => &[],
+        }
+    }
+
+    pub fn predecessors(&self, node: NodeId) -> &[NodeId] {
+        match node {
+            NodeId::Entry => &[],
+            NodeId::Block(id) => &self.blocks[&id].predecessors,
+            NodeId::Exit => &self.exit_predecessors,
+        }
+    }
+
+    /// Adds the edge `from -> to`, unless it is already there.
+    pub fn add_edge(&mut self, from: NodeId, to: NodeId) {
+        let successors = match from {
+            NodeId::Entry => &mut self.entry_successors,
+            NodeId::Block(id) => &mut self.blocks.get_mut(&id).unwrap().successors,
+            NodeId::Exit => unreachable!("the exit has no successors"),
+        };
+
+        if successors.contains(&to) {
+            return;
+        }
+        successors.push(to);
+
+        match to {
+            NodeId::Entry => unreachable!("the entry has no predecessors"),
+            NodeId::Block(id) => self.blocks.get_mut(&id).unwrap().predecessors.push(from),
+            NodeId::Exit => self.exit_predecessors.push(from),
+        }
+    }
+
+    pub fn remove_edge(&mut self, from: NodeId, to: NodeId) {
+        match from {
+            NodeId::Entry => self.entry_successors.retain(|&node| node != to),
+            NodeId::Block(id) => self.blocks.get_mut(&id).unwrap().successors.retain(|&node| node != to),
+            NodeId::Exit => (),
+        }
+
+        match to {
+            NodeId::Entry => (),
+            NodeId::Block(id) => self.blocks.get_mut(&id).unwrap().predecessors.retain(|&node| node != from),
+            NodeId::Exit => self.exit_predecessors.retain(|&node| node != from),
+        }
+    }
+
+    /// Removes the block and every edge that touches it.
+    pub fn remove_block(&mut self, id: usize) {
+        let block = &self.blocks[&id];
+        let edges: Vec<(NodeId, NodeId)> = block.predecessors.iter().map(|&from| (from, NodeId::Block(id)))
+            .chain(block.successors.iter().map(|&to| (NodeId::Block(id), to)))
+            .collect();
+
+        for (from, to) in edges {
+            self.remove_edge(from, to);
+        }
+
+        self.blocks.remove(&id);
+    }
+
+    /// The node control reaches when it falls off the end of block `id`.
+    pub fn next_node(&self, id: usize) -> NodeId {
+        match self.blocks.range(id + 1..).next() {
+            Some((&next, _)) => NodeId::Block(next),
+            None => NodeId::Exit,
+        }
+    }
+
+    fn add_all_edges(&mut self) {
+        let label_blocks: HashMap<String, usize> = self.blocks
+            .iter()
+            .filter_map(|(&id, block)| match block.instructions.first() {
+                Some(Instruction::Label(label)) => Some((label.clone(), id)),
+                _ => None,
+            })
+            .collect();
+
+        let first = match self.blocks.keys().next() {
+            Some(&id) => NodeId::Block(id),
+            None => NodeId::Exit,
+        };
+        self.add_edge(NodeId::Entry, first);
+
+        let ids: Vec<usize> = self.blocks.keys().copied().collect();
+
+        for id in ids {
+            let next = self.next_node(id);
+            let successors = match self.blocks[&id].instructions.last() {
+                Some(Instruction::Return(_)) | Some(Instruction::Trap(_)) => vec![NodeId::Exit],
+                Some(Instruction::Jump(target)) => vec![NodeId::Block(label_blocks[target])],
+                Some(Instruction::JumpIfZero(_, target))
+                | Some(Instruction::JumpIfNotZero(_, target))
+                | Some(Instruction::JumpIf(_, _, _, target)) => {
+                    vec![NodeId::Block(label_blocks[target]), next]
+                }
+                _ => vec![next],
+            };
+
+            for successor in successors {
+                self.add_edge(NodeId::Block(id), successor);
+            }
+        }
+    }
+
+    /// The graph in Graphviz DOT, one box per block listing its instructions.
+    pub fn to_dot(&self) -> String {
+        let mut dot = format!("digraph \"{}\" {{\n", escape_dot(&self.function_name));
+        dot.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+        dot.push_str("    entry [label=\"entry\", shape=oval];\n");
+        dot.push_str("    exit [label=\"exit\", shape=oval];\n");
+
+        for (id, block) in &self.blocks {
+            let label: String = block.instructions
+                .iter()
+                .map(|instruction| format!("{}\\l", escape_dot(&format!("{:?}", instruction))))
+                .collect();
+            dot.push_str(&format!("    block{} [label=\"{}\"];\n", id, label));
+        }
+
+        let nodes = std::iter::once(NodeId::Entry).chain(self.blocks.keys().map(|&id| NodeId::Block(id)));
+        for from in nodes {
+            for &to in self.successors(from) {
+                dot.push_str(&format!("    {} -> {};\n", dot_node_name(from), dot_node_name(to)));
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Splits `instructions` into the instruction lists of the basic blocks.
+fn partition_into_blocks(instructions: Vec<Instruction>) -> Vec<Vec<Instruction>> {
+    let mut blocks = vec![];
+    let mut current = vec![];
+
+    for instruction in instructions {
+        match instruction {
+            Instruction::Label(_) => {
+                if !current.is_empty() {
+                    blocks.push(current);
+                }
+                current = vec![instruction];
+            }
+            Instruction::Jump(_)
+            | Instruction::JumpIfZero(..)
+            | Instruction::JumpIfNotZero(..)
+            | Instruction::JumpIf(..)
+            | Instruction::Return(_)
+            | Instruction::Trap(_) => {
+                current.push(instruction);
+                blocks.push(current);
+                current = vec![];
+            }
+            _ => current.push(instruction),
+        }
+    }
+
+    if !current.is_empty() {
+        blocks.push(current);
+    }
+
+    blocks
+}
+
+fn dot_node_name(node: NodeId) -> String {
+    match node {
+        NodeId::Entry => "entry".to_string(),
+        NodeId::Block(id) => format!("block{}", id),
+        NodeId::Exit => "exit".to_string(),
+    }
+}
+
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::cfg::{Cfg, NodeId};
+    use crate::compiler::optimization::test_helpers::var;
+    use crate::storage::tacky::{FunctionDefinition, Instruction, Val};
+
+    /// The TACKY of `return a && b;`.
+    fn and_function() -> FunctionDefinition {
+        FunctionDefinition::Function("main".to_string(), vec![
+            Instruction::JumpIfZero(var("a.0"), "and_false0".to_string()),
+            Instruction::JumpIfZero(var("b.0"), "and_false0".to_string()),
+            Instruction::Copy(Val::Constant(1), var("tmp.0")),
+            Instruction::Jump("and_false_end0".to_string()),
+            Instruction::Label("and_false0".to_string()),
+            Instruction::Copy(Val::Constant(0), var("tmp.0")),
+            Instruction::Label("and_false_end0".to_string()),
+            Instruction::Return(var("tmp.0")),
+            Instruction::Return(Val::Constant(0)),
+        ])
+    }
+
+    #[test]
+    fn build_cfg_blocks_and_edges() {
+        let cfg = Cfg::from_function(and_function());
+
+        assert_eq!(cfg.blocks.len(), 6);
+        assert_eq!(cfg.blocks[&0].instructions, vec![Instruction::JumpIfZero(var("a.0"), "and_false0".to_string())]);
+        assert_eq!(cfg.blocks[&3].instructions.len(), 2);
+
+        assert_eq!(cfg.entry_successors, vec![NodeId::Block(0)]);
+        assert_eq!(cfg.successors(NodeId::Block(0)), &[NodeId::Block(3), NodeId::Block(1)]);
+        assert_eq!(cfg.successors(NodeId::Block(1)), &[NodeId::Block(3), NodeId::Block(2)]);
+        assert_eq!(cfg.successors(NodeId::Block(2)), &[NodeId::Block(4)]);
+        assert_eq!(cfg.successors(NodeId::Block(3)), &[NodeId::Block(4)]);
+        assert_eq!(cfg.successors(NodeId::Block(4)), &[NodeId::Exit]);
+        assert_eq!(cfg.predecessors(NodeId::Block(3)), &[NodeId::Block(0), NodeId::Block(1)]);
+        assert_eq!(cfg.predecessors(NodeId::Block(4)), &[NodeId::Block(2), NodeId::Block(3)]);
+        // the `Return(0)` after the real return can't be reached
+        assert_eq!(cfg.predecessors(NodeId::Block(5)), &[]);
+        assert_eq!(cfg.exit_predecessors, vec![NodeId::Block(4), NodeId::Block(5)]);
+    }
+
+    #[test]
+    fn cfg_round_trips_to_instructions() {
+        assert_eq!(Cfg::from_function(and_function()).into_function(), and_function());
+
+        let empty = FunctionDefinition::Function("main".to_string(), vec![]);
+        let cfg = Cfg::from_function(empty.clone());
+
+        assert_eq!(cfg.entry_successors, vec![NodeId::Exit]);
+        assert_eq!(cfg.into_function(), empty);
+    }
+
+    #[test]
+    fn remove_block_removes_its_edges() {
+        let mut cfg = Cfg::from_function(and_function());
+        cfg.remove_block(3);
+
+        assert_eq!(cfg.successors(NodeId::Block(0)), &[NodeId::Block(1)]);
+        assert_eq!(cfg.predecessors(NodeId::Block(4)), &[NodeId::Block(2)]);
+        assert_eq!(cfg.next_node(2), NodeId::Block(4));
+    }
+
+    #[test]
+    fn cfg_to_dot() {
+        let function = FunctionDefinition::Function("main".to_string(), vec![
+            Instruction::Label("start".to_string()),
+            Instruction::Return(Val::Constant(0)),
+        ]);
+
+        assert_eq!(
+            Cfg::from_function(function).to_dot(),
+            "digraph \"main\" {\n    node [shape=box, fontname=\"monospace\"];\n    entry [label=\"entry\", shape=oval];\n    exit [label=\"exit\", shape=oval];\n    block0 [label=\"Label(\\\"start\\\")\\lReturn(Constant(0))\\l\"];\n    entry -> block0;\n    block0 -> exit;\n}\n"
+        );
+    }
+}
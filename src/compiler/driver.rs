@@ -0,0 +1,53 @@
+//! Runs the compiler from a resolved AST down to the assembly to emit.
+//!
+//! The command line driver and the UI both compile through `compile` and `generate_assembly`,
+//! so they run the same passes in the same order. `compile` stops at the optimized TACKY, which
+//! is all `--tacky` needs, and the backend only runs when the assembly is asked for.
+
+use crate::compiler::assembly::codegen::{gen_with_options, CodegenOptions};
+use crate::compiler::assembly::instruction_fixup::fixup_program;
+use crate::compiler::assembly::register_allocation::allocate_registers;
+use crate::compiler::assembly::replace_pseudos::{replace_pseudos, StackFrameStatistics};
+use crate::compiler::optimization::{optimize, OptimizationOptions, OptimizationStatistics};
+use crate::compiler::tackygen::{emit_tacky_with_options, TackyOptions};
+use crate::storage::assembly::AssemblyProgram;
+use crate::storage::ast::AstProgram;
+use crate::storage::symbol_table::SymbolTable;
+use crate::storage::tacky::Program;
+
+#[derive(Debug, Clone, Default)]
+pub struct CompilerOptions {
+    pub tacky: TackyOptions,
+    pub optimization: OptimizationOptions,
+}
+
+/// What TACKY generation and optimization produced.
+#[derive(Debug)]
+pub struct TackyOutput {
+    /// The TACKY after optimization.
+    pub tacky: Program,
+    pub optimization_statistics: OptimizationStatistics,
+}
+
+/// What the backend produced from the optimized TACKY.
+#[derive(Debug)]
+pub struct AssemblyOutput {
+    pub stack_frame: StackFrameStatistics,
+    pub assembly: AssemblyProgram,
+}
+
+pub fn compile(ast_program: AstProgram, symbols: &SymbolTable, options: &CompilerOptions) -> TackyOutput {
+    let tacky = emit_tacky_with_options(ast_program, symbols, &options.tacky);
+    let (tacky, optimization_statistics) = optimize(tacky, &options.optimization);
+
+    TackyOutput { tacky, optimization_statistics }
+}
+
+pub fn generate_assembly(tacky: Program, options: &CompilerOptions) -> AssemblyOutput {
+    let assembly = gen_with_options(tacky, &CodegenOptions { reduce_strength: options.optimization.reduce_strength });
+    let assembly = if options.optimization.allocate_registers { allocate_registers(assembly) } else { assembly };
+    let (assembly, stack_frame) = replace_pseudos(assembly);
+    let assembly = fixup_program(-stack_frame.frame_size, assembly);
+
+    AssemblyOutput { stack_frame, assembly }
+}
@@ -53,6 +53,9 @@ fn emit_instruction(instruction: AssemblyInstruction) -> String {
         AssemblyInstruction::Idiv(operand) => {
             result.push_str(format!("\tidivl\t{}\n", emit_operand(operand)).as_str());
         }
+        AssemblyInstruction::Imul(operand) => {
+            result.push_str(format!("\timull\t{}\n", emit_operand(operand)).as_str());
+        }
         AssemblyInstruction::Cdq => {
             result.push_str("\tcdq\n");
         }
@@ -74,6 +77,105 @@ fn emit_instruction(instruction: AssemblyInstruction) -> String {
         AssemblyInstruction::Label(label) => {
             result.push_str(format!(".L{}:\n",label).as_str());
         }
+        AssemblyInstruction::InlineAsm(template, outputs, inputs, _) => {
+            let operands = outputs
+                .into_iter()
+                .chain(inputs)
+                .map(|(_, operand)| emit_operand(operand))
+                .collect::<Vec<_>>();
+
+            // the same markers gcc puts around inline asm, to make it easy to find in the output
+            result.push_str("#APP\n");
+            for line in substitute_asm_operands(&template, &operands).lines() {
+                if !line.trim().is_empty() {
+                    result.push_str(format!("\t{}\n", line.trim()).as_str());
+                }
+            }
+            result.push_str("#NO_APP\n");
+        }
+        AssemblyInstruction::Trap(error) => {
+            result.push_str(&emit_trap(&error));
+        }
+        AssemblyInstruction::Push(register) => {
+            result.push_str(format!("\tpushq\t%{}\n", register_name(register, 8)).as_str());
+        }
+        AssemblyInstruction::Pop(register) => {
+            result.push_str(format!("\tpopq\t%{}\n", register_name(register, 8)).as_str());
+        }
+        AssemblyInstruction::TrapCC(condition, error) => {
+            // jump over the trap with a local label, so no unique name is needed
+            result.push_str(format!("\tj{}\t1f\n", emit_negated_condition_code(condition)).as_str());
+            result.push_str(&emit_trap(&error));
+            result.push_str("1:\n");
+        }
+    }
+
+    result
+}
+
+/// Exit status of a program stopped by a runtime check, picked to stand out from the values
+/// test programs usually return.
+pub const RUNTIME_ERROR_EXIT_CODE: i32 = 99;
+
+/// Writes `error` to stderr and exits with [`RUNTIME_ERROR_EXIT_CODE`], using system calls
+/// directly so the program doesn't need libc for it. The message is kept next to the code
+/// in `.rodata`.
+fn emit_trap(error: &str) -> String {
+    let message = format!("{}\n", error);
+    let mut result = String::new();
+
+    result.push_str("\tleaq\t2f(%rip), %rsi\n");
+    result.push_str(format!("\tmovl\t${}, %edx\n", message.len()).as_str());
+    result.push_str("\tmovl\t$2, %edi\n");
+    result.push_str("\tmovl\t$1, %eax\n");
+    result.push_str("\tsyscall\n");
+    result.push_str(format!("\tmovl\t${}, %edi\n", RUNTIME_ERROR_EXIT_CODE).as_str());
+    result.push_str("\tmovl\t$231, %eax\n");
+    result.push_str("\tsyscall\n");
+    result.push_str("\t.pushsection .rodata\n");
+    result.push_str("2:\n");
+    result.push_str(format!("\t.ascii\t\"{}\"\n", escape_ascii(&message)).as_str());
+    result.push_str("\t.popsection\n");
+
+    result
+}
+
+/// Escapes `text` for an `.ascii` directive.
+fn escape_ascii(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'"' => "\\\"".to_string(),
+            b'\\' => "\\\\".to_string(),
+            b' '..=b'~' => (byte as char).to_string(),
+            _ => format!("\\{:03o}", byte),
+        })
+        .collect()
+}
+
+/// Replaces `%N` with the N-th operand and `%%` with `%`.
+///
+/// Semantic analysis already made sure every reference is in range.
+fn substitute_asm_operands(template: &str, operands: &[String]) -> String {
+    let mut result = String::new();
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            result.push(c);
+            continue;
+        }
+
+        if chars.next_if_eq(&'%').is_some() {
+            result.push('%');
+            continue;
+        }
+
+        let mut digits = String::new();
+        while let Some(digit) = chars.next_if(|c| c.is_ascii_digit()) {
+            digits.push(digit);
+        }
+
+        result.push_str(&operands[digits.parse::<usize>().unwrap()]);
     }
 
     result
@@ -91,15 +193,15 @@ fn emit_binary_op(bin_op: AssemblyBinaryOp) -> String {
         AssemblyBinaryOp::Add => "addl".to_string(),
         AssemblyBinaryOp::Sub => "subl".to_string(),
         AssemblyBinaryOp::Mult => "imull".to_string(),
+        AssemblyBinaryOp::Sal => "sall".to_string(),
+        AssemblyBinaryOp::Sar => "sarl".to_string(),
+        AssemblyBinaryOp::Shr => "shrl".to_string(),
     }
 }
 
 fn emit_operand(operand: AssemblyOperand) -> String {
     match operand {
-        AssemblyOperand::Reg(AssemblyRegister::AX) => "%eax".to_string(),
-        AssemblyOperand::Reg(AssemblyRegister::DX) => "%edx".to_string(),
-        AssemblyOperand::Reg(AssemblyRegister::R10) => "%r10d".to_string(),
-        AssemblyOperand::Reg(AssemblyRegister::R11) => "%r11d".to_string(),
+        AssemblyOperand::Reg(register) => format!("%{}", register_name(register, 4)),
         AssemblyOperand::Stack(num) => format!("{}(%rbp)", num),
         AssemblyOperand::Imm(num) => format!("${}", num),
         _ => unreachable!()
@@ -108,13 +210,45 @@ fn emit_operand(operand: AssemblyOperand) -> String {
 
 fn emit_one_byte_operand(operand: AssemblyOperand) -> String {
     match operand {
-        AssemblyOperand::Reg(AssemblyRegister::AX) => "%al".to_string(),
-        AssemblyOperand::Reg(AssemblyRegister::DX) => "%dl".to_string(),
-        AssemblyOperand::Reg(AssemblyRegister::R10) => "%r10b".to_string(),
-        AssemblyOperand::Reg(AssemblyRegister::R11) => "%r11b".to_string(),
-        AssemblyOperand::Stack(num) => format!("{}(%rbp)", num),
-        AssemblyOperand::Imm(num) => format!("${}", num),
-        _ => unreachable!()
+        AssemblyOperand::Reg(register) => format!("%{}", register_name(register, 1)),
+        other => emit_operand(other),
+    }
+}
+
+/// The name of the `size` byte part of `register`, 8, 4 or 1.
+fn register_name(register: AssemblyRegister, size: usize) -> String {
+    let legacy = |name: &str| match size {
+        8 => format!("r{}", name),
+        4 => format!("e{}", name),
+        _ => name.to_string(),
+    };
+    let numbered = |number: u8| match size {
+        8 => format!("r{}", number),
+        4 => format!("r{}d", number),
+        _ => format!("r{}b", number),
+    };
+
+    match register {
+        AssemblyRegister::AX if size == 1 => "al".to_string(),
+        AssemblyRegister::BX if size == 1 => "bl".to_string(),
+        AssemblyRegister::CX if size == 1 => "cl".to_string(),
+        AssemblyRegister::DX if size == 1 => "dl".to_string(),
+        AssemblyRegister::SI if size == 1 => "sil".to_string(),
+        AssemblyRegister::DI if size == 1 => "dil".to_string(),
+        AssemblyRegister::AX => legacy("ax"),
+        AssemblyRegister::BX => legacy("bx"),
+        AssemblyRegister::CX => legacy("cx"),
+        AssemblyRegister::DX => legacy("dx"),
+        AssemblyRegister::SI => legacy("si"),
+        AssemblyRegister::DI => legacy("di"),
+        AssemblyRegister::R8 => numbered(8),
+        AssemblyRegister::R9 => numbered(9),
+        AssemblyRegister::R10 => numbered(10),
+        AssemblyRegister::R11 => numbered(11),
+        AssemblyRegister::R12 => numbered(12),
+        AssemblyRegister::R13 => numbered(13),
+        AssemblyRegister::R14 => numbered(14),
+        AssemblyRegister::R15 => numbered(15),
     }
 }
 
@@ -126,5 +260,18 @@ fn emit_condition_code(condition: AssemblyCondition) -> String {
         AssemblyCondition::LE => "le".to_string(),
         AssemblyCondition::G => "g".to_string(),
         AssemblyCondition::GE => "ge".to_string(),
+        AssemblyCondition::O => "o".to_string(),
+    }
+}
+
+fn emit_negated_condition_code(condition: AssemblyCondition) -> String {
+    match condition {
+        AssemblyCondition::E => "ne".to_string(),
+        AssemblyCondition::NE => "e".to_string(),
+        AssemblyCondition::L => "ge".to_string(),
+        AssemblyCondition::LE => "g".to_string(),
+        AssemblyCondition::G => "le".to_string(),
+        AssemblyCondition::GE => "l".to_string(),
+        AssemblyCondition::O => "no".to_string(),
     }
 }
\ No newline at end of file
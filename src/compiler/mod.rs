@@ -3,6 +3,10 @@ pub mod token;
 pub mod parser;
 pub mod emit;
 pub mod tackygen;
+pub mod name_generator;
+pub mod optimization;
+pub mod cfg;
+pub mod driver;
 pub mod assembly;
 pub mod semantics;
-
+pub mod standard;
@@ -0,0 +1,89 @@
+//! Names the compiler makes up, like temporaries and jump labels.
+//!
+//! Every compilation owns its own generator, so compiling the same program twice produces the
+//! same names, no matter how many compilations ran before or run at the same time.
+
+use std::collections::{HashMap, HashSet};
+use crate::storage::symbol_table::SymbolTable;
+
+#[derive(Debug, Default)]
+pub struct NameGenerator {
+    temporaries: usize,
+    labels: HashMap<String, usize>,
+    /// Names the program's variables already have.
+    reserved: HashSet<String>,
+}
+
+impl NameGenerator {
+    pub fn new() -> Self {
+        NameGenerator { temporaries: 0, labels: HashMap::new(), reserved: HashSet::new() }
+    }
+
+    /// A generator whose temporaries stay clear of the variables in `symbols`, resolution
+    /// renames a variable called `tmp` to `tmp.0` as well.
+    pub fn avoiding(symbols: &SymbolTable) -> Self {
+        NameGenerator { reserved: symbols.iter().map(|symbol| symbol.unique_name.clone()).collect(), ..NameGenerator::new() }
+    }
+
+    /// A fresh temporary variable, `tmp.0`, `tmp.1`, ... The dot keeps it apart from C
+    /// identifiers, and names a variable already has are skipped.
+    pub fn temporary(&mut self) -> String {
+        loop {
+            let name = format!("tmp.{}", self.temporaries);
+            self.temporaries += 1;
+            if !self.reserved.contains(&name) {
+                return name;
+            }
+        }
+    }
+
+    /// The next number for labels starting with `prefix`, counted separately per prefix so
+    /// related labels (`and_false0`, `and_false_end0`) can share one.
+    pub fn label_id(&mut self, prefix: &str) -> usize {
+        let counter = self.labels.entry(prefix.to_string()).or_insert(0);
+        let id = *counter;
+        *counter += 1;
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::name_generator::NameGenerator;
+    use crate::storage::ast::AstType;
+    use crate::storage::position::Position;
+    use crate::storage::symbol_table::{StorageClass, Symbol, SymbolTable};
+
+    #[test]
+    fn names_are_counted_per_generator() {
+        let mut names = NameGenerator::new();
+
+        assert_eq!(names.temporary(), "tmp.0");
+        assert_eq!(names.temporary(), "tmp.1");
+        assert_eq!(names.label_id("and"), 0);
+        assert_eq!(names.label_id("or"), 0);
+        assert_eq!(names.label_id("and"), 1);
+
+        let mut other = NameGenerator::new();
+
+        assert_eq!(other.temporary(), "tmp.0");
+        assert_eq!(other.label_id("and"), 0);
+    }
+
+    #[test]
+    fn temporaries_skip_variable_names() {
+        let mut symbols = SymbolTable::new();
+        symbols.insert(Symbol {
+            name: "tmp".to_string(),
+            unique_name: "tmp.0".to_string(),
+            symbol_type: AstType::Int,
+            storage_class: StorageClass::Automatic,
+            scope_depth: 1,
+            position: Position::default(),
+        });
+        let mut names = NameGenerator::avoiding(&symbols);
+
+        assert_eq!(names.temporary(), "tmp.1");
+        assert_eq!(names.temporary(), "tmp.2");
+    }
+}
@@ -0,0 +1,182 @@
+//! Rewrites instructions whose result follows from algebra alone.
+//!
+//! Identities like `x + 0`, `x * 1` or `x - x` become copies, whatever `x` holds. None of them
+//! can overflow or divide by zero, so checked instructions are simplified too.
+//!
+//! Within a block the instruction that computed each variable is remembered, which catches
+//! operations that undo each other: `-(-x)` and `~~x` are `x`, `!!x` is `x != 0`, and a jump
+//! on `!x` or on `x != 0` can test `x` itself.
+
+use std::collections::HashMap;
+use crate::compiler::cfg::Cfg;
+use crate::compiler::optimization::liveness::uses_and_definitions;
+use crate::storage::tacky::{BinaryOp, Instruction, Program, UnaryOp, Val};
+
+pub fn simplify_algebra(program: Program) -> Program {
+    match program {
+        Program::Program(function) => {
+            let mut cfg = Cfg::from_function(function);
+
+            for block in cfg.blocks.values_mut() {
+                // the instruction that last wrote each variable, while its operand is unchanged
+                let mut computed_by: HashMap<String, Instruction> = HashMap::new();
+
+                for instruction in block.instructions.iter_mut() {
+                    *instruction = simplify_instruction(instruction, &computed_by);
+
+                    for variable in uses_and_definitions(instruction).1 {
+                        computed_by.retain(|dst, computation| *dst != variable && !reads(computation, &variable));
+
+                        if matches!(instruction, Instruction::Unary(..) | Instruction::Binary(..)) && !reads(instruction, &variable) {
+                            computed_by.insert(variable, instruction.clone());
+                        }
+                    }
+                }
+            }
+
+            Program::Program(cfg.into_function())
+        }
+    }
+}
+
+fn simplify_instruction(instruction: &Instruction, computed_by: &HashMap<String, Instruction>) -> Instruction {
+    let computation = |val: &Val| match val {
+        Val::Var(name) => computed_by.get(name),
+        Val::Constant(_) => None,
+    };
+
+    match instruction {
+        Instruction::Binary(bin_op, src1, src2, dst) | Instruction::CheckedBinary(bin_op, src1, src2, dst, _) => {
+            match simplify_binary(bin_op, src1, src2) {
+                Some(value) => Instruction::Copy(value, dst.clone()),
+                None => instruction.clone(),
+            }
+        }
+        Instruction::Unary(un_op @ (UnaryOp::Negate | UnaryOp::Complement), src, dst) => match computation(src) {
+            Some(Instruction::Unary(inner_op, inner_src, _)) if inner_op == un_op => Instruction::Copy(inner_src.clone(), dst.clone()),
+            _ => instruction.clone(),
+        },
+        Instruction::Unary(UnaryOp::Not, src, dst) => match computation(src) {
+            Some(Instruction::Unary(UnaryOp::Not, inner_src, _)) => {
+                Instruction::Binary(BinaryOp::NotEqual, inner_src.clone(), Val::Constant(0), dst.clone())
+            }
+            _ => instruction.clone(),
+        },
+        Instruction::JumpIfZero(condition, target) => match computation(condition) {
+            Some(Instruction::Unary(UnaryOp::Not, src, _)) => Instruction::JumpIfNotZero(src.clone(), target.clone()),
+            Some(Instruction::Binary(BinaryOp::NotEqual, src, Val::Constant(0), _)) => Instruction::JumpIfZero(src.clone(), target.clone()),
+            _ => instruction.clone(),
+        },
+        Instruction::JumpIfNotZero(condition, target) => match computation(condition) {
+            Some(Instruction::Unary(UnaryOp::Not, src, _)) => Instruction::JumpIfZero(src.clone(), target.clone()),
+            Some(Instruction::Binary(BinaryOp::NotEqual, src, Val::Constant(0), _)) => Instruction::JumpIfNotZero(src.clone(), target.clone()),
+            _ => instruction.clone(),
+        },
+        _ => instruction.clone(),
+    }
+}
+
+/// The value of `src1 bin_op src2` when an identity gives it without computing anything.
+fn simplify_binary(bin_op: &BinaryOp, src1: &Val, src2: &Val) -> Option<Val> {
+    let same_variable = matches!(src1, Val::Var(_)) && src1 == src2;
+
+    match (bin_op, src1, src2) {
+        (BinaryOp::Add, x, Val::Constant(0)) | (BinaryOp::Add, Val::Constant(0), x) => Some(x.clone()),
+        (BinaryOp::Subtract, x, Val::Constant(0)) => Some(x.clone()),
+        (BinaryOp::Subtract, _, _) if same_variable => Some(Val::Constant(0)),
+        (BinaryOp::Multiply, x, Val::Constant(1)) | (BinaryOp::Multiply, Val::Constant(1), x) => Some(x.clone()),
+        (BinaryOp::Multiply, _, Val::Constant(0)) | (BinaryOp::Multiply, Val::Constant(0), _) => Some(Val::Constant(0)),
+        (BinaryOp::Divide, x, Val::Constant(1)) => Some(x.clone()),
+        (BinaryOp::Remainder, _, Val::Constant(1)) => Some(Val::Constant(0)),
+        (BinaryOp::Equal | BinaryOp::LessOrEqual | BinaryOp::GreaterOrEqual, _, _) if same_variable => Some(Val::Constant(1)),
+        (BinaryOp::NotEqual | BinaryOp::LessThan | BinaryOp::GreaterThan, _, _) if same_variable => Some(Val::Constant(0)),
+        _ => None,
+    }
+}
+
+fn reads(instruction: &Instruction, variable: &str) -> bool {
+    uses_and_definitions(instruction).0.iter().any(|used| used == variable)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::optimization::algebraic_simplification::simplify_algebra;
+    use crate::compiler::optimization::test_helpers::{run_pass, var};
+    use crate::storage::tacky::{BinaryOp, Instruction, UnaryOp, Val};
+
+    fn simplify(instructions: Vec<Instruction>) -> Vec<Instruction> {
+        run_pass(simplify_algebra, instructions)
+    }
+
+    #[test]
+    fn simplify_identities() {
+        assert_eq!(
+            simplify(vec![
+                Instruction::Binary(BinaryOp::Add, var("x.0"), Val::Constant(0), var("tmp.0")),
+                Instruction::Binary(BinaryOp::Multiply, Val::Constant(1), var("x.0"), var("tmp.1")),
+                Instruction::Binary(BinaryOp::Multiply, var("x.0"), Val::Constant(0), var("tmp.2")),
+                Instruction::CheckedBinary(BinaryOp::Subtract, var("x.0"), var("x.0"), var("tmp.3"), "overflow".to_string()),
+                Instruction::Binary(BinaryOp::LessOrEqual, var("x.0"), var("x.0"), var("tmp.4")),
+                Instruction::Binary(BinaryOp::Divide, var("x.0"), Val::Constant(2), var("tmp.5")),
+            ]),
+            vec![
+                Instruction::Copy(var("x.0"), var("tmp.0")),
+                Instruction::Copy(var("x.0"), var("tmp.1")),
+                Instruction::Copy(Val::Constant(0), var("tmp.2")),
+                Instruction::Copy(Val::Constant(0), var("tmp.3")),
+                Instruction::Copy(Val::Constant(1), var("tmp.4")),
+                Instruction::Binary(BinaryOp::Divide, var("x.0"), Val::Constant(2), var("tmp.5")),
+            ]
+        );
+    }
+
+    #[test]
+    fn simplify_double_negation() {
+        assert_eq!(
+            simplify(vec![
+                Instruction::Unary(UnaryOp::Negate, var("x.0"), var("tmp.0")),
+                Instruction::Unary(UnaryOp::Negate, var("tmp.0"), var("tmp.1")),
+                Instruction::Unary(UnaryOp::Not, var("x.0"), var("tmp.2")),
+                Instruction::Unary(UnaryOp::Not, var("tmp.2"), var("tmp.3")),
+                Instruction::Return(var("tmp.3")),
+            ]),
+            vec![
+                Instruction::Unary(UnaryOp::Negate, var("x.0"), var("tmp.0")),
+                Instruction::Copy(var("x.0"), var("tmp.1")),
+                Instruction::Unary(UnaryOp::Not, var("x.0"), var("tmp.2")),
+                Instruction::Binary(BinaryOp::NotEqual, var("x.0"), Val::Constant(0), var("tmp.3")),
+                Instruction::Return(var("tmp.3")),
+            ]
+        );
+
+        // x changed in between, so -tmp.0 is not x any more
+        let instructions = vec![
+            Instruction::Unary(UnaryOp::Negate, var("x.0"), var("tmp.0")),
+            Instruction::Copy(Val::Constant(5), var("x.0")),
+            Instruction::Unary(UnaryOp::Negate, var("tmp.0"), var("tmp.1")),
+            Instruction::Return(var("tmp.1")),
+        ];
+        assert_eq!(simplify(instructions.clone()), instructions);
+    }
+
+    #[test]
+    fn simplify_conditions_of_jumps() {
+        // `!!x && y` tests x directly
+        assert_eq!(
+            simplify(vec![
+                Instruction::Unary(UnaryOp::Not, var("x.0"), var("tmp.0")),
+                Instruction::Unary(UnaryOp::Not, var("tmp.0"), var("tmp.1")),
+                Instruction::JumpIfZero(var("tmp.1"), "and_false0".to_string()),
+                Instruction::Unary(UnaryOp::Not, var("y.0"), var("tmp.2")),
+                Instruction::JumpIfNotZero(var("tmp.2"), "and_false0".to_string()),
+                Instruction::Label("and_false0".to_string()),
+            ])[2..],
+            [
+                Instruction::JumpIfZero(var("x.0"), "and_false0".to_string()),
+                Instruction::Unary(UnaryOp::Not, var("y.0"), var("tmp.2")),
+                Instruction::JumpIfZero(var("y.0"), "and_false0".to_string()),
+                Instruction::Label("and_false0".to_string()),
+            ]
+        );
+    }
+}
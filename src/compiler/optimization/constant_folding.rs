@@ -0,0 +1,216 @@
+//! Evaluates TACKY instructions whose operands are all constants.
+//!
+//! Arithmetic wraps around like the generated `addl`/`imull` do, so folding never changes
+//! what the program computes. Operations that are undefined no matter what, division by zero
+//! and `INT_MIN / -1`, are left alone so they still fail at run time. The checked operations
+//! of `-fsanitize=undefined` are only folded when they can't fail.
+
+use crate::storage::tacky::{BinaryOp, FunctionDefinition, Instruction, Program, UnaryOp, Val};
+
+pub fn fold_constants(program: Program) -> Program {
+    match program {
+        Program::Program(function) => Program::Program(fold_function(function)),
+    }
+}
+
+fn fold_function(function: FunctionDefinition) -> FunctionDefinition {
+    match function {
+        FunctionDefinition::Function(name, instructions) => {
+            FunctionDefinition::Function(name, instructions.into_iter().filter_map(fold_instruction).collect())
+        }
+    }
+}
+
+/// The folded form of `instruction`, or `None` when it does nothing at all.
+pub fn fold_instruction(instruction: Instruction) -> Option<Instruction> {
+    match instruction {
+        Instruction::Unary(un_op, Val::Constant(src), dst) => Some(Instruction::Copy(Val::Constant(fold_unary(&un_op, src)), dst)),
+        Instruction::Binary(bin_op, Val::Constant(src1), Val::Constant(src2), dst) => match fold_binary(&bin_op, src1, src2) {
+            Some(result) => Some(Instruction::Copy(Val::Constant(result), dst)),
+            None => Some(Instruction::Binary(bin_op, Val::Constant(src1), Val::Constant(src2), dst)),
+        },
+        Instruction::CheckedUnary(un_op, Val::Constant(src), dst, error) => match checked_unary(&un_op, src) {
+            Some(result) => Some(Instruction::Copy(Val::Constant(result), dst)),
+            None => Some(Instruction::CheckedUnary(un_op, Val::Constant(src), dst, error)),
+        },
+        Instruction::CheckedBinary(bin_op, Val::Constant(src1), Val::Constant(src2), dst, error) => match checked_binary(&bin_op, src1, src2) {
+            Some(result) => Some(Instruction::Copy(Val::Constant(result), dst)),
+            None => Some(Instruction::CheckedBinary(bin_op, Val::Constant(src1), Val::Constant(src2), dst, error)),
+        },
+        Instruction::JumpIfZero(Val::Constant(condition), target) => {
+            if condition == 0 { Some(Instruction::Jump(target)) } else { None }
+        }
+        Instruction::JumpIfNotZero(Val::Constant(condition), target) => {
+            if condition != 0 { Some(Instruction::Jump(target)) } else { None }
+        }
+        Instruction::JumpIf(bin_op, Val::Constant(src1), Val::Constant(src2), target) => {
+            if fold_binary(&bin_op, src1, src2) != Some(0) { Some(Instruction::Jump(target)) } else { None }
+        }
+        instruction => Some(instruction),
+    }
+}
+
+fn fold_unary(un_op: &UnaryOp, src: i32) -> i32 {
+    match un_op {
+        UnaryOp::Complement => !src,
+        UnaryOp::Negate => src.wrapping_neg(),
+        UnaryOp::Not => (src == 0) as i32,
+    }
+}
+
+/// Evaluates `bin_op` with wrapping arithmetic, or `None` when the division is undefined.
+fn fold_binary(bin_op: &BinaryOp, src1: i32, src2: i32) -> Option<i32> {
+    match bin_op {
+        BinaryOp::Add => Some(src1.wrapping_add(src2)),
+        BinaryOp::Subtract => Some(src1.wrapping_sub(src2)),
+        BinaryOp::Multiply => Some(src1.wrapping_mul(src2)),
+        // `idivl` faults on both, there is no result to fold to
+        BinaryOp::Divide => src1.checked_div(src2),
+        BinaryOp::Remainder => src1.checked_rem(src2),
+        BinaryOp::Equal => Some((src1 == src2) as i32),
+        BinaryOp::NotEqual => Some((src1 != src2) as i32),
+        BinaryOp::LessThan => Some((src1 < src2) as i32),
+        BinaryOp::LessOrEqual => Some((src1 <= src2) as i32),
+        BinaryOp::GreaterThan => Some((src1 > src2) as i32),
+        BinaryOp::GreaterOrEqual => Some((src1 >= src2) as i32),
+    }
+}
+
+/// Like `fold_unary`, but `None` when the sanitizer would stop the program.
+fn checked_unary(un_op: &UnaryOp, src: i32) -> Option<i32> {
+    match un_op {
+        UnaryOp::Negate => src.checked_neg(),
+        _ => Some(fold_unary(un_op, src)),
+    }
+}
+
+/// Like `fold_binary`, but `None` when the sanitizer would stop the program.
+fn checked_binary(bin_op: &BinaryOp, src1: i32, src2: i32) -> Option<i32> {
+    match bin_op {
+        BinaryOp::Add => src1.checked_add(src2),
+        BinaryOp::Subtract => src1.checked_sub(src2),
+        BinaryOp::Multiply => src1.checked_mul(src2),
+        _ => fold_binary(bin_op, src1, src2),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::optimization::constant_folding::{checked_binary, checked_unary, fold_binary, fold_constants, fold_unary};
+    use crate::compiler::optimization::test_helpers::run_pass;
+    use crate::storage::tacky::{BinaryOp, Instruction, UnaryOp, Val};
+
+    const MIN: i32 = i32::MIN;
+    const MAX: i32 = i32::MAX;
+
+    fn fold(instructions: Vec<Instruction>) -> Vec<Instruction> {
+        run_pass(fold_constants, instructions)
+    }
+
+    fn tmp() -> Val {
+        Val::Var("tmp.0".to_string())
+    }
+
+    #[test]
+    fn fold_unary_ops() {
+        assert_eq!(fold_unary(&UnaryOp::Complement, 0), -1);
+        assert_eq!(fold_unary(&UnaryOp::Complement, MAX), MIN);
+        assert_eq!(fold_unary(&UnaryOp::Complement, MIN), MAX);
+        assert_eq!(fold_unary(&UnaryOp::Negate, 5), -5);
+        assert_eq!(fold_unary(&UnaryOp::Negate, MAX), MIN + 1);
+        assert_eq!(fold_unary(&UnaryOp::Negate, MIN), MIN);
+        assert_eq!(fold_unary(&UnaryOp::Not, 0), 1);
+        assert_eq!(fold_unary(&UnaryOp::Not, MIN), 0);
+        assert_eq!(fold_unary(&UnaryOp::Not, -1), 0);
+    }
+
+    #[test]
+    fn fold_arithmetic_ops() {
+        assert_eq!(fold_binary(&BinaryOp::Add, 2, 3), Some(5));
+        assert_eq!(fold_binary(&BinaryOp::Add, MAX, 1), Some(MIN));
+        assert_eq!(fold_binary(&BinaryOp::Subtract, 2, 3), Some(-1));
+        assert_eq!(fold_binary(&BinaryOp::Subtract, MIN, 1), Some(MAX));
+        assert_eq!(fold_binary(&BinaryOp::Multiply, -4, 3), Some(-12));
+        assert_eq!(fold_binary(&BinaryOp::Multiply, MAX, 2), Some(-2));
+        assert_eq!(fold_binary(&BinaryOp::Multiply, MIN, -1), Some(MIN));
+
+        // division truncates toward zero and the remainder takes the dividend's sign
+        assert_eq!(fold_binary(&BinaryOp::Divide, 7, 2), Some(3));
+        assert_eq!(fold_binary(&BinaryOp::Divide, -7, 2), Some(-3));
+        assert_eq!(fold_binary(&BinaryOp::Divide, MIN, 1), Some(MIN));
+        assert_eq!(fold_binary(&BinaryOp::Divide, 1, 0), None);
+        assert_eq!(fold_binary(&BinaryOp::Divide, MIN, -1), None);
+        assert_eq!(fold_binary(&BinaryOp::Remainder, 7, 2), Some(1));
+        assert_eq!(fold_binary(&BinaryOp::Remainder, -7, 2), Some(-1));
+        assert_eq!(fold_binary(&BinaryOp::Remainder, 7, -2), Some(1));
+        assert_eq!(fold_binary(&BinaryOp::Remainder, 1, 0), None);
+        assert_eq!(fold_binary(&BinaryOp::Remainder, MIN, -1), None);
+    }
+
+    #[test]
+    fn fold_relational_ops() {
+        let cases = [
+            (BinaryOp::Equal, [0, 1, 0]),
+            (BinaryOp::NotEqual, [1, 0, 1]),
+            (BinaryOp::LessThan, [1, 0, 0]),
+            (BinaryOp::LessOrEqual, [1, 1, 0]),
+            (BinaryOp::GreaterThan, [0, 0, 1]),
+            (BinaryOp::GreaterOrEqual, [0, 1, 1]),
+        ];
+
+        for (bin_op, [less, equal, greater]) in cases {
+            assert_eq!(fold_binary(&bin_op, MIN, MAX), Some(less), "{:?}", bin_op);
+            assert_eq!(fold_binary(&bin_op, MIN, MIN), Some(equal), "{:?}", bin_op);
+            assert_eq!(fold_binary(&bin_op, 0, -1), Some(greater), "{:?}", bin_op);
+        }
+    }
+
+    #[test]
+    fn fold_checked_ops_only_when_they_cannot_fail() {
+        assert_eq!(checked_unary(&UnaryOp::Negate, MAX), Some(-MAX));
+        assert_eq!(checked_unary(&UnaryOp::Negate, MIN), None);
+        assert_eq!(checked_unary(&UnaryOp::Complement, MIN), Some(MAX));
+        assert_eq!(checked_binary(&BinaryOp::Add, MAX - 1, 1), Some(MAX));
+        assert_eq!(checked_binary(&BinaryOp::Add, MAX, 1), None);
+        assert_eq!(checked_binary(&BinaryOp::Subtract, MIN, 1), None);
+        assert_eq!(checked_binary(&BinaryOp::Multiply, MIN, -1), None);
+        assert_eq!(checked_binary(&BinaryOp::Divide, 6, 3), Some(2));
+    }
+
+    #[test]
+    fn fold_instructions() {
+        let error = "main.c:1:1: runtime error: signed integer overflow".to_string();
+
+        assert_eq!(
+            fold(vec![
+                Instruction::Unary(UnaryOp::Negate, Val::Constant(2), tmp()),
+                Instruction::Binary(BinaryOp::Add, Val::Constant(2), Val::Constant(3), tmp()),
+                Instruction::Binary(BinaryOp::Add, Val::Constant(2), tmp(), tmp()),
+                Instruction::Binary(BinaryOp::Divide, Val::Constant(1), Val::Constant(0), tmp()),
+                Instruction::CheckedBinary(BinaryOp::Add, Val::Constant(MAX), Val::Constant(1), tmp(), error.clone()),
+                Instruction::CheckedUnary(UnaryOp::Negate, Val::Constant(1), tmp(), error.clone()),
+                Instruction::JumpIfZero(Val::Constant(0), "a".to_string()),
+                Instruction::JumpIfZero(Val::Constant(7), "b".to_string()),
+                Instruction::JumpIfNotZero(Val::Constant(0), "c".to_string()),
+                Instruction::JumpIfNotZero(Val::Constant(-1), "d".to_string()),
+                Instruction::JumpIf(BinaryOp::LessThan, Val::Constant(1), Val::Constant(2), "f".to_string()),
+                Instruction::JumpIf(BinaryOp::Equal, Val::Constant(1), Val::Constant(2), "g".to_string()),
+                Instruction::JumpIfZero(tmp(), "e".to_string()),
+                Instruction::Return(tmp()),
+            ]),
+            vec![
+                Instruction::Copy(Val::Constant(-2), tmp()),
+                Instruction::Copy(Val::Constant(5), tmp()),
+                Instruction::Binary(BinaryOp::Add, Val::Constant(2), tmp(), tmp()),
+                Instruction::Binary(BinaryOp::Divide, Val::Constant(1), Val::Constant(0), tmp()),
+                Instruction::CheckedBinary(BinaryOp::Add, Val::Constant(MAX), Val::Constant(1), tmp(), error),
+                Instruction::Copy(Val::Constant(-1), tmp()),
+                Instruction::Jump("a".to_string()),
+                Instruction::Jump("d".to_string()),
+                Instruction::Jump("f".to_string()),
+                Instruction::JumpIfZero(tmp(), "e".to_string()),
+                Instruction::Return(tmp()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,299 @@
+//! Finds the variables that hold the same constant on every path, and the branches that can't
+//! be taken because of it.
+//!
+//! Each variable is tracked on a lattice: unknown while no path that sets it was seen yet, a
+//! constant while every path seen so far sets it to that constant, and overdefined once it
+//! might hold anything. Blocks are only visited once an edge into them is known to be taken,
+//! so a branch on a constant never lets the other side's values into the join. That is what
+//! makes `0 && b` or `1 || b` fold completely: the side that sets the result to the other
+//! constant is never visited, so the `and_false_end`/`or_false_end` join sees one constant.
+//!
+//! Afterwards the constants replace the variables, the instructions are folded and the blocks
+//! no taken edge leads to are removed.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use crate::compiler::cfg::{Cfg, NodeId};
+use crate::compiler::optimization::constant_folding::fold_instruction;
+use crate::compiler::optimization::liveness::uses_and_definitions;
+use crate::compiler::optimization::ssa::map_uses;
+use crate::storage::tacky::{Instruction, Program, Val};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LatticeValue {
+    Unknown,
+    Constant(i32),
+    Overdefined,
+}
+
+impl LatticeValue {
+    /// The value of a variable where paths with `self` and `other` join.
+    fn meet(self, other: LatticeValue) -> LatticeValue {
+        match (self, other) {
+            (LatticeValue::Unknown, value) | (value, LatticeValue::Unknown) => value,
+            (LatticeValue::Constant(first), LatticeValue::Constant(second)) if first == second => self,
+            _ => LatticeValue::Overdefined,
+        }
+    }
+}
+
+/// The values of the variables at some point, variables that are missing are unknown.
+type Values = HashMap<String, LatticeValue>;
+
+pub fn propagate_constants(program: Program) -> Program {
+    match program {
+        Program::Program(function) => {
+            let mut cfg = Cfg::from_function(function);
+            let values_in = analyze_constants(&cfg);
+
+            let never_executed: Vec<usize> = cfg.blocks.keys().copied().filter(|id| !values_in.contains_key(id)).collect();
+            for id in never_executed {
+                cfg.remove_block(id);
+            }
+
+            for (id, block) in cfg.blocks.iter_mut() {
+                let mut values = values_in[id].clone();
+                let mut instructions = vec![];
+
+                for instruction in block.instructions.drain(..) {
+                    if let Some(folded) = fold_instruction(substitute_constants(&instruction, &values)) {
+                        instructions.push(folded);
+                    }
+
+                    transfer(&instruction, &mut values);
+                }
+
+                block.instructions = instructions;
+            }
+
+            Program::Program(cfg.into_function())
+        }
+    }
+}
+
+/// The values at the start of every block some taken edge leads to. Blocks that are missing
+/// never run.
+pub fn analyze_constants(cfg: &Cfg) -> HashMap<usize, Values> {
+    // nothing set the variables before the function starts, they hold whatever is there
+    let entry_values: Values = cfg.blocks
+        .values()
+        .flat_map(|block| block.instructions.iter())
+        .flat_map(|instruction| {
+            let (uses, definitions) = uses_and_definitions(instruction);
+            uses.into_iter().chain(definitions)
+        })
+        .map(|variable| (variable, LatticeValue::Overdefined))
+        .collect();
+
+    let mut values_out: HashMap<NodeId, Values> = HashMap::from([(NodeId::Entry, entry_values)]);
+    let mut values_in: HashMap<usize, Values> = HashMap::new();
+    let mut executable_edges: HashSet<(NodeId, NodeId)> = HashSet::new();
+    let mut worklist: VecDeque<usize> = VecDeque::new();
+
+    for &successor in &cfg.entry_successors {
+        executable_edges.insert((NodeId::Entry, successor));
+        if let NodeId::Block(id) = successor {
+            worklist.push_back(id);
+        }
+    }
+
+    while let Some(id) = worklist.pop_front() {
+        let node = NodeId::Block(id);
+        let block = &cfg.blocks[&id];
+
+        let mut values = Values::new();
+        for predecessor in block.predecessors.iter().filter(|&&predecessor| executable_edges.contains(&(predecessor, node))) {
+            for (variable, value) in &values_out[predecessor] {
+                let met = values.get(variable).copied().unwrap_or(LatticeValue::Unknown).meet(*value);
+                values.insert(variable.clone(), met);
+            }
+        }
+        values_in.insert(id, values.clone());
+
+        for instruction in &block.instructions {
+            transfer(instruction, &mut values);
+        }
+
+        let successors = executable_successors(cfg, id, &values);
+        let changed = values_out.get(&node) != Some(&values);
+        values_out.insert(node, values);
+
+        for successor in successors {
+            let newly_executable = executable_edges.insert((node, successor));
+
+            if let NodeId::Block(successor) = successor {
+                if (newly_executable || changed) && !worklist.contains(&successor) {
+                    worklist.push_back(successor);
+                }
+            }
+        }
+    }
+
+    values_in
+}
+
+/// The successors of block `id` control can go to, given the values at its end.
+fn executable_successors(cfg: &Cfg, id: usize, values: &Values) -> Vec<NodeId> {
+    let block = &cfg.blocks[&id];
+    let condition = match block.instructions.last() {
+        Some(Instruction::JumpIfZero(condition, _)) => Some((value_of(condition, values), true)),
+        Some(Instruction::JumpIfNotZero(condition, _)) => Some((value_of(condition, values), false)),
+        // a comparison is taken when it holds, when it is 1 rather than 0
+        Some(Instruction::JumpIf(bin_op, src1, src2, _)) => match (value_of(src1, values), value_of(src2, values)) {
+            (LatticeValue::Constant(src1), LatticeValue::Constant(src2)) => {
+                let holds = fold_instruction(Instruction::JumpIf(bin_op.clone(), Val::Constant(src1), Val::Constant(src2), String::new())).is_some();
+                Some((LatticeValue::Constant(holds as i32), false))
+            }
+            (LatticeValue::Unknown, _) | (_, LatticeValue::Unknown) => Some((LatticeValue::Unknown, false)),
+            _ => Some((LatticeValue::Overdefined, false)),
+        },
+        _ => None,
+    };
+
+    let Some((condition, jumps_if_zero)) = condition else {
+        return block.successors.clone();
+    };
+
+    let fall_through = cfg.next_node(id);
+    let taken = block.successors.iter().copied().find(|&successor| successor != fall_through).unwrap_or(fall_through);
+
+    match condition {
+        LatticeValue::Unknown => vec![],
+        LatticeValue::Constant(condition) => vec![if (condition == 0) == jumps_if_zero { taken } else { fall_through }],
+        LatticeValue::Overdefined => block.successors.clone(),
+    }
+}
+
+/// Updates `values` to what they are after `instruction`.
+fn transfer(instruction: &Instruction, values: &mut Values) {
+    let substituted = substitute_constants(instruction, values);
+    let (uses, definitions) = uses_and_definitions(&substituted);
+
+    match fold_instruction(substituted) {
+        Some(Instruction::Copy(src, Val::Var(dst))) => {
+            values.insert(dst, value_of(&src, values));
+        }
+        _ => {
+            // the result is only known once all operands are
+            let any_unknown = uses.iter().any(|variable| values.get(variable).is_none_or(|value| *value == LatticeValue::Unknown));
+            let result = if any_unknown { LatticeValue::Unknown } else { LatticeValue::Overdefined };
+
+            for variable in definitions {
+                values.insert(variable, result);
+            }
+        }
+    }
+}
+
+fn value_of(val: &Val, values: &Values) -> LatticeValue {
+    match val {
+        Val::Constant(constant) => LatticeValue::Constant(*constant),
+        Val::Var(name) => values.get(name).copied().unwrap_or(LatticeValue::Unknown),
+    }
+}
+
+/// `instruction` with the variables that hold a constant replaced by it.
+fn substitute_constants(instruction: &Instruction, values: &Values) -> Instruction {
+    let constant_of = |val: &Val| match value_of(val, values) {
+        LatticeValue::Constant(constant) => Val::Constant(constant),
+        _ => val.clone(),
+    };
+
+    match instruction {
+        Instruction::InlineAsm(template, outputs, inputs, clobbers) => {
+            let inputs = inputs
+                .iter()
+                .map(|(constraint, val)| match constant_of(val) {
+                    // a memory-only operand has to stay in memory
+                    Val::Constant(_) if !constraint.contains(['r', 'i', 'g']) => (constraint.clone(), val.clone()),
+                    replaced => (constraint.clone(), replaced),
+                })
+                .collect();
+
+            Instruction::InlineAsm(template.clone(), outputs.clone(), inputs, clobbers.clone())
+        }
+        _ => map_uses(instruction, constant_of),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::optimization::constant_propagation::propagate_constants;
+    use crate::compiler::optimization::test_helpers::{run_pass, var};
+    use crate::storage::tacky::{BinaryOp, Instruction, Val};
+
+    fn propagate(instructions: Vec<Instruction>) -> Vec<Instruction> {
+        run_pass(propagate_constants, instructions)
+    }
+
+    /// `return a && b;` as `emit_tacky` generates it, after `a = 0;` and `b = 7;`.
+    fn and_of(a: i32) -> Vec<Instruction> {
+        vec![
+            Instruction::Copy(Val::Constant(a), var("a.0")),
+            Instruction::Copy(Val::Constant(7), var("b.0")),
+            Instruction::JumpIfZero(var("a.0"), "and_false0".to_string()),
+            Instruction::JumpIfZero(var("b.0"), "and_false0".to_string()),
+            Instruction::Copy(Val::Constant(1), var("tmp.0")),
+            Instruction::Jump("and_false_end0".to_string()),
+            Instruction::Label("and_false0".to_string()),
+            Instruction::Copy(Val::Constant(0), var("tmp.0")),
+            Instruction::Label("and_false_end0".to_string()),
+            Instruction::Return(var("tmp.0")),
+        ]
+    }
+
+    #[test]
+    fn propagate_prunes_constant_branches() {
+        assert_eq!(propagate(and_of(0)), vec![
+            Instruction::Copy(Val::Constant(0), var("a.0")),
+            Instruction::Copy(Val::Constant(7), var("b.0")),
+            Instruction::Jump("and_false0".to_string()),
+            Instruction::Label("and_false0".to_string()),
+            Instruction::Copy(Val::Constant(0), var("tmp.0")),
+            Instruction::Label("and_false_end0".to_string()),
+            Instruction::Return(Val::Constant(0)),
+        ]);
+
+        // both tests pass, so the jumps go and the false side never runs
+        assert_eq!(propagate(and_of(3)), vec![
+            Instruction::Copy(Val::Constant(3), var("a.0")),
+            Instruction::Copy(Val::Constant(7), var("b.0")),
+            Instruction::Copy(Val::Constant(1), var("tmp.0")),
+            Instruction::Jump("and_false_end0".to_string()),
+            Instruction::Label("and_false_end0".to_string()),
+            Instruction::Return(Val::Constant(1)),
+        ]);
+    }
+
+    #[test]
+    fn propagate_merges_values_at_joins() {
+        // x is 5 on both paths, y is 1 on one and 2 on the other
+        let instructions = propagate(vec![
+            Instruction::JumpIfZero(var("c.0"), "else0".to_string()),
+            Instruction::Copy(Val::Constant(5), var("x.0")),
+            Instruction::Copy(Val::Constant(1), var("y.0")),
+            Instruction::Jump("end0".to_string()),
+            Instruction::Label("else0".to_string()),
+            Instruction::Binary(BinaryOp::Add, Val::Constant(2), Val::Constant(3), var("x.0")),
+            Instruction::Copy(Val::Constant(2), var("y.0")),
+            Instruction::Label("end0".to_string()),
+            Instruction::Binary(BinaryOp::Multiply, var("x.0"), var("y.0"), var("tmp.0")),
+            Instruction::Return(var("tmp.0")),
+        ]);
+
+        assert_eq!(instructions[5], Instruction::Copy(Val::Constant(5), var("x.0")));
+        assert_eq!(instructions[8], Instruction::Binary(BinaryOp::Multiply, Val::Constant(5), var("y.0"), var("tmp.0")));
+    }
+
+    #[test]
+    fn propagate_keeps_values_of_inline_asm_and_parameters() {
+        let instructions = vec![
+            Instruction::Copy(Val::Constant(1), var("x.0")),
+            Instruction::InlineAsm("movl $2, %0".to_string(), vec![("=r".to_string(), var("x.0"))], vec![], vec![]),
+            Instruction::Binary(BinaryOp::Add, var("x.0"), var("u.0"), var("tmp.0")),
+            Instruction::Return(var("tmp.0")),
+        ];
+
+        // neither the asm output nor the uninitialized u is known
+        assert_eq!(propagate(instructions.clone()), instructions);
+    }
+}
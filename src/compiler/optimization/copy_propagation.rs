@@ -0,0 +1,266 @@
+//! Replaces variables with the value they were copied from.
+//!
+//! A reaching-copies analysis finds, for every instruction, the copies `dst = src` that hold
+//! there on every path: `dst` was copied from `src` and neither changed since. Reads of `dst`
+//! can then read `src` instead, and a copy that is already known to hold is dropped. This
+//! undoes the chains of `Copy`s that declarations and assignments lower to, so constant
+//! folding can see the constants behind them.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use crate::compiler::cfg::{Cfg, NodeId};
+use crate::storage::tacky::{Instruction, Program, Val};
+
+/// The copies that hold at some point, as `(src, dst)` pairs.
+type ReachingCopies = HashSet<(Val, Val)>;
+
+pub fn propagate_copies(program: Program) -> Program {
+    match program {
+        Program::Program(function) => {
+            let mut cfg = Cfg::from_function(function);
+            let reaching_in = find_reaching_copies(&cfg);
+
+            for (id, block) in cfg.blocks.iter_mut() {
+                let mut reaching = reaching_in[id].clone();
+                let mut instructions = vec![];
+
+                for instruction in block.instructions.drain(..) {
+                    if let Some(rewritten) = rewrite_instruction(&instruction, &reaching) {
+                        instructions.push(rewritten);
+                    }
+
+                    transfer(&instruction, &mut reaching);
+                }
+
+                block.instructions = instructions;
+            }
+
+            Program::Program(cfg.into_function())
+        }
+    }
+}
+
+/// The copies that reach the start of each block.
+///
+/// A copy reaches a block only when it reaches it from every predecessor, so the blocks start
+/// out with every copy in the function and lose them until nothing changes.
+fn find_reaching_copies(cfg: &Cfg) -> HashMap<usize, ReachingCopies> {
+    let all_copies: ReachingCopies = cfg.blocks
+        .values()
+        .flat_map(|block| block.instructions.iter())
+        .filter_map(|instruction| match instruction {
+            Instruction::Copy(src, dst) => Some((src.clone(), dst.clone())),
+            _ => None,
+        })
+        .collect();
+
+    let mut reaching_out: HashMap<usize, ReachingCopies> = cfg.blocks.keys().map(|&id| (id, all_copies.clone())).collect();
+    let mut reaching_in: HashMap<usize, ReachingCopies> = HashMap::new();
+    let mut worklist: VecDeque<usize> = cfg.blocks.keys().copied().collect();
+
+    while let Some(id) = worklist.pop_front() {
+        let mut reaching = meet(cfg, id, &reaching_out);
+        reaching_in.insert(id, reaching.clone());
+
+        for instruction in &cfg.blocks[&id].instructions {
+            transfer(instruction, &mut reaching);
+        }
+
+        if reaching != reaching_out[&id] {
+            reaching_out.insert(id, reaching);
+
+            for successor in cfg.successors(NodeId::Block(id)) {
+                if let NodeId::Block(successor) = successor {
+                    if !worklist.contains(successor) {
+                        worklist.push_back(*successor);
+                    }
+                }
+            }
+        }
+    }
+
+    reaching_in
+}
+
+/// The copies that hold on every edge into block `id`. Nothing holds when the function starts,
+/// nor in a block nothing jumps to.
+fn meet(cfg: &Cfg, id: usize, reaching_out: &HashMap<usize, ReachingCopies>) -> ReachingCopies {
+    let mut predecessors = cfg.predecessors(NodeId::Block(id)).iter();
+
+    let mut reaching = match predecessors.next() {
+        Some(NodeId::Block(predecessor)) => reaching_out[predecessor].clone(),
+        _ => return ReachingCopies::new(),
+    };
+
+    for predecessor in predecessors {
+        match predecessor {
+            NodeId::Block(predecessor) => reaching.retain(|copy| reaching_out[predecessor].contains(copy)),
+            _ => return ReachingCopies::new(),
+        }
+    }
+
+    reaching
+}
+
+/// Updates `reaching` to the copies that hold after `instruction`.
+fn transfer(instruction: &Instruction, reaching: &mut ReachingCopies) {
+    match instruction {
+        Instruction::Copy(src, dst) => {
+            // `x = x`, or `y = x` right after `x = y`, changes nothing
+            if src == dst || reaching.contains(&(dst.clone(), src.clone())) {
+                return;
+            }
+
+            kill(dst, reaching);
+            reaching.insert((src.clone(), dst.clone()));
+        }
+        Instruction::Unary(_, _, dst)
+        | Instruction::Binary(_, _, _, dst)
+        | Instruction::CheckedUnary(_, _, dst, _)
+        | Instruction::CheckedBinary(_, _, _, dst, _) => kill(dst, reaching),
+        Instruction::InlineAsm(_, outputs, _, _) => {
+            for (_, output) in outputs {
+                kill(output, reaching);
+            }
+        }
+        Instruction::Return(_)
+        | Instruction::Jump(_)
+        | Instruction::JumpIfZero(..)
+        | Instruction::JumpIfNotZero(..)
+        | Instruction::JumpIf(..)
+        | Instruction::Label(_)
+        | Instruction::Trap(_) => (),
+    }
+}
+
+/// Forgets every copy from or to `var`, it was just given a new value.
+fn kill(var: &Val, reaching: &mut ReachingCopies) {
+    reaching.retain(|(src, dst)| src != var && dst != var);
+}
+
+/// `instruction` reading the copied values, or `None` when it is a copy that already holds.
+fn rewrite_instruction(instruction: &Instruction, reaching: &ReachingCopies) -> Option<Instruction> {
+    let replace = |val: &Val| replace_operand(val, reaching);
+
+    let rewritten = match instruction {
+        Instruction::Copy(src, dst) => {
+            if src == dst || reaching.contains(&(src.clone(), dst.clone())) || reaching.contains(&(dst.clone(), src.clone())) {
+                return None;
+            }
+
+            Instruction::Copy(replace(src), dst.clone())
+        }
+        Instruction::Return(val) => Instruction::Return(replace(val)),
+        Instruction::Unary(un_op, src, dst) => Instruction::Unary(un_op.clone(), replace(src), dst.clone()),
+        Instruction::Binary(bin_op, src1, src2, dst) => Instruction::Binary(bin_op.clone(), replace(src1), replace(src2), dst.clone()),
+        Instruction::JumpIfZero(condition, target) => Instruction::JumpIfZero(replace(condition), target.clone()),
+        Instruction::JumpIfNotZero(condition, target) => Instruction::JumpIfNotZero(replace(condition), target.clone()),
+        Instruction::JumpIf(bin_op, src1, src2, target) => Instruction::JumpIf(bin_op.clone(), replace(src1), replace(src2), target.clone()),
+        Instruction::CheckedUnary(un_op, src, dst, error) => Instruction::CheckedUnary(un_op.clone(), replace(src), dst.clone(), error.clone()),
+        Instruction::CheckedBinary(bin_op, src1, src2, dst, error) => {
+            Instruction::CheckedBinary(bin_op.clone(), replace(src1), replace(src2), dst.clone(), error.clone())
+        }
+        Instruction::InlineAsm(template, outputs, inputs, clobbers) => {
+            let inputs = inputs
+                .iter()
+                .map(|(constraint, val)| match replace(val) {
+                    // a memory-only operand has to stay in memory
+                    Val::Constant(_) if !constraint.contains(['r', 'i', 'g']) => (constraint.clone(), val.clone()),
+                    replaced => (constraint.clone(), replaced),
+                })
+                .collect();
+
+            Instruction::InlineAsm(template.clone(), outputs.clone(), inputs, clobbers.clone())
+        }
+        Instruction::Jump(_) | Instruction::Label(_) | Instruction::Trap(_) => instruction.clone(),
+    };
+
+    Some(rewritten)
+}
+
+fn replace_operand(val: &Val, reaching: &ReachingCopies) -> Val {
+    if let Val::Var(_) = val {
+        if let Some((src, _)) = reaching.iter().find(|(_, dst)| dst == val) {
+            return src.clone();
+        }
+    }
+
+    val.clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::optimization::constant_folding::fold_constants;
+    use crate::compiler::optimization::copy_propagation::propagate_copies;
+    use crate::compiler::optimization::test_helpers::{instructions, program, var};
+    use crate::storage::tacky::{BinaryOp, Instruction, UnaryOp, Val};
+
+    #[test]
+    fn propagate_copies_enables_folding() {
+        // int a = 2;
+        // int b = a * 3;
+        // return b;
+        let tacky = program(vec![
+            Instruction::Copy(Val::Constant(2), var("a.0")),
+            Instruction::Binary(BinaryOp::Multiply, var("a.0"), Val::Constant(3), var("tmp.0")),
+            Instruction::Copy(var("tmp.0"), var("b.0")),
+            Instruction::Return(var("b.0")),
+        ]);
+
+        let optimized = instructions(propagate_copies(fold_constants(propagate_copies(tacky))));
+
+        assert_eq!(optimized.last(), Some(&Instruction::Return(Val::Constant(6))));
+    }
+
+    #[test]
+    fn propagate_copies_stops_at_redefinitions() {
+        assert_eq!(
+            instructions(propagate_copies(program(vec![
+                Instruction::Copy(var("x.0"), var("y.0")),
+                Instruction::Unary(UnaryOp::Negate, var("x.0"), var("x.0")),
+                Instruction::Return(var("y.0")),
+            ]))),
+            vec![
+                Instruction::Copy(var("x.0"), var("y.0")),
+                Instruction::Unary(UnaryOp::Negate, var("x.0"), var("x.0")),
+                Instruction::Return(var("y.0")),
+            ]
+        );
+    }
+
+    #[test]
+    fn propagate_copies_removes_copies_that_already_hold() {
+        // `a = b = a;` and a plain `a = a;`
+        assert_eq!(
+            instructions(propagate_copies(program(vec![
+                Instruction::Copy(var("a.0"), var("b.0")),
+                Instruction::Copy(var("b.0"), var("a.0")),
+                Instruction::Copy(var("a.0"), var("a.0")),
+                Instruction::Return(var("b.0")),
+            ]))),
+            vec![
+                Instruction::Copy(var("a.0"), var("b.0")),
+                Instruction::Return(var("a.0")),
+            ]
+        );
+    }
+
+    #[test]
+    fn propagate_copies_only_when_every_path_agrees() {
+        // tmp.0 = a ? 1 : 0, where only one path copies 1 into it
+        let tacky = vec![
+            Instruction::Copy(Val::Constant(5), var("b.0")),
+            Instruction::JumpIfZero(var("a.0"), "false".to_string()),
+            Instruction::Copy(Val::Constant(1), var("tmp.0")),
+            Instruction::Jump("end".to_string()),
+            Instruction::Label("false".to_string()),
+            Instruction::Copy(Val::Constant(0), var("tmp.0")),
+            Instruction::Label("end".to_string()),
+            Instruction::Binary(BinaryOp::Add, var("tmp.0"), var("b.0"), var("tmp.1")),
+            Instruction::Return(var("tmp.1")),
+        ];
+
+        let optimized = instructions(propagate_copies(program(tacky)));
+
+        assert_eq!(optimized[7], Instruction::Binary(BinaryOp::Add, var("tmp.0"), Val::Constant(5), var("tmp.1")));
+    }
+}
@@ -0,0 +1,139 @@
+//! Removes instructions that compute values nobody reads.
+//!
+//! An instruction whose destination isn't live right after it is a dead store, as long as
+//! computing the value has no other effect. Checked operations can stop the program and inline
+//! assembly can do anything, so they always stay, and so does a division that could fault
+//! for some value in the ranges of its operands.
+
+use crate::compiler::cfg::Cfg;
+use crate::compiler::optimization::liveness::{analyze_liveness, transfer};
+use crate::compiler::optimization::range_analysis::{self, analyze_ranges, is_safe_division, range_of, Ranges};
+use crate::storage::tacky::{BinaryOp, Instruction, Program, Val};
+
+pub fn eliminate_dead_stores(program: Program) -> Program {
+    match program {
+        Program::Program(function) => {
+            let mut cfg = Cfg::from_function(function);
+            let live_out = analyze_liveness(&cfg);
+            let ranges_in = analyze_ranges(&cfg);
+
+            for (id, block) in cfg.blocks.iter_mut() {
+                let mut live = live_out[id].clone();
+                let mut kept = vec![];
+
+                // the ranges before each instruction, for the divisions
+                let mut ranges = ranges_in.get(id).cloned().unwrap_or_default();
+                let mut ranges_before = vec![];
+                for instruction in &block.instructions {
+                    ranges_before.push(ranges.clone());
+                    range_analysis::transfer(instruction, &mut ranges);
+                }
+
+                // walking backward, so what a removed instruction read doesn't keep anything alive
+                let instructions = std::mem::take(&mut block.instructions);
+                for (instruction, ranges) in instructions.into_iter().zip(ranges_before).rev() {
+                    if let Some(dst) = removable_destination(&instruction, &ranges) {
+                        if !live.contains(dst) {
+                            continue;
+                        }
+                    }
+
+                    transfer(&instruction, &mut live);
+                    kept.push(instruction);
+                }
+
+                kept.reverse();
+                block.instructions = kept;
+            }
+
+            Program::Program(cfg.into_function())
+        }
+    }
+}
+
+/// The variable `instruction` writes, if writing it is all `instruction` does.
+fn removable_destination<'a>(instruction: &'a Instruction, ranges: &Ranges) -> Option<&'a String> {
+    match instruction {
+        Instruction::Binary(BinaryOp::Divide | BinaryOp::Remainder, dividend, divisor, _)
+            if !is_safe_division(range_of(dividend, ranges), range_of(divisor, ranges)) => None,
+        Instruction::Copy(_, Val::Var(dst))
+        | Instruction::Unary(_, _, Val::Var(dst))
+        | Instruction::Binary(_, _, _, Val::Var(dst)) => Some(dst),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::optimization::dead_store_elimination::eliminate_dead_stores;
+    use crate::compiler::optimization::test_helpers::{run_pass, var};
+    use crate::storage::tacky::{BinaryOp, Instruction, UnaryOp, Val};
+
+    fn eliminate(instructions: Vec<Instruction>) -> Vec<Instruction> {
+        run_pass(eliminate_dead_stores, instructions)
+    }
+
+    #[test]
+    fn eliminate_stores_that_are_never_read() {
+        // int a = 2; int b = 6; return 6; after copy propagation
+        assert_eq!(
+            eliminate(vec![
+                Instruction::Copy(Val::Constant(2), var("a.0")),
+                Instruction::Binary(BinaryOp::Multiply, var("a.0"), Val::Constant(3), var("tmp.0")),
+                Instruction::Copy(var("tmp.0"), var("b.0")),
+                Instruction::Return(Val::Constant(6)),
+            ]),
+            vec![Instruction::Return(Val::Constant(6))]
+        );
+
+        // the first store is overwritten before anything reads it
+        assert_eq!(
+            eliminate(vec![
+                Instruction::Copy(Val::Constant(1), var("a.0")),
+                Instruction::Unary(UnaryOp::Negate, var("b.0"), var("a.0")),
+                Instruction::Return(var("a.0")),
+            ]),
+            vec![
+                Instruction::Unary(UnaryOp::Negate, var("b.0"), var("a.0")),
+                Instruction::Return(var("a.0")),
+            ]
+        );
+    }
+
+    #[test]
+    fn eliminate_keeps_side_effects() {
+        let error = "main.c:1:1: runtime error: signed integer overflow".to_string();
+        let instructions = vec![
+            Instruction::CheckedBinary(BinaryOp::Add, var("a.0"), Val::Constant(1), var("tmp.0"), error),
+            Instruction::Binary(BinaryOp::Divide, Val::Constant(1), var("a.0"), var("tmp.1")),
+            Instruction::Binary(BinaryOp::Remainder, var("a.0"), Val::Constant(-1), var("tmp.2")),
+            Instruction::InlineAsm("nop".to_string(), vec![("=r".to_string(), var("tmp.3"))], vec![], vec![]),
+            Instruction::Return(Val::Constant(0)),
+        ];
+
+        assert_eq!(eliminate(instructions.clone()), instructions);
+
+        assert_eq!(
+            eliminate(vec![
+                Instruction::Binary(BinaryOp::Divide, var("a.0"), Val::Constant(2), var("tmp.0")),
+                Instruction::Return(Val::Constant(0)),
+            ]),
+            vec![Instruction::Return(Val::Constant(0))]
+        );
+    }
+
+    #[test]
+    fn eliminate_divisions_the_ranges_prove_safe() {
+        // a % 10 + 20 is between 11 and 29, and the dividend can't be INT_MIN for -1
+        assert_eq!(
+            eliminate(vec![
+                Instruction::Binary(BinaryOp::Remainder, var("a.0"), Val::Constant(10), var("tmp.0")),
+                Instruction::Binary(BinaryOp::Add, var("tmp.0"), Val::Constant(20), var("tmp.1")),
+                Instruction::Binary(BinaryOp::Divide, Val::Constant(100), var("tmp.1"), var("tmp.2")),
+                Instruction::Binary(BinaryOp::Remainder, var("tmp.1"), Val::Constant(-1), var("tmp.3")),
+                Instruction::Return(Val::Constant(0)),
+            ]),
+            vec![Instruction::Return(Val::Constant(0))]
+        );
+    }
+}
@@ -0,0 +1,205 @@
+//! Dominators of the nodes of a CFG.
+//!
+//! A node dominates another when every path from the entry to the other passes through it.
+//! The immediate dominators are found with the iterative algorithm of Cooper, Harvey and
+//! Kennedy, which walks the nodes in reverse postorder until the dominator tree settles.
+
+use std::collections::{HashMap, HashSet};
+use crate::compiler::cfg::{Cfg, NodeId};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dominators {
+    /// The immediate dominator of every node reachable from the entry, except the entry.
+    immediate: HashMap<NodeId, NodeId>,
+    /// The reachable nodes in reverse postorder, the entry first.
+    order: Vec<NodeId>,
+}
+
+impl Dominators {
+    pub fn compute(cfg: &Cfg) -> Self {
+        let order = reverse_postorder(cfg);
+        let position: HashMap<NodeId, usize> = order.iter().enumerate().map(|(index, &node)| (node, index)).collect();
+        let mut immediate: HashMap<NodeId, NodeId> = HashMap::from([(NodeId::Entry, NodeId::Entry)]);
+        let mut changed = true;
+
+        while changed {
+            changed = false;
+
+            for &node in order.iter().skip(1) {
+                let mut processed = cfg.predecessors(node).iter().filter(|predecessor| immediate.contains_key(predecessor));
+                let mut new_immediate = *processed.next().expect("a reachable node has a processed predecessor");
+
+                for &predecessor in processed {
+                    new_immediate = intersect(&immediate, &position, predecessor, new_immediate);
+                }
+
+                if immediate.get(&node) != Some(&new_immediate) {
+                    immediate.insert(node, new_immediate);
+                    changed = true;
+                }
+            }
+        }
+
+        immediate.remove(&NodeId::Entry);
+        Dominators { immediate, order }
+    }
+
+    pub fn immediate_dominator(&self, node: NodeId) -> Option<NodeId> {
+        self.immediate.get(&node).copied()
+    }
+
+    /// Whether every path from the entry to `node` passes through `dominator`, which is true
+    /// for `node` itself.
+    pub fn dominates(&self, dominator: NodeId, node: NodeId) -> bool {
+        let mut current = node;
+
+        loop {
+            if current == dominator {
+                return true;
+            }
+
+            match self.immediate_dominator(current) {
+                Some(parent) => current = parent,
+                None => return false,
+            }
+        }
+    }
+
+    /// The nodes `node` immediately dominates, its children in the dominator tree.
+    pub fn children(&self, node: NodeId) -> Vec<NodeId> {
+        self.order.iter().copied().filter(|&child| self.immediate_dominator(child) == Some(node)).collect()
+    }
+
+    /// The reachable nodes in reverse postorder.
+    pub fn reachable_nodes(&self) -> &[NodeId] {
+        &self.order
+    }
+
+    /// The dominance frontier of every reachable node: the nodes where its dominance ends,
+    /// those it doesn't strictly dominate but that have a predecessor it dominates.
+    pub fn frontiers(&self, cfg: &Cfg) -> HashMap<NodeId, HashSet<NodeId>> {
+        let mut frontiers: HashMap<NodeId, HashSet<NodeId>> = self.order.iter().map(|&node| (node, HashSet::new())).collect();
+
+        for &node in &self.order {
+            let predecessors: Vec<NodeId> = cfg.predecessors(node).iter().copied().filter(|predecessor| frontiers.contains_key(predecessor)).collect();
+
+            if predecessors.len() < 2 {
+                continue;
+            }
+
+            let immediate = self.immediate_dominator(node);
+
+            for predecessor in predecessors {
+                let mut runner = Some(predecessor);
+
+                while runner.is_some() && runner != immediate {
+                    let current = runner.unwrap();
+                    frontiers.get_mut(&current).unwrap().insert(node);
+                    runner = self.immediate_dominator(current);
+                }
+            }
+        }
+
+        frontiers
+    }
+}
+
+fn reverse_postorder(cfg: &Cfg) -> Vec<NodeId> {
+    let mut visited = HashSet::new();
+    let mut postorder = vec![];
+    // (node, whether its successors were pushed already)
+    let mut stack = vec![(NodeId::Entry, false)];
+
+    while let Some((node, expanded)) = stack.pop() {
+        if expanded {
+            postorder.push(node);
+            continue;
+        }
+
+        if !visited.insert(node) {
+            continue;
+        }
+
+        stack.push((node, true));
+        for &successor in cfg.successors(node).iter().rev() {
+            if !visited.contains(&successor) {
+                stack.push((successor, false));
+            }
+        }
+    }
+
+    postorder.reverse();
+    postorder
+}
+
+/// The closest common dominator of `first` and `second`.
+fn intersect(immediate: &HashMap<NodeId, NodeId>, position: &HashMap<NodeId, usize>, first: NodeId, second: NodeId) -> NodeId {
+    let mut first = first;
+    let mut second = second;
+
+    while first != second {
+        while position[&first] > position[&second] {
+            first = immediate[&first];
+        }
+        while position[&second] > position[&first] {
+            second = immediate[&second];
+        }
+    }
+
+    first
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use crate::compiler::cfg::{Cfg, NodeId};
+    use crate::compiler::optimization::dominance::Dominators;
+    use crate::compiler::optimization::test_helpers::var;
+    use crate::storage::tacky::{FunctionDefinition, Instruction, Val};
+
+    /// The diamond of `return a && b;`: block 0 and 1 test, 2 and 3 set the result, 4 returns.
+    fn diamond() -> Cfg {
+        Cfg::from_function(FunctionDefinition::Function("main".to_string(), vec![
+            Instruction::JumpIfZero(var("a.0"), "and_false0".to_string()),
+            Instruction::JumpIfZero(var("b.0"), "and_false0".to_string()),
+            Instruction::Copy(Val::Constant(1), var("tmp.0")),
+            Instruction::Jump("and_false_end0".to_string()),
+            Instruction::Label("and_false0".to_string()),
+            Instruction::Copy(Val::Constant(0), var("tmp.0")),
+            Instruction::Label("and_false_end0".to_string()),
+            Instruction::Return(var("tmp.0")),
+        ]))
+    }
+
+    #[test]
+    fn dominators_of_diamond() {
+        let cfg = diamond();
+        let dominators = Dominators::compute(&cfg);
+
+        assert_eq!(dominators.immediate_dominator(NodeId::Entry), None);
+        assert_eq!(dominators.immediate_dominator(NodeId::Block(0)), Some(NodeId::Entry));
+        assert_eq!(dominators.immediate_dominator(NodeId::Block(1)), Some(NodeId::Block(0)));
+        assert_eq!(dominators.immediate_dominator(NodeId::Block(2)), Some(NodeId::Block(1)));
+        // block 3 is reached from both tests
+        assert_eq!(dominators.immediate_dominator(NodeId::Block(3)), Some(NodeId::Block(0)));
+        assert_eq!(dominators.immediate_dominator(NodeId::Block(4)), Some(NodeId::Block(0)));
+        assert_eq!(dominators.immediate_dominator(NodeId::Exit), Some(NodeId::Block(4)));
+
+        assert!(dominators.dominates(NodeId::Block(0), NodeId::Block(4)));
+        assert!(dominators.dominates(NodeId::Block(4), NodeId::Block(4)));
+        assert!(!dominators.dominates(NodeId::Block(1), NodeId::Block(3)));
+        assert_eq!(dominators.children(NodeId::Block(0)), vec![NodeId::Block(1), NodeId::Block(3), NodeId::Block(4)]);
+    }
+
+    #[test]
+    fn dominance_frontiers_of_diamond() {
+        let cfg = diamond();
+        let frontiers = Dominators::compute(&cfg).frontiers(&cfg);
+
+        assert_eq!(frontiers[&NodeId::Block(0)], HashSet::new());
+        assert_eq!(frontiers[&NodeId::Block(1)], HashSet::from([NodeId::Block(3), NodeId::Block(4)]));
+        assert_eq!(frontiers[&NodeId::Block(2)], HashSet::from([NodeId::Block(4)]));
+        assert_eq!(frontiers[&NodeId::Block(3)], HashSet::from([NodeId::Block(4)]));
+        assert_eq!(frontiers[&NodeId::Block(4)], HashSet::new());
+    }
+}
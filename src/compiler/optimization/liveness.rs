@@ -0,0 +1,161 @@
+//! Which variables are live, still going to be read before they are written again.
+//!
+//! A backward dataflow analysis over the CFG: a variable is live at the end of a block when it
+//! is live at the start of any successor. Nothing is live at the exit, every variable is local
+//! to the function.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use crate::compiler::cfg::{Cfg, NodeId};
+use crate::storage::tacky::{Instruction, Val};
+
+pub type LiveVariables = HashSet<String>;
+
+/// The variables live at the end of every block.
+pub fn analyze_liveness(cfg: &Cfg) -> HashMap<usize, LiveVariables> {
+    let mut live_in: HashMap<usize, LiveVariables> = cfg.blocks.keys().map(|&id| (id, LiveVariables::new())).collect();
+    let mut live_out: HashMap<usize, LiveVariables> = HashMap::new();
+    // going backward through the blocks settles most of them on the first visit
+    let mut worklist: VecDeque<usize> = cfg.blocks.keys().rev().copied().collect();
+
+    while let Some(id) = worklist.pop_front() {
+        let mut live: LiveVariables = cfg.successors(NodeId::Block(id))
+            .iter()
+            .filter_map(|successor| match successor {
+                NodeId::Block(successor) => Some(live_in[successor].iter().cloned()),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+        live_out.insert(id, live.clone());
+
+        for instruction in cfg.blocks[&id].instructions.iter().rev() {
+            transfer(instruction, &mut live);
+        }
+
+        if live != live_in[&id] {
+            live_in.insert(id, live);
+
+            for predecessor in cfg.predecessors(NodeId::Block(id)) {
+                if let NodeId::Block(predecessor) = predecessor {
+                    if !worklist.contains(predecessor) {
+                        worklist.push_back(*predecessor);
+                    }
+                }
+            }
+        }
+    }
+
+    live_out
+}
+
+/// The variables live right after each of `instructions`, given the ones live after the last.
+pub fn live_after_each(instructions: &[Instruction], live_out: &LiveVariables) -> Vec<LiveVariables> {
+    let mut live = live_out.clone();
+    let mut live_after = vec![LiveVariables::new(); instructions.len()];
+
+    for (index, instruction) in instructions.iter().enumerate().rev() {
+        live_after[index] = live.clone();
+        transfer(instruction, &mut live);
+    }
+
+    live_after
+}
+
+/// Updates `live` from the variables live after `instruction` to the ones live before it.
+pub fn transfer(instruction: &Instruction, live: &mut LiveVariables) {
+    let (uses, definitions) = uses_and_definitions(instruction);
+
+    for definition in definitions {
+        live.remove(&definition);
+    }
+
+    live.extend(uses);
+}
+
+/// The variables `instruction` reads and the ones it writes.
+pub fn uses_and_definitions(instruction: &Instruction) -> (Vec<String>, Vec<String>) {
+    let vars = |vals: &[&Val]| vals.iter().filter_map(|val| match val {
+        Val::Var(name) => Some(name.clone()),
+        Val::Constant(_) => None,
+    }).collect::<Vec<String>>();
+
+    match instruction {
+        Instruction::Copy(src, dst)
+        | Instruction::Unary(_, src, dst)
+        | Instruction::CheckedUnary(_, src, dst, _) => (vars(&[src]), vars(&[dst])),
+        Instruction::Binary(_, src1, src2, dst)
+        | Instruction::CheckedBinary(_, src1, src2, dst, _) => (vars(&[src1, src2]), vars(&[dst])),
+        Instruction::Return(val)
+        | Instruction::JumpIfZero(val, _)
+        | Instruction::JumpIfNotZero(val, _) => (vars(&[val]), vec![]),
+        Instruction::JumpIf(_, src1, src2, _) => (vars(&[src1, src2]), vec![]),
+        Instruction::InlineAsm(_, outputs, inputs, _) => {
+            // a read-write `+` output is read as well
+            let read_outputs = outputs.iter().filter(|(constraint, _)| constraint.starts_with('+')).map(|(_, val)| val);
+            let uses = vars(&inputs.iter().map(|(_, val)| val).chain(read_outputs).collect::<Vec<_>>());
+
+            (uses, vars(&outputs.iter().map(|(_, val)| val).collect::<Vec<_>>()))
+        }
+        Instruction::Jump(_) | Instruction::Label(_) | Instruction::Trap(_) => (vec![], vec![]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use crate::compiler::cfg::Cfg;
+    use crate::compiler::optimization::liveness::{analyze_liveness, live_after_each, transfer};
+    use crate::compiler::optimization::test_helpers::var;
+    use crate::storage::tacky::{BinaryOp, FunctionDefinition, Instruction, Val};
+
+    fn names(names: &[&str]) -> HashSet<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn liveness_across_blocks() {
+        // b is read after the branches join, tmp.0 is written on both paths
+        let cfg = Cfg::from_function(FunctionDefinition::Function("main".to_string(), vec![
+            Instruction::Copy(Val::Constant(5), var("b.0")),
+            Instruction::JumpIfZero(var("a.0"), "false".to_string()),
+            Instruction::Copy(Val::Constant(1), var("tmp.0")),
+            Instruction::Jump("end".to_string()),
+            Instruction::Label("false".to_string()),
+            Instruction::Copy(Val::Constant(0), var("tmp.0")),
+            Instruction::Label("end".to_string()),
+            Instruction::Binary(BinaryOp::Add, var("tmp.0"), var("b.0"), var("tmp.1")),
+            Instruction::Return(var("tmp.1")),
+        ]));
+
+        let live_out = analyze_liveness(&cfg);
+
+        assert_eq!(live_out[&0], names(&["b.0"]));
+        assert_eq!(live_out[&1], names(&["b.0", "tmp.0"]));
+        assert_eq!(live_out[&2], names(&["b.0", "tmp.0"]));
+        assert_eq!(live_out[&3], names(&[]));
+
+        assert_eq!(
+            live_after_each(&cfg.blocks[&3].instructions, &live_out[&3]),
+            vec![names(&["tmp.0", "b.0"]), names(&["tmp.1"]), names(&[])]
+        );
+    }
+
+    #[test]
+    fn liveness_of_inline_asm_operands() {
+        let cfg = Cfg::from_function(FunctionDefinition::Function("main".to_string(), vec![
+            Instruction::InlineAsm(
+                "addl %2, %0".to_string(),
+                vec![("+r".to_string(), var("x.0")), ("=r".to_string(), var("y.0"))],
+                vec![("r".to_string(), var("z.0"))],
+                vec![],
+            ),
+            Instruction::Return(var("y.0")),
+        ]));
+
+        let instructions = &cfg.blocks[&0].instructions;
+        let mut live = live_after_each(instructions, &names(&[]))[0].clone();
+        transfer(&instructions[0], &mut live);
+
+        assert_eq!(live, names(&["x.0", "z.0"]));
+    }
+}
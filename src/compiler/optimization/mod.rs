@@ -0,0 +1,251 @@
+//! Optimization passes over TACKY and the loop that runs them.
+//!
+//! The passes feed each other: folding turns conditions into constants, which makes code
+//! unreachable, copy propagation hands folding new constants and leaves stores behind for dead
+//! store elimination, and so do the copies common subexpression elimination leaves.
+//! `optimize` repeats them until none of them finds anything left to do.
+
+use std::fmt;
+use std::str::FromStr;
+use crate::compiler::optimization::algebraic_simplification::simplify_algebra;
+use crate::compiler::optimization::constant_folding::fold_constants;
+use crate::compiler::optimization::constant_propagation::propagate_constants;
+use crate::compiler::optimization::copy_propagation::propagate_copies;
+use crate::compiler::optimization::dead_store_elimination::eliminate_dead_stores;
+use crate::compiler::optimization::range_analysis::fold_ranges;
+use crate::compiler::optimization::unreachable_code::eliminate_unreachable_code;
+use crate::compiler::optimization::value_numbering::eliminate_common_subexpressions;
+use crate::storage::tacky::{FunctionDefinition, Program};
+
+pub mod constant_folding;
+pub mod algebraic_simplification;
+pub mod constant_propagation;
+pub mod range_analysis;
+pub mod unreachable_code;
+pub mod copy_propagation;
+pub mod liveness;
+pub mod dead_store_elimination;
+pub mod dominance;
+pub mod ssa;
+pub mod value_numbering;
+#[cfg(test)]
+pub(crate) mod test_helpers;
+
+type Pass = fn(Program) -> Program;
+
+/// How often the passes repeat at most, in case they keep undoing each other.
+pub const MAX_ITERATIONS: usize = 16;
+
+/// `-O0` optimizes nothing, `-O1` folds constants, simplifies algebra, removes unreachable code,
+/// reduces strength and allocates registers, and `-O2` also propagates constants and copies,
+/// eliminates common subexpressions and removes dead stores.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub enum OptimizationLevel {
+    #[default]
+    O0,
+    O1,
+    O2,
+}
+
+impl FromStr for OptimizationLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "0" => Ok(OptimizationLevel::O0),
+            "1" => Ok(OptimizationLevel::O1),
+            "2" => Ok(OptimizationLevel::O2),
+            _ => Err(format!("unknown optimization level '{}', expected 0, 1 or 2", s)),
+        }
+    }
+}
+
+/// The passes `optimize` runs, and whether code generation reduces strength and allocates
+/// registers.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OptimizationOptions {
+    pub fold_constants: bool,
+    pub simplify_algebra: bool,
+    pub propagate_constants: bool,
+    pub fold_ranges: bool,
+    pub eliminate_unreachable_code: bool,
+    pub propagate_copies: bool,
+    pub eliminate_common_subexpressions: bool,
+    pub eliminate_dead_stores: bool,
+    pub reduce_strength: bool,
+    pub allocate_registers: bool,
+}
+
+impl OptimizationOptions {
+    pub fn for_level(level: OptimizationLevel) -> Self {
+        OptimizationOptions {
+            fold_constants: level >= OptimizationLevel::O1,
+            simplify_algebra: level >= OptimizationLevel::O1,
+            propagate_constants: level >= OptimizationLevel::O2,
+            fold_ranges: level >= OptimizationLevel::O2,
+            eliminate_unreachable_code: level >= OptimizationLevel::O1,
+            propagate_copies: level >= OptimizationLevel::O2,
+            eliminate_common_subexpressions: level >= OptimizationLevel::O2,
+            eliminate_dead_stores: level >= OptimizationLevel::O2,
+            reduce_strength: level >= OptimizationLevel::O1,
+            allocate_registers: level >= OptimizationLevel::O1,
+        }
+    }
+
+    /// The enabled passes in the order they run.
+    fn passes(&self) -> Vec<(&'static str, Pass)> {
+        let passes: [(bool, &'static str, Pass); 8] = [
+            (self.fold_constants, "fold-constants", fold_constants),
+            (self.simplify_algebra, "simplify-algebra", simplify_algebra),
+            (self.propagate_constants, "propagate-constants", propagate_constants),
+            (self.fold_ranges, "fold-ranges", fold_ranges),
+            (self.eliminate_unreachable_code, "eliminate-unreachable-code", eliminate_unreachable_code),
+            (self.propagate_copies, "propagate-copies", propagate_copies),
+            (self.eliminate_common_subexpressions, "eliminate-common-subexpressions", eliminate_common_subexpressions),
+            (self.eliminate_dead_stores, "eliminate-dead-stores", eliminate_dead_stores),
+        ];
+
+        passes.into_iter().filter(|(enabled, _, _)| *enabled).map(|(_, name, pass)| (name, pass)).collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PassStatistics {
+    pub name: &'static str,
+    pub runs: usize,
+    /// How many of the runs changed the program.
+    pub changes: usize,
+    pub removed_instructions: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OptimizationStatistics {
+    /// Rounds of running every pass, including the last one that changed nothing.
+    pub iterations: usize,
+    pub passes: Vec<PassStatistics>,
+}
+
+impl fmt::Display for OptimizationStatistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} iteration(s)", self.iterations)?;
+
+        for pass in &self.passes {
+            writeln!(
+                f,
+                "  {}: {} run(s), {} changed the program, {} instruction(s) removed",
+                pass.name, pass.runs, pass.changes, pass.removed_instructions
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Runs the enabled passes over and over until the program stops changing, or at most
+/// `MAX_ITERATIONS` times.
+pub fn optimize(program: Program, options: &OptimizationOptions) -> (Program, OptimizationStatistics) {
+    let passes = options.passes();
+    let mut statistics = OptimizationStatistics {
+        iterations: 0,
+        passes: passes
+            .iter()
+            .map(|(name, _)| PassStatistics { name, runs: 0, changes: 0, removed_instructions: 0 })
+            .collect(),
+    };
+
+    if passes.is_empty() {
+        return (program, statistics);
+    }
+
+    let mut program = program;
+
+    while statistics.iterations < MAX_ITERATIONS {
+        statistics.iterations += 1;
+        let mut changed = false;
+
+        for ((_, pass), pass_statistics) in passes.iter().zip(statistics.passes.iter_mut()) {
+            let before = program.clone();
+            program = pass(program);
+
+            pass_statistics.runs += 1;
+            if program != before {
+                changed = true;
+                pass_statistics.changes += 1;
+                pass_statistics.removed_instructions += instruction_count(&before).saturating_sub(instruction_count(&program));
+            }
+        }
+
+        if !changed {
+            break;
+        }
+    }
+
+    (program, statistics)
+}
+
+fn instruction_count(program: &Program) -> usize {
+    match program {
+        Program::Program(FunctionDefinition::Function(_, instructions)) => instructions.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::optimization::{optimize, OptimizationLevel, OptimizationOptions};
+    use crate::compiler::optimization::test_helpers::{self, var};
+    use crate::storage::tacky::{BinaryOp, Instruction, Program, Val};
+
+    /// `int a = 2; int b = a * 3; return b;` as `emit_tacky` generates it.
+    fn program() -> Program {
+        test_helpers::program(vec![
+            Instruction::Copy(Val::Constant(2), var("a.0")),
+            Instruction::Binary(BinaryOp::Multiply, var("a.0"), Val::Constant(3), var("tmp.0")),
+            Instruction::Copy(var("tmp.0"), var("b.0")),
+            Instruction::Return(var("b.0")),
+            Instruction::Return(Val::Constant(0)),
+        ])
+    }
+
+    #[test]
+    fn optimize_until_nothing_changes() {
+        let options = OptimizationOptions { propagate_constants: false, ..OptimizationOptions::for_level(OptimizationLevel::O2) };
+        let (optimized, statistics) = optimize(program(), &options);
+
+        assert_eq!(optimized, test_helpers::program(vec![
+            Instruction::Return(Val::Constant(6)),
+        ]));
+
+        // folding only sees `2 * 3` after the first round propagated `a`, and the third round
+        // finds nothing left to do
+        assert_eq!(statistics.iterations, 3);
+        assert!(statistics.passes.iter().all(|pass| pass.runs == 3));
+        assert_eq!(statistics.passes.iter().map(|pass| pass.removed_instructions).sum::<usize>(), 4);
+
+        // constant propagation sees through `a` on its own
+        let (optimized, statistics) = optimize(program(), &OptimizationOptions::for_level(OptimizationLevel::O2));
+
+        assert_eq!(optimized, test_helpers::program(vec![
+            Instruction::Return(Val::Constant(6)),
+        ]));
+        assert_eq!(statistics.iterations, 2);
+    }
+
+    #[test]
+    fn optimize_levels() {
+        let (optimized, statistics) = optimize(program(), &OptimizationOptions::for_level(OptimizationLevel::O0));
+
+        assert_eq!(optimized, program());
+        assert_eq!(statistics.iterations, 0);
+
+        // without copy propagation there's nothing to fold, only the unreachable return goes
+        let (optimized, statistics) = optimize(program(), &OptimizationOptions::for_level(OptimizationLevel::O1));
+
+        assert_eq!(statistics.passes.iter().map(|pass| pass.name).collect::<Vec<_>>(), vec!["fold-constants", "simplify-algebra", "eliminate-unreachable-code"]);
+        assert_eq!(optimized, test_helpers::program(vec![
+            Instruction::Copy(Val::Constant(2), var("a.0")),
+            Instruction::Binary(BinaryOp::Multiply, var("a.0"), Val::Constant(3), var("tmp.0")),
+            Instruction::Copy(var("tmp.0"), var("b.0")),
+            Instruction::Return(var("b.0")),
+        ]));
+    }
+}
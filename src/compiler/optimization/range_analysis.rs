@@ -0,0 +1,625 @@
+//! Value range analysis: the interval every variable's value lies in.
+//!
+//! Constants give exact intervals and arithmetic combines them, giving up on the whole `int`
+//! range when the result can wrap around. Checked arithmetic can't wrap, the sanitizer stops
+//! the program first, so its result is cut off at the limits instead. Branches narrow the
+//! ranges further: past `JumpIf(x >= 10)` that falls through, `x` is at most 9. A branch
+//! whose condition the ranges decide only has one way out, the other isn't followed.
+//!
+//! `fold_ranges` uses the results to fold comparisons and `!`, branches and the runtime checks
+//! of `-fsanitize=undefined` that can't fail. Dead store elimination asks them whether a
+//! division can fault, and the lint which values a variable can have.
+
+use std::collections::{HashMap, VecDeque};
+use crate::compiler::cfg::{Cfg, NodeId};
+use crate::compiler::optimization::liveness::uses_and_definitions;
+use crate::storage::tacky::{BinaryOp, FunctionDefinition, Instruction, Program, UnaryOp, Val};
+
+/// How often a block is visited before the ranges that still change are given up on. Without
+/// loops no block needs more than one visit per predecessor.
+const MAX_VISITS: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interval {
+    pub low: i32,
+    pub high: i32,
+}
+
+impl Interval {
+    pub const FULL: Interval = Interval { low: i32::MIN, high: i32::MAX };
+    const BOOLEAN: Interval = Interval { low: 0, high: 1 };
+
+    pub fn constant(value: i32) -> Interval {
+        Interval { low: value, high: value }
+    }
+
+    /// The interval from `low` to `high`, or all of them when that doesn't fit, as the
+    /// arithmetic wrapped around.
+    fn wrapping(low: i64, high: i64) -> Interval {
+        match (i32::try_from(low), i32::try_from(high)) {
+            (Ok(low), Ok(high)) => Interval { low, high },
+            _ => Interval::FULL,
+        }
+    }
+
+    /// The interval from `low` to `high`, cut off where a checked operation stops the program.
+    fn saturating(low: i64, high: i64) -> Interval {
+        let clamp = |value: i64| value.clamp(i32::MIN as i64, i32::MAX as i64) as i32;
+        Interval { low: clamp(low), high: clamp(high) }
+    }
+
+    /// The only value in the interval, if there is just one.
+    pub fn value(&self) -> Option<i32> {
+        (self.low == self.high).then_some(self.low)
+    }
+
+    pub fn contains(&self, value: i32) -> bool {
+        self.low <= value && value <= self.high
+    }
+
+    /// The smallest interval containing both.
+    fn hull(self, other: Interval) -> Interval {
+        Interval { low: self.low.min(other.low), high: self.high.max(other.high) }
+    }
+
+    fn intersect(self, other: Interval) -> Option<Interval> {
+        let low = self.low.max(other.low);
+        let high = self.high.min(other.high);
+
+        (low <= high).then_some(Interval { low, high })
+    }
+}
+
+/// The ranges of the variables at some point. Variables that are missing can have any value.
+pub type Ranges = HashMap<String, Interval>;
+
+pub fn range_of(val: &Val, ranges: &Ranges) -> Interval {
+    match val {
+        Val::Constant(value) => Interval::constant(*value),
+        Val::Var(name) => ranges.get(name).copied().unwrap_or(Interval::FULL),
+    }
+}
+
+/// Whether dividing a value in `dividend` by one in `divisor` never faults.
+pub fn is_safe_division(dividend: Interval, divisor: Interval) -> bool {
+    !(divisor.contains(0) || dividend.contains(i32::MIN) && divisor.contains(-1))
+}
+
+/// The outcome of `src1 bin_op src2` for any values in the intervals, if it's always the same.
+pub fn compare(bin_op: &BinaryOp, src1: Interval, src2: Interval) -> Option<bool> {
+    match bin_op {
+        BinaryOp::LessThan if src1.high < src2.low => Some(true),
+        BinaryOp::LessThan if src1.low >= src2.high => Some(false),
+        BinaryOp::LessOrEqual if src1.high <= src2.low => Some(true),
+        BinaryOp::LessOrEqual if src1.low > src2.high => Some(false),
+        BinaryOp::GreaterThan | BinaryOp::GreaterOrEqual => compare(&mirrored(bin_op), src2, src1),
+        BinaryOp::Equal if src1.intersect(src2).is_none() => Some(false),
+        BinaryOp::Equal if src1.value().is_some() && src1 == src2 => Some(true),
+        BinaryOp::NotEqual => compare(&BinaryOp::Equal, src1, src2).map(|equal| !equal),
+        _ => None,
+    }
+}
+
+/// The operator that gives the same result with the operands swapped.
+fn mirrored(bin_op: &BinaryOp) -> BinaryOp {
+    match bin_op {
+        BinaryOp::LessThan => BinaryOp::GreaterThan,
+        BinaryOp::LessOrEqual => BinaryOp::GreaterOrEqual,
+        BinaryOp::GreaterThan => BinaryOp::LessThan,
+        BinaryOp::GreaterOrEqual => BinaryOp::LessOrEqual,
+        other => other.clone(),
+    }
+}
+
+fn is_comparison(bin_op: &BinaryOp) -> bool {
+    matches!(
+        bin_op,
+        BinaryOp::Equal | BinaryOp::NotEqual | BinaryOp::LessThan | BinaryOp::LessOrEqual | BinaryOp::GreaterThan | BinaryOp::GreaterOrEqual
+    )
+}
+
+/// The smallest and largest exact result of the operations that can overflow.
+fn exact_bounds(bin_op: &BinaryOp, src1: Interval, src2: Interval) -> Option<(i64, i64)> {
+    let (low1, high1, low2, high2) = (src1.low as i64, src1.high as i64, src2.low as i64, src2.high as i64);
+
+    match bin_op {
+        BinaryOp::Add => Some((low1 + low2, high1 + high2)),
+        BinaryOp::Subtract => Some((low1 - high2, high1 - low2)),
+        BinaryOp::Multiply => {
+            let corners = [low1 * low2, low1 * high2, high1 * low2, high1 * high2];
+            Some((*corners.iter().min().unwrap(), *corners.iter().max().unwrap()))
+        }
+        _ => None,
+    }
+}
+
+/// Whether a checked operation on values in the intervals can overflow.
+pub fn can_overflow(instruction: &Instruction, ranges: &Ranges) -> bool {
+    let bounds = match instruction {
+        Instruction::CheckedUnary(UnaryOp::Negate, src, _, _) => {
+            let src = range_of(src, ranges);
+            Some((-(src.high as i64), -(src.low as i64)))
+        }
+        Instruction::CheckedBinary(bin_op, src1, src2, _, _) => exact_bounds(bin_op, range_of(src1, ranges), range_of(src2, ranges)),
+        _ => None,
+    };
+
+    bounds.is_some_and(|(low, high)| low < i32::MIN as i64 || high > i32::MAX as i64)
+}
+
+fn evaluate_unary(un_op: &UnaryOp, src: Interval, checked: bool) -> Interval {
+    match un_op {
+        UnaryOp::Negate if checked => Interval::saturating(-(src.high as i64), -(src.low as i64)),
+        UnaryOp::Negate => Interval::wrapping(-(src.high as i64), -(src.low as i64)),
+        UnaryOp::Complement => Interval { low: !src.high, high: !src.low },
+        UnaryOp::Not if !src.contains(0) => Interval::constant(0),
+        UnaryOp::Not if src.value() == Some(0) => Interval::constant(1),
+        UnaryOp::Not => Interval::BOOLEAN,
+    }
+}
+
+fn evaluate_binary(bin_op: &BinaryOp, src1: Interval, src2: Interval, checked: bool) -> Interval {
+    if let Some((low, high)) = exact_bounds(bin_op, src1, src2) {
+        return if checked { Interval::saturating(low, high) } else { Interval::wrapping(low, high) };
+    }
+
+    match bin_op {
+        _ if is_comparison(bin_op) => match compare(bin_op, src1, src2) {
+            Some(result) => Interval::constant(result as i32),
+            None => Interval::BOOLEAN,
+        },
+        BinaryOp::Divide => {
+            // a quotient is largest or smallest for the extreme dividends and the divisors
+            // closest to and furthest from 0, on each side of 0
+            let divisor_sides = [src2.intersect(Interval { low: i32::MIN, high: -1 }), src2.intersect(Interval { low: 1, high: i32::MAX })];
+            let quotients: Vec<i64> = divisor_sides
+                .into_iter()
+                .flatten()
+                .flat_map(|side| [side.low, side.high])
+                .flat_map(|divisor| [src1.low as i64 / divisor as i64, src1.high as i64 / divisor as i64])
+                .collect();
+
+            match (quotients.iter().min(), quotients.iter().max()) {
+                (Some(&low), Some(&high)) => Interval::wrapping(low, high),
+                // only ever divides by zero
+                _ => Interval::FULL,
+            }
+        }
+        BinaryOp::Remainder if src2.value() == Some(0) => Interval::FULL,
+        BinaryOp::Remainder => {
+            // smaller than the divisor, with the sign of the dividend
+            let limit = (src2.low as i64).abs().max((src2.high as i64).abs()) - 1;
+            let low = if src1.low < 0 { (src1.low as i64).max(-limit) } else { 0 };
+            let high = if src1.high > 0 { (src1.high as i64).min(limit) } else { 0 };
+
+            Interval::wrapping(low, high)
+        }
+        _ => Interval::FULL,
+    }
+}
+
+/// Updates `ranges` to what they are after `instruction`.
+pub fn transfer(instruction: &Instruction, ranges: &mut Ranges) {
+    let result = match instruction {
+        Instruction::Copy(src, _) => Some(range_of(src, ranges)),
+        Instruction::Unary(un_op, src, _) => Some(evaluate_unary(un_op, range_of(src, ranges), false)),
+        Instruction::CheckedUnary(un_op, src, _, _) => Some(evaluate_unary(un_op, range_of(src, ranges), true)),
+        Instruction::Binary(bin_op, src1, src2, _) => Some(evaluate_binary(bin_op, range_of(src1, ranges), range_of(src2, ranges), false)),
+        Instruction::CheckedBinary(bin_op, src1, src2, _, _) => {
+            Some(evaluate_binary(bin_op, range_of(src1, ranges), range_of(src2, ranges), true))
+        }
+        _ => None,
+    };
+
+    for variable in uses_and_definitions(instruction).1 {
+        match result {
+            Some(interval) if interval != Interval::FULL => ranges.insert(variable, interval),
+            _ => ranges.remove(&variable),
+        };
+    }
+}
+
+/// The ranges at the start of every block a feasible path leads to. Blocks that are missing
+/// can't run.
+pub fn analyze_ranges(cfg: &Cfg) -> HashMap<usize, Ranges> {
+    let mut edge_ranges: HashMap<(NodeId, NodeId), Ranges> = HashMap::new();
+    let mut ranges_in: HashMap<usize, Ranges> = HashMap::new();
+    let mut visits: HashMap<usize, usize> = HashMap::new();
+    let mut worklist: VecDeque<usize> = VecDeque::new();
+
+    for &successor in &cfg.entry_successors {
+        edge_ranges.insert((NodeId::Entry, successor), Ranges::new());
+        if let NodeId::Block(id) = successor {
+            worklist.push_back(id);
+        }
+    }
+
+    while let Some(id) = worklist.pop_front() {
+        let node = NodeId::Block(id);
+        let block = &cfg.blocks[&id];
+
+        let incoming: Vec<&Ranges> = block.predecessors.iter().filter_map(|&predecessor| edge_ranges.get(&(predecessor, node))).collect();
+        let mut ranges = join(&incoming);
+
+        let visit_count = visits.entry(id).or_insert(0);
+        *visit_count += 1;
+
+        if let Some(previous) = ranges_in.get(&id) {
+            if *previous == ranges {
+                continue;
+            }
+
+            if *visit_count > MAX_VISITS {
+                ranges.retain(|variable, interval| previous.get(variable) == Some(interval));
+            }
+        }
+
+        ranges_in.insert(id, ranges.clone());
+
+        for instruction in &block.instructions {
+            transfer(instruction, &mut ranges);
+        }
+
+        for (successor, successor_ranges) in outgoing_ranges(cfg, id, &ranges) {
+            if edge_ranges.get(&(node, successor)) != Some(&successor_ranges) {
+                edge_ranges.insert((node, successor), successor_ranges);
+
+                if let NodeId::Block(successor) = successor {
+                    if !worklist.contains(&successor) {
+                        worklist.push_back(successor);
+                    }
+                }
+            }
+        }
+    }
+
+    ranges_in
+}
+
+/// The ranges where paths with `incoming` ranges join.
+fn join(incoming: &[&Ranges]) -> Ranges {
+    let Some((first, rest)) = incoming.split_first() else {
+        return Ranges::new();
+    };
+
+    let mut ranges = (*first).clone();
+    for other in rest {
+        ranges = ranges
+            .into_iter()
+            .filter_map(|(variable, interval)| other.get(&variable).map(|other_interval| (variable, interval.hull(*other_interval))))
+            .collect();
+    }
+
+    ranges
+}
+
+/// The successors of block `id` that a feasible path leads to, with the ranges on the way
+/// there, given `ranges` at its end.
+fn outgoing_ranges(cfg: &Cfg, id: usize, ranges: &Ranges) -> Vec<(NodeId, Ranges)> {
+    let block = &cfg.blocks[&id];
+    let fall_through = cfg.next_node(id);
+    let taken = block.successors.iter().copied().find(|&successor| successor != fall_through);
+
+    // the ranges where the jump is taken or not, `None` when that can't happen
+    let refined = |is_taken: bool| match block.instructions.last() {
+        // JumpIfZero is taken when its condition is false
+        Some(Instruction::JumpIfZero(condition, _)) => refine(ranges, condition, computation_of(&block.instructions, condition), !is_taken),
+        Some(Instruction::JumpIfNotZero(condition, _)) => refine(ranges, condition, computation_of(&block.instructions, condition), is_taken),
+        Some(Instruction::JumpIf(bin_op, src1, src2, _)) => {
+            let mut refined = ranges.clone();
+            constrain_comparison(&mut refined, bin_op, src1, src2, is_taken).map(|_| refined)
+        }
+        _ => Some(ranges.clone()),
+    };
+
+    match (block.instructions.last(), taken) {
+        (Some(Instruction::JumpIfZero(..) | Instruction::JumpIfNotZero(..) | Instruction::JumpIf(..)), Some(taken)) => {
+            [(taken, true), (fall_through, false)]
+                .into_iter()
+                .filter_map(|(successor, is_taken)| refined(is_taken).map(|refined| (successor, refined)))
+                .collect()
+        }
+        _ => block.successors.iter().map(|&successor| (successor, ranges.clone())).collect(),
+    }
+}
+
+/// The instruction at the end of `instructions` that computed `condition`, if neither the
+/// condition nor what it was computed from changed since.
+fn computation_of<'a>(instructions: &'a [Instruction], condition: &Val) -> Option<&'a Instruction> {
+    let Val::Var(name) = condition else { return None };
+    let mut changed_since: Vec<String> = vec![];
+
+    for instruction in instructions.iter().rev() {
+        let (uses, definitions) = uses_and_definitions(instruction);
+
+        if definitions.contains(name) {
+            let unchanged = uses.iter().all(|used| !changed_since.contains(used) && used != name);
+            return (unchanged && matches!(instruction, Instruction::Binary(..) | Instruction::Unary(UnaryOp::Not, ..))).then_some(instruction);
+        }
+
+        changed_since.extend(definitions);
+    }
+
+    None
+}
+
+/// `ranges` on the path where `condition`, computed by `computation`, is `truth`, or `None` if
+/// there is no such path.
+fn refine(ranges: &Ranges, condition: &Val, computation: Option<&Instruction>, truth: bool) -> Option<Ranges> {
+    let mut refined = ranges.clone();
+    constrain_truth(&mut refined, condition, truth)?;
+
+    match computation {
+        Some(Instruction::Unary(UnaryOp::Not, src, _)) => constrain_truth(&mut refined, src, !truth)?,
+        Some(Instruction::Binary(bin_op, src1, src2, _)) if is_comparison(bin_op) => {
+            constrain_comparison(&mut refined, bin_op, src1, src2, truth)?
+        }
+        _ => {}
+    }
+
+    Some(refined)
+}
+
+fn narrow(ranges: &mut Ranges, val: &Val, interval: Interval) {
+    if let Val::Var(name) = val {
+        ranges.insert(name.clone(), interval);
+    }
+}
+
+fn constrain_truth(ranges: &mut Ranges, val: &Val, truth: bool) -> Option<()> {
+    let range = range_of(val, ranges);
+
+    let narrowed = if !truth {
+        range.intersect(Interval::constant(0))?
+    } else if range.value() == Some(0) {
+        return None;
+    } else if range.low == 0 {
+        Interval { low: 1, high: range.high }
+    } else if range.high == 0 {
+        Interval { low: range.low, high: -1 }
+    } else {
+        range
+    };
+
+    narrow(ranges, val, narrowed);
+    Some(())
+}
+
+fn constrain_comparison(ranges: &mut Ranges, bin_op: &BinaryOp, src1: &Val, src2: &Val, truth: bool) -> Option<()> {
+    match (bin_op, truth) {
+        (BinaryOp::LessThan, true) | (BinaryOp::GreaterOrEqual, false) => constrain_less(ranges, src1, src2, 1),
+        (BinaryOp::LessThan, false) | (BinaryOp::GreaterOrEqual, true) => constrain_less(ranges, src2, src1, 0),
+        (BinaryOp::LessOrEqual, true) | (BinaryOp::GreaterThan, false) => constrain_less(ranges, src1, src2, 0),
+        (BinaryOp::LessOrEqual, false) | (BinaryOp::GreaterThan, true) => constrain_less(ranges, src2, src1, 1),
+        (BinaryOp::Equal, true) | (BinaryOp::NotEqual, false) => {
+            let both = range_of(src1, ranges).intersect(range_of(src2, ranges))?;
+            narrow(ranges, src1, both);
+            narrow(ranges, src2, both);
+            Some(())
+        }
+        (BinaryOp::Equal, false) | (BinaryOp::NotEqual, true) => {
+            constrain_not_equal(ranges, src1, src2)?;
+            constrain_not_equal(ranges, src2, src1)
+        }
+        _ => Some(()),
+    }
+}
+
+/// Narrows the ranges to `smaller + gap <= larger`.
+fn constrain_less(ranges: &mut Ranges, smaller: &Val, larger: &Val, gap: i64) -> Option<()> {
+    let smaller_range = range_of(smaller, ranges);
+    let larger_range = range_of(larger, ranges);
+
+    let smaller_high = (smaller_range.high as i64).min(larger_range.high as i64 - gap);
+    let larger_low = (larger_range.low as i64).max(smaller_range.low as i64 + gap);
+
+    if smaller_high < smaller_range.low as i64 || larger_low > larger_range.high as i64 {
+        return None;
+    }
+
+    narrow(ranges, smaller, Interval { low: smaller_range.low, high: smaller_high as i32 });
+    narrow(ranges, larger, Interval { low: larger_low as i32, high: larger_range.high });
+    Some(())
+}
+
+/// Narrows the range of `val` to leave out the value of `other`, when that is one of its ends.
+fn constrain_not_equal(ranges: &mut Ranges, val: &Val, other: &Val) -> Option<()> {
+    let range = range_of(val, ranges);
+    let Some(excluded) = range_of(other, ranges).value() else { return Some(()) };
+
+    if range.value() == Some(excluded) {
+        None
+    } else if range.low == excluded {
+        narrow(ranges, val, Interval { low: excluded + 1, high: range.high });
+        Some(())
+    } else if range.high == excluded {
+        narrow(ranges, val, Interval { low: range.low, high: excluded - 1 });
+        Some(())
+    } else {
+        Some(())
+    }
+}
+
+/// Folds comparisons, `!` and branches the ranges decide, and turns checked operations that
+/// can't overflow into plain ones. Blocks no feasible path leads to are removed, which takes
+/// the sanitizer's traps for division checks that always pass with them.
+pub fn fold_ranges(program: Program) -> Program {
+    match program {
+        Program::Program(function) => {
+            let mut cfg = Cfg::from_function(function);
+            let ranges_in = analyze_ranges(&cfg);
+
+            let infeasible: Vec<usize> = cfg.blocks.keys().copied().filter(|id| !ranges_in.contains_key(id)).collect();
+            for id in infeasible {
+                cfg.remove_block(id);
+            }
+
+            let ids: Vec<usize> = cfg.blocks.keys().copied().collect();
+            for id in ids {
+                let mut ranges = ranges_in[&id].clone();
+                let mut instructions = vec![];
+
+                for instruction in &cfg.blocks[&id].instructions {
+                    instructions.push(fold_instruction(instruction, &ranges));
+                    transfer(instruction, &mut ranges);
+                }
+
+                let outgoing: Vec<NodeId> = outgoing_ranges(&cfg, id, &ranges).into_iter().map(|(successor, _)| successor).collect();
+                let fall_through = cfg.next_node(id);
+
+                match instructions.pop() {
+                    // only one way out is feasible, falling through needs no jump
+                    Some(Instruction::JumpIfZero(..) | Instruction::JumpIfNotZero(..) | Instruction::JumpIf(..)) if outgoing == [fall_through] => {}
+                    Some(
+                        Instruction::JumpIfZero(_, target) | Instruction::JumpIfNotZero(_, target) | Instruction::JumpIf(_, _, _, target),
+                    ) if outgoing.len() == 1 => {
+                        instructions.push(Instruction::Jump(target));
+                    }
+                    Some(last) => instructions.push(last),
+                    None => {}
+                }
+
+                cfg.blocks.get_mut(&id).unwrap().instructions = instructions;
+            }
+
+            Program::Program(cfg.into_function())
+        }
+    }
+}
+
+fn fold_instruction(instruction: &Instruction, ranges: &Ranges) -> Instruction {
+    match instruction {
+        Instruction::Binary(bin_op, src1, src2, dst) if is_comparison(bin_op) => {
+            match compare(bin_op, range_of(src1, ranges), range_of(src2, ranges)) {
+                Some(result) => Instruction::Copy(Val::Constant(result as i32), dst.clone()),
+                None => instruction.clone(),
+            }
+        }
+        Instruction::Unary(UnaryOp::Not, src, dst) => match evaluate_unary(&UnaryOp::Not, range_of(src, ranges), false).value() {
+            Some(result) => Instruction::Copy(Val::Constant(result), dst.clone()),
+            None => instruction.clone(),
+        },
+        Instruction::CheckedUnary(un_op, src, dst, _) if !can_overflow(instruction, ranges) => {
+            Instruction::Unary(un_op.clone(), src.clone(), dst.clone())
+        }
+        Instruction::CheckedBinary(bin_op, src1, src2, dst, _) if !can_overflow(instruction, ranges) => {
+            Instruction::Binary(bin_op.clone(), src1.clone(), src2.clone(), dst.clone())
+        }
+        _ => instruction.clone(),
+    }
+}
+
+/// The values each variable can have wherever `function` reads it, for checks that don't look
+/// at where in the function they are.
+pub fn variable_ranges(function: &FunctionDefinition) -> Ranges {
+    let cfg = Cfg::from_function(function.clone());
+    let mut read_ranges = Ranges::new();
+    let mut unbounded = vec![];
+
+    for (id, ranges_in) in analyze_ranges(&cfg) {
+        let mut ranges = ranges_in;
+
+        for instruction in &cfg.blocks[&id].instructions {
+            for variable in uses_and_definitions(instruction).0 {
+                match ranges.get(&variable) {
+                    Some(interval) => {
+                        let read = read_ranges.get(&variable).map_or(*interval, |read| read.hull(*interval));
+                        read_ranges.insert(variable, read);
+                    }
+                    None => unbounded.push(variable),
+                }
+            }
+
+            transfer(instruction, &mut ranges);
+        }
+    }
+
+    for variable in unbounded {
+        read_ranges.remove(&variable);
+    }
+
+    read_ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::optimization::range_analysis::{evaluate_binary, fold_ranges, Interval};
+    use crate::compiler::optimization::test_helpers::{run_pass, var};
+    use crate::storage::tacky::{BinaryOp, Instruction, Val};
+
+    fn fold(instructions: Vec<Instruction>) -> Vec<Instruction> {
+        run_pass(fold_ranges, instructions)
+    }
+
+    fn interval(low: i32, high: i32) -> Interval {
+        Interval { low, high }
+    }
+
+    #[test]
+    fn evaluate_interval_arithmetic() {
+        assert_eq!(evaluate_binary(&BinaryOp::Add, interval(1, 5), interval(-2, 3), false), interval(-1, 8));
+        assert_eq!(evaluate_binary(&BinaryOp::Multiply, interval(-3, 2), interval(4, 5), false), interval(-15, 10));
+        assert_eq!(evaluate_binary(&BinaryOp::Divide, interval(-100, 50), interval(-5, 10), false), interval(-100, 100));
+        assert_eq!(evaluate_binary(&BinaryOp::Remainder, interval(-7, 100), interval(10, 10), false), interval(-7, 9));
+
+        // wrapping can give anything, a checked operation stops the program instead
+        assert_eq!(evaluate_binary(&BinaryOp::Add, interval(0, i32::MAX), interval(1, 1), false), Interval::FULL);
+        assert_eq!(evaluate_binary(&BinaryOp::Add, interval(0, i32::MAX), interval(1, 1), true), interval(1, i32::MAX));
+    }
+
+    #[test]
+    fn fold_comparisons_decided_by_branches() {
+        // return x > 5 && x > 2;
+        assert_eq!(
+            fold(vec![
+                Instruction::Binary(BinaryOp::GreaterThan, var("x.0"), Val::Constant(5), var("tmp.0")),
+                Instruction::JumpIfZero(var("tmp.0"), "and_false0".to_string()),
+                Instruction::Binary(BinaryOp::GreaterThan, var("x.0"), Val::Constant(2), var("tmp.1")),
+                Instruction::JumpIfZero(var("tmp.1"), "and_false0".to_string()),
+                Instruction::Copy(Val::Constant(1), var("tmp.2")),
+                Instruction::Jump("and_false_end0".to_string()),
+                Instruction::Label("and_false0".to_string()),
+                Instruction::Copy(Val::Constant(0), var("tmp.2")),
+                Instruction::Label("and_false_end0".to_string()),
+                Instruction::Return(var("tmp.2")),
+            ]),
+            vec![
+                Instruction::Binary(BinaryOp::GreaterThan, var("x.0"), Val::Constant(5), var("tmp.0")),
+                Instruction::JumpIfZero(var("tmp.0"), "and_false0".to_string()),
+                Instruction::Copy(Val::Constant(1), var("tmp.1")),
+                Instruction::Copy(Val::Constant(1), var("tmp.2")),
+                Instruction::Jump("and_false_end0".to_string()),
+                Instruction::Label("and_false0".to_string()),
+                Instruction::Copy(Val::Constant(0), var("tmp.2")),
+                Instruction::Label("and_false_end0".to_string()),
+                Instruction::Return(var("tmp.2")),
+            ]
+        );
+    }
+
+    #[test]
+    fn fold_sanitizer_checks_that_cannot_fail() {
+        let overflow = "main.c:1:1: runtime error: signed integer overflow".to_string();
+        let division = "main.c:1:1: runtime error: division by zero".to_string();
+
+        // return 100 / (x % 10 + 20); with -fsanitize=undefined
+        assert_eq!(
+            fold(vec![
+                Instruction::Binary(BinaryOp::Remainder, var("x.0"), Val::Constant(10), var("tmp.0")),
+                Instruction::CheckedBinary(BinaryOp::Add, var("tmp.0"), Val::Constant(20), var("tmp.1"), overflow),
+                Instruction::JumpIfNotZero(var("tmp.1"), "check_passed0".to_string()),
+                Instruction::Trap(division),
+                Instruction::Label("check_passed0".to_string()),
+                Instruction::Binary(BinaryOp::Divide, Val::Constant(100), var("tmp.1"), var("tmp.2")),
+                Instruction::Return(var("tmp.2")),
+            ]),
+            vec![
+                Instruction::Binary(BinaryOp::Remainder, var("x.0"), Val::Constant(10), var("tmp.0")),
+                Instruction::Binary(BinaryOp::Add, var("tmp.0"), Val::Constant(20), var("tmp.1")),
+                Instruction::Label("check_passed0".to_string()),
+                Instruction::Binary(BinaryOp::Divide, Val::Constant(100), var("tmp.1"), var("tmp.2")),
+                Instruction::Return(var("tmp.2")),
+            ]
+        );
+    }
+}
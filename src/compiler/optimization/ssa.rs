@@ -0,0 +1,678 @@
+//! Static single assignment form of TACKY.
+//!
+//! In SSA every variable is written exactly once, so a variable's name stands for one value
+//! and analyses don't have to track when it changes. Each write of `x.0` gets its own version,
+//! `x.0.1`, `x.0.2`, ... and where paths with different versions join, a phi picks the version
+//! of the path control came from. Reading a variable before any write reads version 0, which
+//! nothing ever writes.
+//!
+//! Phis are only placed where the variable is live (pruned SSA), at the dominance frontiers of
+//! its writes. Variables written by inline assembly keep their name: a `+` operand reads and
+//! writes the same operand, which can't be split into two versions.
+//!
+//! Leaving SSA gives the versions their original name back wherever that doesn't merge two
+//! values that are live at once, so going in and out of SSA again doesn't pile up suffixes.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use crate::compiler::cfg::{Cfg, NodeId};
+use crate::compiler::optimization::dominance::Dominators;
+use crate::compiler::optimization::liveness::{analyze_liveness, live_after_each, transfer, uses_and_definitions, LiveVariables};
+use crate::compiler::optimization::unreachable_code::remove_unreachable_blocks;
+use crate::storage::tacky::{FunctionDefinition, Instruction, Val};
+
+/// `dst = phi(sources)`, where each source is the value coming in from one predecessor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Phi {
+    pub dst: String,
+    pub sources: Vec<(NodeId, Val)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SsaFunction {
+    pub cfg: Cfg,
+    /// The phis at the start of each block, by block id.
+    pub phis: BTreeMap<usize, Vec<Phi>>,
+    /// The variables inline assembly writes, which keep their name.
+    pub unversioned: HashSet<String>,
+    /// The variable each version was renamed from.
+    pub originals: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SsaError {
+    MultipleDefinitions(String), // MultipleDefinitions(variable)
+    PhiSources(usize, String), // PhiSources(block id, phi destination), sources don't match the predecessors
+}
+
+impl fmt::Display for SsaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SsaError::MultipleDefinitions(variable) => write!(f, "'{}' is defined more than once", variable),
+            SsaError::PhiSources(id, dst) => write!(f, "the phi for '{}' in block {} doesn't have one source per predecessor", dst, id),
+        }
+    }
+}
+
+/// Converts `function` into SSA. Unreachable blocks are dropped on the way.
+pub fn into_ssa(function: FunctionDefinition) -> SsaFunction {
+    let mut cfg = Cfg::from_function(function);
+    remove_unreachable_blocks(&mut cfg);
+
+    let unversioned: HashSet<String> = cfg.blocks
+        .values()
+        .flat_map(|block| block.instructions.iter())
+        .filter_map(|instruction| match instruction {
+            Instruction::InlineAsm(_, outputs, _, _) => Some(outputs.iter().filter_map(|(_, output)| var_name(output))),
+            _ => None,
+        })
+        .flatten()
+        .collect();
+
+    let variables: HashSet<String> = cfg.blocks
+        .values()
+        .flat_map(|block| block.instructions.iter())
+        .flat_map(|instruction| {
+            let (uses, definitions) = uses_and_definitions(instruction);
+            uses.into_iter().chain(definitions)
+        })
+        .filter(|variable| !unversioned.contains(variable))
+        .collect();
+
+    let dominators = Dominators::compute(&cfg);
+    let phi_variables = place_phis(&cfg, &dominators, &unversioned);
+
+    let mut renamer = Renamer {
+        unversioned: &unversioned,
+        versions: HashMap::new(),
+        stacks: HashMap::new(),
+        phis: phi_variables
+            .iter()
+            .map(|(&id, variables)| (id, variables.iter().map(|_| Phi { dst: String::new(), sources: vec![] }).collect()))
+            .collect(),
+        phi_variables: &phi_variables,
+    };
+    renamer.rename(NodeId::Entry, &mut cfg, &dominators);
+
+    let originals = variables
+        .into_iter()
+        .flat_map(|variable| {
+            let last_version = renamer.versions.get(&variable).copied().unwrap_or(0);
+            (0..=last_version).map(move |version| (format!("{}.{}", variable, version), variable.clone()))
+        })
+        .collect();
+
+    let ssa = SsaFunction { phis: renamer.phis, cfg, unversioned, originals };
+    debug_assert_eq!(verify_ssa(&ssa), Ok(()));
+    ssa
+}
+
+/// The variables that need a phi at the start of each block.
+fn place_phis(cfg: &Cfg, dominators: &Dominators, unversioned: &HashSet<String>) -> BTreeMap<usize, Vec<String>> {
+    let frontiers = dominators.frontiers(cfg);
+    let live_out = analyze_liveness(cfg);
+    let live_in: HashMap<usize, LiveVariables> = cfg.blocks
+        .iter()
+        .map(|(id, block)| {
+            let mut live = live_out[id].clone();
+            for instruction in block.instructions.iter().rev() {
+                transfer(instruction, &mut live);
+            }
+            (*id, live)
+        })
+        .collect();
+
+    let mut definition_blocks: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    for (&id, block) in &cfg.blocks {
+        for instruction in &block.instructions {
+            for variable in uses_and_definitions(instruction).1 {
+                if !unversioned.contains(&variable) {
+                    definition_blocks.entry(variable).or_default().push(id);
+                }
+            }
+        }
+    }
+
+    let mut phi_variables: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+
+    for (variable, blocks) in definition_blocks {
+        let mut worklist = blocks;
+        let mut has_phi = HashSet::new();
+
+        while let Some(id) = worklist.pop() {
+            for frontier in &frontiers[&NodeId::Block(id)] {
+                if let NodeId::Block(frontier) = *frontier {
+                    if live_in[&frontier].contains(&variable) && has_phi.insert(frontier) {
+                        phi_variables.entry(frontier).or_default().push(variable.clone());
+                        // the phi is a new definition of the variable
+                        worklist.push(frontier);
+                    }
+                }
+            }
+        }
+    }
+
+    phi_variables
+}
+
+/// Gives every definition a new version while walking down the dominator tree, so each use
+/// sees the version of the closest definition that dominates it.
+struct Renamer<'a> {
+    unversioned: &'a HashSet<String>,
+    versions: HashMap<String, usize>,
+    stacks: HashMap<String, Vec<String>>,
+    phis: BTreeMap<usize, Vec<Phi>>,
+    phi_variables: &'a BTreeMap<usize, Vec<String>>,
+}
+
+impl Renamer<'_> {
+    fn rename(&mut self, node: NodeId, cfg: &mut Cfg, dominators: &Dominators) {
+        let mut defined = vec![];
+
+        if let NodeId::Block(id) = node {
+            if let Some(variables) = self.phi_variables.get(&id) {
+                for (index, variable) in variables.iter().enumerate() {
+                    let dst = self.define(variable);
+                    self.phis.get_mut(&id).unwrap()[index].dst = dst;
+                    defined.push(variable.clone());
+                }
+            }
+
+            let block = cfg.blocks.get_mut(&id).unwrap();
+            for instruction in block.instructions.iter_mut() {
+                *instruction = map_uses(instruction, |val| self.current(val));
+                for variable in uses_and_definitions(instruction).1 {
+                    if !self.unversioned.contains(&variable) {
+                        defined.push(variable);
+                    }
+                }
+                *instruction = map_definitions(instruction, |val| match var_name(val) {
+                    Some(variable) if !self.unversioned.contains(&variable) => Val::Var(self.define(&variable)),
+                    _ => val.clone(),
+                });
+            }
+        }
+
+        for successor in cfg.successors(node).to_vec() {
+            if let NodeId::Block(successor) = successor {
+                if let Some(variables) = self.phi_variables.get(&successor) {
+                    for (index, variable) in variables.iter().enumerate() {
+                        let source = self.current(&Val::Var(variable.clone()));
+                        self.phis.get_mut(&successor).unwrap()[index].sources.push((node, source));
+                    }
+                }
+            }
+        }
+
+        for child in dominators.children(node) {
+            self.rename(child, cfg, dominators);
+        }
+
+        for variable in defined {
+            self.stacks.get_mut(&variable).unwrap().pop();
+        }
+    }
+
+    fn define(&mut self, variable: &str) -> String {
+        let version = self.versions.entry(variable.to_string()).or_insert(0);
+        *version += 1;
+
+        let name = format!("{}.{}", variable, version);
+        self.stacks.entry(variable.to_string()).or_default().push(name.clone());
+        name
+    }
+
+    fn current(&self, val: &Val) -> Val {
+        match var_name(val) {
+            Some(variable) if !self.unversioned.contains(&variable) => {
+                match self.stacks.get(&variable).and_then(|stack| stack.last()) {
+                    Some(name) => Val::Var(name.clone()),
+                    None => Val::Var(format!("{}.0", variable)),
+                }
+            }
+            _ => val.clone(),
+        }
+    }
+}
+
+/// Converts `ssa` back into plain TACKY, turning each phi into copies at the end of its
+/// predecessors.
+///
+/// When the predecessor has another successor as well, the copies can't go at its end, they
+/// go on the edge itself: after the conditional jump when the edge falls through, or into a
+/// new block the jump is redirected to otherwise.
+pub fn from_ssa(ssa: SsaFunction) -> FunctionDefinition {
+    let SsaFunction { cfg, phis, originals, .. } = ssa;
+
+    let mut edge_copies: HashMap<(NodeId, usize), Vec<(Val, String)>> = HashMap::new();
+    for (&id, block_phis) in &phis {
+        for phi in block_phis {
+            for (predecessor, source) in &phi.sources {
+                edge_copies.entry((*predecessor, id)).or_default().push((source.clone(), phi.dst.clone()));
+            }
+        }
+    }
+
+    let mut copies_on_edge = |from: NodeId, to: NodeId| match to {
+        NodeId::Block(to) => sequentialize(edge_copies.remove(&(from, to)).unwrap_or_default()),
+        _ => vec![],
+    };
+
+    let mut instructions = vec![];
+    let mut split_blocks = vec![];
+
+    for &successor in &cfg.entry_successors {
+        instructions.append(&mut copies_on_edge(NodeId::Entry, successor));
+    }
+
+    for (&id, block) in &cfg.blocks {
+        let node = NodeId::Block(id);
+        let mut body = block.instructions.clone();
+
+        match body.pop() {
+            Some(Instruction::JumpIfZero(condition, target)) if block.successors.len() == 2 => {
+                let (taken, fall_through) = branch_targets(&cfg, id, &target);
+                let target = split_edge(id, target, copies_on_edge(node, taken), &mut split_blocks);
+
+                body.push(Instruction::JumpIfZero(condition, target));
+                body.append(&mut copies_on_edge(node, fall_through));
+            }
+            Some(Instruction::JumpIfNotZero(condition, target)) if block.successors.len() == 2 => {
+                let (taken, fall_through) = branch_targets(&cfg, id, &target);
+                let target = split_edge(id, target, copies_on_edge(node, taken), &mut split_blocks);
+
+                body.push(Instruction::JumpIfNotZero(condition, target));
+                body.append(&mut copies_on_edge(node, fall_through));
+            }
+            Some(Instruction::JumpIf(bin_op, src1, src2, target)) if block.successors.len() == 2 => {
+                let (taken, fall_through) = branch_targets(&cfg, id, &target);
+                let target = split_edge(id, target, copies_on_edge(node, taken), &mut split_blocks);
+
+                body.push(Instruction::JumpIf(bin_op, src1, src2, target));
+                body.append(&mut copies_on_edge(node, fall_through));
+            }
+            Some(terminator @ (Instruction::Jump(_) | Instruction::JumpIfZero(..) | Instruction::JumpIfNotZero(..) | Instruction::JumpIf(..))) => {
+                // every way out leads to the same block
+                body.append(&mut copies_on_edge(node, block.successors[0]));
+                body.push(terminator);
+            }
+            Some(last) => {
+                body.push(last);
+                if let [successor] = block.successors[..] {
+                    body.append(&mut copies_on_edge(node, successor));
+                }
+            }
+            None => {}
+        }
+
+        instructions.append(&mut body);
+    }
+
+    let split_labels: Vec<String> = split_blocks
+        .iter()
+        .filter_map(|instruction| match instruction {
+            Instruction::Label(label) => Some(label.clone()),
+            _ => None,
+        })
+        .collect();
+
+    instructions.append(&mut split_blocks);
+    let function = FunctionDefinition::Function(cfg.function_name, instructions);
+
+    match restore_names(Cfg::from_function(function), &originals).into_function() {
+        FunctionDefinition::Function(name, instructions) => FunctionDefinition::Function(name, remove_empty_splits(instructions, &split_labels)),
+    }
+}
+
+/// Drops the blocks `split_edge` added whose copies all went away when the names were
+/// restored, and points the jumps to them back at the label they jump on to.
+fn remove_empty_splits(instructions: Vec<Instruction>, split_labels: &[String]) -> Vec<Instruction> {
+    let targets: HashMap<String, String> = instructions
+        .windows(2)
+        .filter_map(|pair| match pair {
+            [Instruction::Label(label), Instruction::Jump(target)] if split_labels.contains(label) => Some((label.clone(), target.clone())),
+            _ => None,
+        })
+        .collect();
+
+    let retarget = |label: String| targets.get(&label).cloned().unwrap_or(label);
+    let mut remaining = vec![];
+    let mut instructions = instructions.into_iter();
+
+    while let Some(instruction) = instructions.next() {
+        match instruction {
+            Instruction::Label(label) if targets.contains_key(&label) => {
+                // the jump on to the target
+                instructions.next();
+            }
+            Instruction::JumpIfZero(condition, target) => remaining.push(Instruction::JumpIfZero(condition, retarget(target))),
+            Instruction::JumpIfNotZero(condition, target) => remaining.push(Instruction::JumpIfNotZero(condition, retarget(target))),
+            Instruction::JumpIf(bin_op, src1, src2, target) => remaining.push(Instruction::JumpIf(bin_op, src1, src2, retarget(target))),
+            instruction => remaining.push(instruction),
+        }
+    }
+
+    remaining
+}
+
+/// Renames the versions of each variable back to the variable, in the order they first appear,
+/// except a version that is live where another one already renamed is written, or the other way
+/// around, which keeps its own name. The copies that become `x = x` are dropped.
+fn restore_names(mut cfg: Cfg, originals: &HashMap<String, String>) -> Cfg {
+    let live_out = analyze_liveness(&cfg);
+    let mut interfering: HashSet<(String, String)> = HashSet::new();
+
+    for (id, block) in &cfg.blocks {
+        for (instruction, live) in block.instructions.iter().zip(live_after_each(&block.instructions, &live_out[id])) {
+            for definition in uses_and_definitions(instruction).1 {
+                let Some(original) = originals.get(&definition) else { continue };
+
+                for variable in live.iter().filter(|&variable| *variable != definition && originals.get(variable) == Some(original)) {
+                    interfering.insert((definition.clone(), variable.clone()));
+                    interfering.insert((variable.clone(), definition.clone()));
+                }
+            }
+        }
+    }
+
+    let mut names: HashMap<String, String> = HashMap::new();
+    let mut renamed: HashMap<&str, Vec<String>> = HashMap::new();
+
+    for instruction in cfg.blocks.values().flat_map(|block| block.instructions.iter()) {
+        let (uses, definitions) = uses_and_definitions(instruction);
+
+        for version in uses.into_iter().chain(definitions) {
+            let Some(original) = originals.get(&version) else { continue };
+            if names.contains_key(&version) {
+                continue;
+            }
+
+            let renamed = renamed.entry(original).or_default();
+            if renamed.iter().any(|other| interfering.contains(&(other.clone(), version.clone()))) {
+                names.insert(version.clone(), version);
+            } else {
+                renamed.push(version.clone());
+                names.insert(version, original.clone());
+            }
+        }
+    }
+
+    let rename = |val: &Val| match val {
+        Val::Var(name) => Val::Var(names.get(name).unwrap_or(name).clone()),
+        Val::Constant(_) => val.clone(),
+    };
+
+    for block in cfg.blocks.values_mut() {
+        block.instructions = block.instructions
+            .iter()
+            .map(|instruction| map_definitions(&map_uses(instruction, rename), rename))
+            .filter(|instruction| !matches!(instruction, Instruction::Copy(Val::Var(src), Val::Var(dst)) if src == dst))
+            .collect();
+    }
+
+    cfg
+}
+
+/// The block a conditional jump in block `id` jumps to, and the node it falls through to.
+fn branch_targets(cfg: &Cfg, id: usize, target: &str) -> (NodeId, NodeId) {
+    let fall_through = cfg.next_node(id);
+    let taken = cfg.blocks[&id].successors.iter().copied().find(|&successor| successor != fall_through).unwrap_or_else(|| {
+        unreachable!("the jump to '{}' has two successors", target)
+    });
+
+    (taken, fall_through)
+}
+
+/// The label a jump from block `id` to `target` should jump to, so that it runs `copies` on the
+/// way, which needs a new block when there are any.
+fn split_edge(id: usize, target: String, copies: Vec<Instruction>, split_blocks: &mut Vec<Instruction>) -> String {
+    if copies.is_empty() {
+        return target;
+    }
+
+    let label = format!("{}.split{}", target, id);
+    split_blocks.push(Instruction::Label(label.clone()));
+    split_blocks.extend(copies);
+    split_blocks.push(Instruction::Jump(target));
+    label
+}
+
+/// Orders the copies of one edge, which all happen at once, so none overwrites a value another
+/// still has to read. A cycle, like swapping two variables, goes through a temporary.
+fn sequentialize(copies: Vec<(Val, String)>) -> Vec<Instruction> {
+    let mut pending: Vec<(Val, String)> = copies.into_iter().filter(|(src, dst)| var_name(src).as_ref() != Some(dst)).collect();
+    let mut instructions = vec![];
+
+    while !pending.is_empty() {
+        let ready = pending.iter().position(|(_, dst)| !pending.iter().any(|(src, _)| var_name(src).as_ref() == Some(dst)));
+
+        match ready {
+            Some(index) => {
+                let (src, dst) = pending.remove(index);
+                instructions.push(Instruction::Copy(src, Val::Var(dst)));
+            }
+            None => {
+                let saved = pending[0].1.clone();
+                let temporary = format!("{}.swap", saved);
+                instructions.push(Instruction::Copy(Val::Var(saved.clone()), Val::Var(temporary.clone())));
+
+                for (src, _) in pending.iter_mut() {
+                    if var_name(src).as_ref() == Some(&saved) {
+                        *src = Val::Var(temporary.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    instructions
+}
+
+/// Checks that every versioned variable is defined once and that every phi has one source
+/// per predecessor of its block.
+pub fn verify_ssa(ssa: &SsaFunction) -> Result<(), SsaError> {
+    let mut defined = HashSet::new();
+
+    for (&id, block) in &ssa.cfg.blocks {
+        for phi in ssa.phis.get(&id).into_iter().flatten() {
+            if !defined.insert(phi.dst.clone()) {
+                return Err(SsaError::MultipleDefinitions(phi.dst.clone()));
+            }
+
+            let mut sources: Vec<NodeId> = phi.sources.iter().map(|(predecessor, _)| *predecessor).collect();
+            let mut predecessors = block.predecessors.clone();
+            sources.sort();
+            predecessors.sort();
+
+            if sources != predecessors {
+                return Err(SsaError::PhiSources(id, phi.dst.clone()));
+            }
+        }
+
+        for instruction in &block.instructions {
+            for variable in uses_and_definitions(instruction).1 {
+                if !ssa.unversioned.contains(&variable) && !defined.insert(variable.clone()) {
+                    return Err(SsaError::MultipleDefinitions(variable));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn var_name(val: &Val) -> Option<String> {
+    match val {
+        Val::Var(name) => Some(name.clone()),
+        Val::Constant(_) => None,
+    }
+}
+
+/// `instruction` with `replace` applied to every value it reads.
+pub fn map_uses(instruction: &Instruction, replace: impl Fn(&Val) -> Val) -> Instruction {
+    match instruction {
+        Instruction::Return(val) => Instruction::Return(replace(val)),
+        Instruction::Copy(src, dst) => Instruction::Copy(replace(src), dst.clone()),
+        Instruction::Unary(un_op, src, dst) => Instruction::Unary(un_op.clone(), replace(src), dst.clone()),
+        Instruction::Binary(bin_op, src1, src2, dst) => Instruction::Binary(bin_op.clone(), replace(src1), replace(src2), dst.clone()),
+        Instruction::JumpIfZero(condition, target) => Instruction::JumpIfZero(replace(condition), target.clone()),
+        Instruction::JumpIfNotZero(condition, target) => Instruction::JumpIfNotZero(replace(condition), target.clone()),
+        Instruction::JumpIf(bin_op, src1, src2, target) => Instruction::JumpIf(bin_op.clone(), replace(src1), replace(src2), target.clone()),
+        Instruction::CheckedUnary(un_op, src, dst, error) => Instruction::CheckedUnary(un_op.clone(), replace(src), dst.clone(), error.clone()),
+        Instruction::CheckedBinary(bin_op, src1, src2, dst, error) => {
+            Instruction::CheckedBinary(bin_op.clone(), replace(src1), replace(src2), dst.clone(), error.clone())
+        }
+        Instruction::InlineAsm(template, outputs, inputs, clobbers) => {
+            let inputs = inputs.iter().map(|(constraint, val)| (constraint.clone(), replace(val))).collect();
+            Instruction::InlineAsm(template.clone(), outputs.clone(), inputs, clobbers.clone())
+        }
+        Instruction::Jump(_) | Instruction::Label(_) | Instruction::Trap(_) => instruction.clone(),
+    }
+}
+
+/// `instruction` with `replace` applied to every value it writes.
+pub fn map_definitions(instruction: &Instruction, mut replace: impl FnMut(&Val) -> Val) -> Instruction {
+    match instruction {
+        Instruction::Copy(src, dst) => Instruction::Copy(src.clone(), replace(dst)),
+        Instruction::Unary(un_op, src, dst) => Instruction::Unary(un_op.clone(), src.clone(), replace(dst)),
+        Instruction::Binary(bin_op, src1, src2, dst) => Instruction::Binary(bin_op.clone(), src1.clone(), src2.clone(), replace(dst)),
+        Instruction::CheckedUnary(un_op, src, dst, error) => Instruction::CheckedUnary(un_op.clone(), src.clone(), replace(dst), error.clone()),
+        Instruction::CheckedBinary(bin_op, src1, src2, dst, error) => {
+            Instruction::CheckedBinary(bin_op.clone(), src1.clone(), src2.clone(), replace(dst), error.clone())
+        }
+        Instruction::InlineAsm(template, outputs, inputs, clobbers) => {
+            let outputs = outputs.iter().map(|(constraint, val)| (constraint.clone(), replace(val))).collect();
+            Instruction::InlineAsm(template.clone(), outputs, inputs.clone(), clobbers.clone())
+        }
+        _ => instruction.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::cfg::NodeId;
+    use crate::compiler::optimization::ssa::{from_ssa, into_ssa, sequentialize, verify_ssa, Phi, SsaError};
+    use crate::compiler::optimization::test_helpers::var;
+    use crate::storage::tacky::{BinaryOp, FunctionDefinition, Instruction, Val};
+
+    /// `x = 1; if (c) x = 2; return x;` written with a jump around the second assignment.
+    fn conditional_assignment() -> FunctionDefinition {
+        FunctionDefinition::Function("main".to_string(), vec![
+            Instruction::Copy(Val::Constant(1), var("x.0")),
+            Instruction::JumpIfZero(var("c.0"), "end".to_string()),
+            Instruction::Copy(Val::Constant(2), var("x.0")),
+            Instruction::Label("end".to_string()),
+            Instruction::Return(var("x.0")),
+        ])
+    }
+
+    #[test]
+    fn into_ssa_places_phis_at_joins() {
+        let ssa = into_ssa(conditional_assignment());
+
+        assert_eq!(verify_ssa(&ssa), Ok(()));
+        assert_eq!(ssa.cfg.blocks[&0].instructions, vec![
+            Instruction::Copy(Val::Constant(1), var("x.0.1")),
+            Instruction::JumpIfZero(var("c.0.0"), "end".to_string()),
+        ]);
+        assert_eq!(ssa.cfg.blocks[&1].instructions, vec![Instruction::Copy(Val::Constant(2), var("x.0.2"))]);
+        assert_eq!(ssa.phis[&2], vec![Phi {
+            dst: "x.0.3".to_string(),
+            sources: vec![(NodeId::Block(0), var("x.0.1")), (NodeId::Block(1), var("x.0.2"))],
+        }]);
+        assert_eq!(ssa.cfg.blocks[&2].instructions[1], Instruction::Return(var("x.0.3")));
+        // nothing reads c after the jump, so it needs no phi
+        assert_eq!(ssa.phis.len(), 1);
+    }
+
+    #[test]
+    fn from_ssa_splits_critical_edges() {
+        // the jump from block 0 to `end` is critical: block 0 has two successors and `end` two
+        // predecessors, so the copy for it needs a block of its own. Without the original names
+        // the versions stay apart, and so do the copies between them
+        let mut ssa = into_ssa(conditional_assignment());
+        ssa.originals.clear();
+
+        assert_eq!(from_ssa(ssa), FunctionDefinition::Function("main".to_string(), vec![
+            Instruction::Copy(Val::Constant(1), var("x.0.1")),
+            Instruction::JumpIfZero(var("c.0.0"), "end.split0".to_string()),
+            Instruction::Copy(Val::Constant(2), var("x.0.2")),
+            Instruction::Copy(var("x.0.2"), var("x.0.3")),
+            Instruction::Label("end".to_string()),
+            Instruction::Return(var("x.0.3")),
+            Instruction::Label("end.split0".to_string()),
+            Instruction::Copy(var("x.0.1"), var("x.0.3")),
+            Instruction::Jump("end".to_string()),
+        ]));
+    }
+
+    #[test]
+    fn from_ssa_copies_on_fall_through_edge() {
+        // if (c) x = 2; else x = 1; return x; with the copy of the else branch falling through
+        let function = FunctionDefinition::Function("main".to_string(), vec![
+            Instruction::Copy(Val::Constant(1), var("x.0")),
+            Instruction::JumpIfNotZero(var("c.0"), "then".to_string()),
+            Instruction::Jump("end".to_string()),
+            Instruction::Label("then".to_string()),
+            Instruction::Binary(BinaryOp::Add, var("x.0"), Val::Constant(1), var("x.0")),
+            Instruction::Label("end".to_string()),
+            Instruction::Return(var("x.0")),
+        ]);
+
+        let mut ssa = into_ssa(function);
+        ssa.originals.clear();
+        let FunctionDefinition::Function(_, instructions) = from_ssa(ssa);
+
+        assert_eq!(instructions[2], Instruction::Copy(var("x.0.1"), var("x.0.3")));
+        assert_eq!(instructions[3], Instruction::Jump("end".to_string()));
+        assert_eq!(instructions[5], Instruction::Binary(BinaryOp::Add, var("x.0.1"), Val::Constant(1), var("x.0.2")));
+        assert_eq!(instructions[6], Instruction::Copy(var("x.0.2"), var("x.0.3")));
+    }
+
+    #[test]
+    fn from_ssa_restores_original_names() {
+        // every copy a phi became is between versions of `x`, which are never live at once, so
+        // the block split off for the critical edge is left without copies and goes as well
+        assert_eq!(from_ssa(into_ssa(conditional_assignment())), conditional_assignment());
+
+        // `x.0.1` is still read after `x.0.2` is written, so only one of them can be `x.0`
+        let mut ssa = into_ssa(FunctionDefinition::Function("main".to_string(), vec![
+            Instruction::Copy(Val::Constant(1), var("x.0")),
+            Instruction::Copy(Val::Constant(2), var("x.0")),
+            Instruction::Return(var("x.0")),
+        ]));
+        ssa.cfg.blocks.get_mut(&0).unwrap().instructions[2] = Instruction::Return(var("x.0.1"));
+
+        assert_eq!(from_ssa(ssa), FunctionDefinition::Function("main".to_string(), vec![
+            Instruction::Copy(Val::Constant(1), var("x.0")),
+            Instruction::Copy(Val::Constant(2), var("x.0.2")),
+            Instruction::Return(var("x.0")),
+        ]));
+    }
+
+    #[test]
+    fn sequentialize_swaps_through_temporary() {
+        assert_eq!(
+            sequentialize(vec![(var("b.1"), "a.1".to_string()), (var("a.1"), "b.1".to_string()), (Val::Constant(3), "c.1".to_string())]),
+            vec![
+                Instruction::Copy(Val::Constant(3), var("c.1")),
+                Instruction::Copy(var("a.1"), var("a.1.swap")),
+                Instruction::Copy(var("b.1"), var("a.1")),
+                Instruction::Copy(var("a.1.swap"), var("b.1")),
+            ]
+        );
+    }
+
+    #[test]
+    fn verify_ssa_rejects_multiple_definitions() {
+        let mut ssa = into_ssa(conditional_assignment());
+        ssa.cfg.blocks.get_mut(&1).unwrap().instructions.push(Instruction::Copy(Val::Constant(3), var("x.0.1")));
+
+        assert_eq!(verify_ssa(&ssa), Err(SsaError::MultipleDefinitions("x.0.1".to_string())));
+
+        let mut ssa = into_ssa(conditional_assignment());
+        ssa.phis.get_mut(&2).unwrap()[0].sources.pop();
+
+        assert_eq!(verify_ssa(&ssa), Err(SsaError::PhiSources(2, "x.0.3".to_string())));
+    }
+}
@@ -0,0 +1,24 @@
+//! What the optimization tests share: building TACKY for `main` and running a pass over it.
+
+use crate::compiler::optimization::Pass;
+use crate::storage::tacky::{FunctionDefinition, Instruction, Program, Val};
+
+pub fn var(name: &str) -> Val {
+    Val::Var(name.to_string())
+}
+
+/// `instructions` as the body of `main`.
+pub fn program(instructions: Vec<Instruction>) -> Program {
+    Program::Program(FunctionDefinition::Function("main".to_string(), instructions))
+}
+
+pub fn instructions(program: Program) -> Vec<Instruction> {
+    match program {
+        Program::Program(FunctionDefinition::Function(_, instructions)) => instructions,
+    }
+}
+
+/// Runs `pass` over `main` with `instructions` as its body, and returns the new body.
+pub fn run_pass(pass: Pass, body: Vec<Instruction>) -> Vec<Instruction> {
+    instructions(pass(program(body)))
+}
@@ -0,0 +1,153 @@
+//! Removes code that can never run, along with jumps and labels that became pointless.
+//!
+//! Blocks the entry can't reach go first, like everything after an early `return` and the
+//! `Return(0)` every function ends with. What is left is laid out in the original order, so a
+//! jump to the block right after it only falls through, and a label that is only reached by
+//! falling through needs no name any more.
+
+use std::collections::HashSet;
+use crate::compiler::cfg::{Cfg, NodeId};
+use crate::storage::tacky::{Instruction, Program};
+
+pub fn eliminate_unreachable_code(program: Program) -> Program {
+    match program {
+        Program::Program(function) => {
+            let mut cfg = Cfg::from_function(function);
+
+            remove_unreachable_blocks(&mut cfg);
+            remove_useless_jumps(&mut cfg);
+            remove_useless_labels(&mut cfg);
+
+            Program::Program(cfg.into_function())
+        }
+    }
+}
+
+/// Removes the blocks no path from the entry leads to.
+pub fn remove_unreachable_blocks(cfg: &mut Cfg) {
+    let mut reachable = HashSet::new();
+    let mut stack = vec![NodeId::Entry];
+
+    while let Some(node) = stack.pop() {
+        if reachable.insert(node) {
+            stack.extend_from_slice(cfg.successors(node));
+        }
+    }
+
+    let unreachable: Vec<usize> = cfg.blocks.keys().copied().filter(|&id| !reachable.contains(&NodeId::Block(id))).collect();
+
+    for id in unreachable {
+        cfg.remove_block(id);
+    }
+}
+
+/// Drops jumps whose every target is the block that follows anyway.
+fn remove_useless_jumps(cfg: &mut Cfg) {
+    let ids: Vec<usize> = cfg.blocks.keys().copied().collect();
+
+    for id in ids {
+        let next = cfg.next_node(id);
+        let block = cfg.blocks.get_mut(&id).unwrap();
+
+        let is_jump = matches!(
+            block.instructions.last(),
+            Some(Instruction::Jump(_)) | Some(Instruction::JumpIfZero(..)) | Some(Instruction::JumpIfNotZero(..)) | Some(Instruction::JumpIf(..))
+        );
+
+        // the condition of a removed conditional jump is a plain value, reading it does nothing
+        if is_jump && block.successors.iter().all(|&successor| successor == next) {
+            block.instructions.pop();
+        }
+    }
+}
+
+/// Drops labels of blocks that are only entered from the block before them. Useless jumps
+/// are gone by now, so nothing refers to those labels.
+fn remove_useless_labels(cfg: &mut Cfg) {
+    let mut previous = NodeId::Entry;
+    let ids: Vec<usize> = cfg.blocks.keys().copied().collect();
+
+    for id in ids {
+        let block = cfg.blocks.get_mut(&id).unwrap();
+
+        if matches!(block.instructions.first(), Some(Instruction::Label(_))) && block.predecessors == [previous] {
+            block.instructions.remove(0);
+        }
+
+        previous = NodeId::Block(id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::optimization::unreachable_code::eliminate_unreachable_code;
+    use crate::compiler::optimization::test_helpers::{run_pass, var};
+    use crate::storage::tacky::{Instruction, Val};
+
+    fn eliminate(instructions: Vec<Instruction>) -> Vec<Instruction> {
+        run_pass(eliminate_unreachable_code, instructions)
+    }
+
+    #[test]
+    fn eliminate_code_after_return() {
+        assert_eq!(
+            eliminate(vec![
+                Instruction::Copy(Val::Constant(1), var("a.0")),
+                Instruction::Return(var("a.0")),
+                Instruction::Copy(Val::Constant(2), var("a.0")),
+                Instruction::Return(Val::Constant(0)),
+            ]),
+            vec![
+                Instruction::Copy(Val::Constant(1), var("a.0")),
+                Instruction::Return(var("a.0")),
+            ]
+        );
+    }
+
+    #[test]
+    fn eliminate_branch_skipped_by_folded_jump() {
+        // `return 0 && b;` after constant folding turned the first test into a jump
+        assert_eq!(
+            eliminate(vec![
+                Instruction::Jump("and_false0".to_string()),
+                Instruction::JumpIfZero(var("b.0"), "and_false0".to_string()),
+                Instruction::Copy(Val::Constant(1), var("tmp.0")),
+                Instruction::Jump("and_false_end0".to_string()),
+                Instruction::Label("and_false0".to_string()),
+                Instruction::Copy(Val::Constant(0), var("tmp.0")),
+                Instruction::Label("and_false_end0".to_string()),
+                Instruction::Return(var("tmp.0")),
+                Instruction::Return(Val::Constant(0)),
+            ]),
+            vec![
+                Instruction::Copy(Val::Constant(0), var("tmp.0")),
+                Instruction::Return(var("tmp.0")),
+            ]
+        );
+    }
+
+    #[test]
+    fn eliminate_keeps_labels_that_are_jumped_to() {
+        let instructions = vec![
+            Instruction::JumpIfZero(var("a.0"), "and_false0".to_string()),
+            Instruction::Copy(Val::Constant(1), var("tmp.0")),
+            Instruction::Jump("and_false_end0".to_string()),
+            Instruction::Label("and_false0".to_string()),
+            Instruction::Copy(Val::Constant(0), var("tmp.0")),
+            Instruction::Label("and_false_end0".to_string()),
+            Instruction::Return(var("tmp.0")),
+        ];
+
+        assert_eq!(eliminate(instructions.clone()), instructions);
+
+        // a conditional jump to the next block does nothing either way
+        assert_eq!(
+            eliminate(vec![
+                Instruction::JumpIfNotZero(var("a.0"), "next".to_string()),
+                Instruction::Label("next".to_string()),
+                Instruction::Return(var("a.0")),
+            ]),
+            vec![Instruction::Return(var("a.0"))]
+        );
+    }
+}
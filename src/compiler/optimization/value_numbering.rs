@@ -0,0 +1,250 @@
+//! Reuses values that were already computed, global value numbering over SSA.
+//!
+//! In SSA a variable holds one value for good, so two instructions applying the same operator
+//! to the same variables compute the same value. Walking down the dominator tree, every pure
+//! computation is remembered while the blocks it dominates are visited, and a later one that
+//! computes it again becomes a copy of the first result. Copies give their destination the
+//! value of their source, so `b = a; c = b * y` still matches `a * y`.
+//!
+//! Leaving SSA afterwards gives the variables their names back, except where a reused value
+//! is still needed after its variable was written again. When nothing is redundant the program
+//! is returned as it came in.
+
+use std::collections::{HashMap, HashSet};
+use crate::compiler::cfg::{Cfg, NodeId};
+use crate::compiler::optimization::dominance::Dominators;
+use crate::compiler::optimization::ssa::{from_ssa, into_ssa};
+use crate::storage::tacky::{BinaryOp, Instruction, Program, UnaryOp, Val};
+
+/// A pure computation, with its operands replaced by their value numbers.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Expression {
+    Unary(UnaryOp, Val), // Unary(operator, operand)
+    Binary(BinaryOp, Val, Val), // Binary(operator, operand1, operand2)
+}
+
+/// The variable that holds each available expression, and whether a checked instruction
+/// computed it.
+type Available = HashMap<Expression, (String, bool)>;
+
+pub fn eliminate_common_subexpressions(program: Program) -> Program {
+    match program {
+        Program::Program(function) => {
+            let mut ssa = into_ssa(function.clone());
+            let dominators = Dominators::compute(&ssa.cfg);
+
+            let mut numbering = ValueNumbering { unversioned: &ssa.unversioned, values: HashMap::new(), replaced: 0 };
+            numbering.number(NodeId::Entry, &mut ssa.cfg, &dominators, Available::new());
+
+            if numbering.replaced == 0 {
+                return Program::Program(function);
+            }
+
+            Program::Program(from_ssa(ssa))
+        }
+    }
+}
+
+struct ValueNumbering<'a> {
+    unversioned: &'a HashSet<String>,
+    /// The value number of each variable that was copied, the value it was copied from.
+    values: HashMap<String, Val>,
+    replaced: usize,
+}
+
+impl ValueNumbering<'_> {
+    /// Numbers the values of `node` and the nodes it dominates, with the expressions computed
+    /// in the blocks dominating `node` available.
+    fn number(&mut self, node: NodeId, cfg: &mut Cfg, dominators: &Dominators, available: Available) {
+        let mut available = available;
+
+        if let NodeId::Block(id) = node {
+            for instruction in cfg.blocks.get_mut(&id).unwrap().instructions.iter_mut() {
+                let (expression, dst, checked) = match instruction {
+                    Instruction::Copy(src, Val::Var(dst)) => {
+                        if let (Some(value), false) = (self.value(src), self.unversioned.contains(dst)) {
+                            self.values.insert(dst.clone(), value);
+                        }
+                        continue;
+                    }
+                    Instruction::Unary(un_op, src, Val::Var(dst)) => (self.unary(un_op, src), dst.clone(), false),
+                    Instruction::Binary(bin_op, src1, src2, Val::Var(dst)) => (self.binary(bin_op, src1, src2), dst.clone(), false),
+                    Instruction::CheckedUnary(un_op, src, Val::Var(dst), _) => (self.unary(un_op, src), dst.clone(), true),
+                    Instruction::CheckedBinary(bin_op, src1, src2, Val::Var(dst), _) => (self.binary(bin_op, src1, src2), dst.clone(), true),
+                    _ => continue,
+                };
+
+                let Some(expression) = expression else { continue };
+                if self.unversioned.contains(&dst) {
+                    continue;
+                }
+
+                match available.get(&expression) {
+                    // an unchecked result can't stand in for a check that has to run
+                    Some((leader, leader_checked)) if *leader_checked || !checked => {
+                        self.values.insert(dst.clone(), Val::Var(leader.clone()));
+                        *instruction = Instruction::Copy(Val::Var(leader.clone()), Val::Var(dst));
+                        self.replaced += 1;
+                    }
+                    _ => {
+                        available.insert(expression, (dst, checked));
+                    }
+                }
+            }
+        }
+
+        for child in dominators.children(node) {
+            self.number(child, cfg, dominators, available.clone());
+        }
+    }
+
+    /// The value number of `val`, or `None` when it's a variable inline assembly writes,
+    /// whose value can change.
+    fn value(&self, val: &Val) -> Option<Val> {
+        match val {
+            Val::Var(name) if self.unversioned.contains(name) => None,
+            Val::Var(name) => Some(self.values.get(name).cloned().unwrap_or_else(|| val.clone())),
+            Val::Constant(_) => Some(val.clone()),
+        }
+    }
+
+    fn unary(&self, un_op: &UnaryOp, src: &Val) -> Option<Expression> {
+        Some(Expression::Unary(un_op.clone(), self.value(src)?))
+    }
+
+    /// The expression of a binary operation, with the operands of a commutative operator in
+    /// a fixed order and `a > b` written as `b < a`, so the ways of writing one value match.
+    fn binary(&self, bin_op: &BinaryOp, src1: &Val, src2: &Val) -> Option<Expression> {
+        let src1 = self.value(src1)?;
+        let src2 = self.value(src2)?;
+
+        let expression = match bin_op {
+            BinaryOp::Add | BinaryOp::Multiply | BinaryOp::Equal | BinaryOp::NotEqual if src2 < src1 => {
+                Expression::Binary(bin_op.clone(), src2, src1)
+            }
+            BinaryOp::GreaterThan => Expression::Binary(BinaryOp::LessThan, src2, src1),
+            BinaryOp::GreaterOrEqual => Expression::Binary(BinaryOp::LessOrEqual, src2, src1),
+            _ => Expression::Binary(bin_op.clone(), src1, src2),
+        };
+
+        Some(expression)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::optimization::ssa::{from_ssa, into_ssa};
+    use crate::compiler::optimization::test_helpers::{run_pass, var};
+    use crate::compiler::optimization::value_numbering::eliminate_common_subexpressions;
+    use crate::storage::tacky::{BinaryOp, FunctionDefinition, Instruction, Val};
+
+    fn eliminate(instructions: Vec<Instruction>) -> Vec<Instruction> {
+        run_pass(eliminate_common_subexpressions, instructions)
+    }
+
+    #[test]
+    fn eliminate_repeated_computation_in_block() {
+        // return x * y + y * x;
+        assert_eq!(
+            eliminate(vec![
+                Instruction::Binary(BinaryOp::Multiply, var("x.0"), var("y.0"), var("tmp.0")),
+                Instruction::Binary(BinaryOp::Multiply, var("y.0"), var("x.0"), var("tmp.1")),
+                Instruction::Binary(BinaryOp::Add, var("tmp.0"), var("tmp.1"), var("tmp.2")),
+                Instruction::Return(var("tmp.2")),
+            ]),
+            vec![
+                Instruction::Binary(BinaryOp::Multiply, var("x.0"), var("y.0"), var("tmp.0")),
+                Instruction::Copy(var("tmp.0"), var("tmp.1")),
+                Instruction::Binary(BinaryOp::Add, var("tmp.0"), var("tmp.1"), var("tmp.2")),
+                Instruction::Return(var("tmp.2")),
+            ]
+        );
+    }
+
+    #[test]
+    fn eliminate_computation_of_dominating_block() {
+        // int b = a + 1; int c = a && (b = a + 1); return (a + 1) + b;
+        let instructions = eliminate(vec![
+            Instruction::Binary(BinaryOp::Add, var("a.0"), Val::Constant(1), var("tmp.0")),
+            Instruction::Copy(var("tmp.0"), var("b.0")),
+            Instruction::JumpIfZero(var("a.0"), "and_false0".to_string()),
+            Instruction::Binary(BinaryOp::Add, var("a.0"), Val::Constant(1), var("tmp.1")),
+            Instruction::Copy(var("tmp.1"), var("b.0")),
+            Instruction::Label("and_false0".to_string()),
+            Instruction::Binary(BinaryOp::Add, var("a.0"), Val::Constant(1), var("tmp.2")),
+            Instruction::Binary(BinaryOp::Add, var("tmp.2"), var("b.0"), var("tmp.3")),
+            Instruction::Return(var("tmp.3")),
+        ]);
+
+        // both later `a + 1` are dominated by the first one
+        let additions = instructions
+            .iter()
+            .filter(|instruction| matches!(instruction, Instruction::Binary(BinaryOp::Add, _, Val::Constant(1), _)))
+            .count();
+        assert_eq!(additions, 1);
+        assert!(instructions.contains(&Instruction::Copy(var("tmp.0"), var("tmp.1"))));
+        assert!(instructions.contains(&Instruction::Copy(var("tmp.0"), var("tmp.2"))));
+    }
+
+    #[test]
+    fn eliminate_keeps_computations_of_other_branches() {
+        // return (a && (b = x * y)) + x * y; the multiplication in the branch doesn't always run
+        let instructions = vec![
+            Instruction::JumpIfZero(var("a.0"), "and_false0".to_string()),
+            Instruction::Binary(BinaryOp::Multiply, var("x.0"), var("y.0"), var("tmp.0")),
+            Instruction::Copy(var("tmp.0"), var("b.0")),
+            Instruction::Label("and_false0".to_string()),
+            Instruction::Binary(BinaryOp::Multiply, var("x.0"), var("y.0"), var("tmp.1")),
+            Instruction::Return(var("tmp.1")),
+        ];
+
+        // nothing was redundant, so the variables keep their names
+        assert_eq!(eliminate(instructions.clone()), instructions);
+    }
+
+    #[test]
+    fn eliminate_keeps_checks_that_did_not_run() {
+        let instructions = vec![
+            Instruction::Binary(BinaryOp::Add, var("x.0"), Val::Constant(1), var("tmp.0")),
+            Instruction::CheckedBinary(BinaryOp::Add, var("x.0"), Val::Constant(1), var("tmp.1"), "overflow".to_string()),
+            Instruction::Binary(BinaryOp::Add, var("tmp.0"), var("tmp.1"), var("tmp.2")),
+            Instruction::Return(var("tmp.2")),
+        ];
+
+        assert_eq!(eliminate(instructions.clone()), instructions);
+
+        // the other way around the check already ran
+        assert_eq!(
+            eliminate(vec![
+                Instruction::CheckedBinary(BinaryOp::Add, var("x.0"), Val::Constant(1), var("tmp.0"), "overflow".to_string()),
+                Instruction::Binary(BinaryOp::Add, var("x.0"), Val::Constant(1), var("tmp.1")),
+                Instruction::Return(var("tmp.1")),
+            ])[1],
+            Instruction::Copy(var("tmp.0"), var("tmp.1"))
+        );
+    }
+
+    #[test]
+    fn eliminate_keeps_names_through_ssa() {
+        // x = a * b; x = 5; return x + a * b; the first `x` is still needed after the second
+        let eliminated = eliminate(vec![
+            Instruction::Binary(BinaryOp::Multiply, var("a.0"), var("b.0"), var("x.0")),
+            Instruction::Copy(Val::Constant(5), var("x.0")),
+            Instruction::Binary(BinaryOp::Multiply, var("a.0"), var("b.0"), var("tmp.0")),
+            Instruction::Binary(BinaryOp::Add, var("x.0"), var("tmp.0"), var("tmp.1")),
+            Instruction::Return(var("tmp.1")),
+        ]);
+
+        assert_eq!(eliminated, vec![
+            Instruction::Binary(BinaryOp::Multiply, var("a.0"), var("b.0"), var("x.0")),
+            Instruction::Copy(Val::Constant(5), var("x.0.2")),
+            Instruction::Copy(var("x.0"), var("tmp.0")),
+            Instruction::Binary(BinaryOp::Add, var("x.0.2"), var("tmp.0"), var("tmp.1")),
+            Instruction::Return(var("tmp.1")),
+        ]);
+
+        // another round through SSA doesn't add suffixes
+        let function = FunctionDefinition::Function("main".to_string(), eliminated.clone());
+        assert_eq!(from_ssa(into_ssa(function)), FunctionDefinition::Function("main".to_string(), eliminated));
+    }
+}
@@ -1,27 +1,88 @@
+use std::collections::HashSet;
+use crate::compiler::standard::Standard;
 use crate::compiler::token::Token;
-use crate::storage::ast::{AstBinaryOp, AstBlockItem, AstDeclaration, AstExpression, AstFunctionDefinition, AstProgram, AstStatement, AstUnaryOp};
+use crate::storage::ast::{AstAsmOperand, AstBinaryOp, AstBlockItem, AstDeclaration, AstEnumerator, AstExpression, AstFunctionDefinition, AstIncrementOp, AstProgram, AstStatement, AstType, AstUnaryOp};
 use crate::storage::ast::AstExpression::Assignment;
+use crate::storage::position::Position;
 
 #[derive(Debug, PartialEq)]
 pub struct ParserErr(String);
 
+/// State the parser has to carry between block items.
+///
+/// Typedef names are tracked here so the parser can tell `T x;` (a declaration) apart from
+/// `x = T;` (an expression) without waiting for semantic analysis.
+///
+/// It also keeps the token positions produced by the tokenizer, so nodes that semantic
+/// analysis reports errors for can remember where they came from, and the language
+/// standard that decides which constructs are accepted.
+#[derive(Debug, Default)]
+pub struct ParserContext {
+    typedef_names: HashSet<String>,
+    positions: Vec<Position>,
+    standard: Standard,
+}
+
+impl ParserContext {
+    pub fn new(positions: Vec<Position>, standard: Standard) -> Self {
+        ParserContext { typedef_names: HashSet::new(), positions, standard }
+    }
+
+    fn is_typedef_name(&self, name: &str) -> bool {
+        self.typedef_names.contains(name)
+    }
+
+    /// Position of the first remaining token.
+    ///
+    /// Tokens are only ever removed from the front, so the number of tokens left tells us
+    /// how far into the original token stream we are.
+    fn position_of_next(&self, tokens: &[Token]) -> Position {
+        self.positions
+            .len()
+            .checked_sub(tokens.len())
+            .and_then(|index| self.positions.get(index))
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
 pub fn parse_program(tokens: &mut Vec<Token>) -> Result<AstProgram, ParserErr> {
-    let function = match parse_function(tokens) {
+    parse_program_with_context(tokens, ParserContext::default())
+}
+
+pub fn parse_program_with_context(tokens: &mut Vec<Token>, mut context: ParserContext) -> Result<AstProgram, ParserErr> {
+    let mut declarations = parse_file_scope_declarations(tokens, &context)?;
+
+    let function = match parse_function(tokens, &mut context) {
         Ok(exp) => exp,
         Err(err) => return Err(err),
     };
 
+    declarations.append(&mut parse_file_scope_declarations(tokens, &context)?);
+
     if tokens.len() != 0 {
         Err(ParserErr("Syntax error!".to_string()))
     } else {
-        Ok(AstProgram::Program(function))
+        Ok(AstProgram::Program(declarations, function))
+    }
+}
+
+/// Static assertions are the only declarations allowed outside of the function for now.
+fn parse_file_scope_declarations(tokens: &mut Vec<Token>, context: &ParserContext) -> Result<Vec<AstDeclaration>, ParserErr> {
+    let mut declarations = vec![];
+
+    while tokens.first() == Some(&Token::StaticAssert) {
+        declarations.push(parse_static_assert(tokens, context)?);
     }
+
+    Ok(declarations)
 }
 
-fn parse_function(tokens: &mut Vec<Token>) -> Result<AstFunctionDefinition, ParserErr> {
+fn parse_function(tokens: &mut Vec<Token>, context: &mut ParserContext) -> Result<AstFunctionDefinition, ParserErr> {
     expect(&Token::Integer, tokens)?;
     tokens.remove(0);
 
+    let position = context.position_of_next(tokens);
     let identifier = if let Some(Token::Identifier(identifier_name)) = tokens.first() {
         identifier_name.clone()
     } else {
@@ -45,9 +106,19 @@ fn parse_function(tokens: &mut Vec<Token>) -> Result<AstFunctionDefinition, Pars
     )?;
 
     let mut function_body = vec![];
+    let mut seen_statement = false;
 
     while tokens.first() != Some(&Token::CloseBrace) {
-        let next_block_item = parse_block_item(tokens)?;
+        let next_block_item = parse_block_item(tokens, context)?;
+
+        match next_block_item {
+            AstBlockItem::Statement(_) => seen_statement = true,
+            AstBlockItem::Declaration(_) if seen_statement && context.standard < Standard::C99 => {
+                return Err(ParserErr(context.standard.not_allowed("declarations after statements")));
+            }
+            AstBlockItem::Declaration(_) => (),
+        }
+
         function_body.push(next_block_item);
     }
 
@@ -59,33 +130,50 @@ fn parse_function(tokens: &mut Vec<Token>) -> Result<AstFunctionDefinition, Pars
     expect(&Token::CloseBrace, tokens)?;
     tokens.remove(0);
 
-    Ok(AstFunctionDefinition::Function(identifier, function_body))
+    Ok(AstFunctionDefinition::Function(identifier, function_body, position))
 }
 
-fn parse_block_item(tokens: &mut Vec<Token>) -> Result<AstBlockItem, ParserErr> {
-    if tokens.first() == Some(&Token::Integer) {
-        Ok(AstBlockItem::Declaration(parse_declaration(tokens)?))
+fn parse_block_item(tokens: &mut Vec<Token>, context: &mut ParserContext) -> Result<AstBlockItem, ParserErr> {
+    if is_declaration_start(tokens, context) {
+        Ok(AstBlockItem::Declaration(parse_declaration(tokens, context)?))
     }
     else {
-        Ok(AstBlockItem::Statement(parse_statement(tokens)?))
+        Ok(AstBlockItem::Statement(parse_statement(tokens, context)?))
     }
 }
 
-fn parse_declaration(tokens: &mut Vec<Token>) -> Result<AstDeclaration, ParserErr> {
-    expect(&Token::Integer, tokens)?;
-    tokens.remove(0);
+/// A block item is a declaration if it starts with a type specifier or with `typedef`.
+/// Identifiers only count when they name a typedef, which is the classic "lexer hack".
+fn is_declaration_start(tokens: &[Token], context: &ParserContext) -> bool {
+    match tokens.first() {
+        Some(Token::Integer | Token::Typedef | Token::Enum | Token::Const | Token::StaticAssert) => true,
+        Some(Token::Identifier(identifier)) => context.is_typedef_name(identifier),
+        _ => false,
+    }
+}
 
-    let identifier = if let Some(Token::Identifier(identifier_name)) = tokens.first() {
-        identifier_name.clone()
-    } else {
-        return Err(ParserErr("Syntax error!".to_string()));
-    };
-    tokens.remove(0);
+fn parse_declaration(tokens: &mut Vec<Token>, context: &mut ParserContext) -> Result<AstDeclaration, ParserErr> {
+    if tokens.first() == Some(&Token::Typedef) {
+        return parse_typedef(tokens, context);
+    }
+
+    if is_enum_definition(tokens) {
+        return parse_enum(tokens, context);
+    }
+
+    if tokens.first() == Some(&Token::StaticAssert) {
+        return parse_static_assert(tokens, context);
+    }
+
+    let declaration_type = parse_type(tokens, context)?;
+
+    let position = context.position_of_next(tokens);
+    let identifier = parse_identifier(tokens)?;
 
     let expression;
     if tokens.first() == Some(&Token::Equal) {
         tokens.remove(0);
-        expression = Some(parse_expression(tokens, 0)?);
+        expression = Some(parse_expression(tokens, 0, context)?);
     } else {
         expression = None;
     };
@@ -94,14 +182,185 @@ fn parse_declaration(tokens: &mut Vec<Token>) -> Result<AstDeclaration, ParserEr
     expect(&Token::Semicolon, tokens)?;
     tokens.remove(0);
 
-    Ok(AstDeclaration::Declaration(identifier, expression))
+    Ok(AstDeclaration::Declaration(identifier, declaration_type, expression, position))
+}
+
+/// ```"typedef" <type> <identifier> ";"```
+fn parse_typedef(tokens: &mut Vec<Token>, context: &mut ParserContext) -> Result<AstDeclaration, ParserErr> {
+    expect(&Token::Typedef, tokens)?;
+    tokens.remove(0);
+
+    let aliased_type = parse_type(tokens, context)?;
+    let position = context.position_of_next(tokens);
+    let identifier = parse_identifier(tokens)?;
+
+    expect(&Token::Semicolon, tokens)?;
+    tokens.remove(0);
+
+    context.typedef_names.insert(identifier.clone());
+
+    Ok(AstDeclaration::Typedef(identifier, aliased_type, position))
+}
+
+/// ```"_Static_assert" "(" <exp> "," <string> ")" ";"```
+fn parse_static_assert(tokens: &mut Vec<Token>, context: &ParserContext) -> Result<AstDeclaration, ParserErr> {
+    if context.standard < Standard::C11 {
+        return Err(ParserErr(context.standard.not_allowed("static assertions")));
+    }
+
+    let position = context.position_of_next(tokens);
+
+    expect_sequence_with_remove(&vec![Token::StaticAssert, Token::OpenParen], tokens)?;
+
+    let condition = parse_expression(tokens, 0, context)?;
+
+    expect(&Token::Comma, tokens)?;
+    tokens.remove(0);
+
+    let message = parse_string_literal(tokens)?;
+
+    expect_sequence_with_remove(&vec![Token::CloseParen, Token::Semicolon], tokens)?;
+
+    Ok(AstDeclaration::StaticAssert(condition, message, position))
+}
+
+/// `enum` followed by an enumerator list, optionally preceded by a tag.
+fn is_enum_definition(tokens: &[Token]) -> bool {
+    matches!(
+        (tokens.first(), tokens.get(1), tokens.get(2)),
+        (Some(Token::Enum), Some(Token::OpenBrace), _) | (Some(Token::Enum), Some(Token::Identifier(_)), Some(Token::OpenBrace))
+    )
+}
+
+/// ```"enum" [ <identifier> ] "{" <enumerator> { "," <enumerator> } [ "," ] "}" ";"```
+fn parse_enum(tokens: &mut Vec<Token>, context: &ParserContext) -> Result<AstDeclaration, ParserErr> {
+    let position = context.position_of_next(tokens);
+
+    expect(&Token::Enum, tokens)?;
+    tokens.remove(0);
+
+    let tag = if let Some(Token::Identifier(tag)) = tokens.first() {
+        let tag = tag.clone();
+        tokens.remove(0);
+        Some(tag)
+    } else {
+        None
+    };
+
+    expect(&Token::OpenBrace, tokens)?;
+    tokens.remove(0);
+
+    let mut enumerators = vec![];
+
+    while tokens.first() != Some(&Token::CloseBrace) {
+        let enumerator_position = context.position_of_next(tokens);
+        let identifier = parse_identifier(tokens)?;
+
+        let value = if tokens.first() == Some(&Token::Equal) {
+            tokens.remove(0);
+            Some(parse_expression(tokens, 0, context)?)
+        } else {
+            None
+        };
+
+        enumerators.push(AstEnumerator::Enumerator(identifier, value, enumerator_position));
+
+        if tokens.first() == Some(&Token::Comma) {
+            tokens.remove(0);
+
+            if tokens.first() == Some(&Token::CloseBrace) && context.standard < Standard::C99 {
+                return Err(ParserErr(context.standard.not_allowed("trailing commas in enumerator lists")));
+            }
+        } else {
+            break;
+        }
+    }
+
+    if enumerators.is_empty() {
+        return Err(ParserErr("expected at least one enumerator".to_string()));
+    }
+
+    expect_sequence_with_remove(&vec![Token::CloseBrace, Token::Semicolon], tokens)?;
+
+    Ok(AstDeclaration::Enum(tag, enumerators, position))
+}
+
+/// ```
+/// <type> ::= { "const" } <specifier> { "const" } { "*" { "const" } }
+/// <specifier> ::= "int" | "enum" <identifier> | <typedef-name>
+/// ```
+fn parse_type(tokens: &mut Vec<Token>, context: &ParserContext) -> Result<AstType, ParserErr> {
+    let mut is_const = parse_const_qualifiers(tokens);
+
+    let specifier = match tokens.first() {
+        Some(Token::Integer) => {
+            tokens.remove(0);
+            AstType::Int
+        }
+        Some(Token::Enum) => {
+            tokens.remove(0);
+            AstType::Enum(parse_identifier(tokens)?)
+        }
+        Some(Token::Identifier(identifier)) if context.is_typedef_name(identifier) => {
+            let identifier = identifier.clone();
+            tokens.remove(0);
+            AstType::Typedef(identifier)
+        }
+        Some(token) => return Err(ParserErr(format!("expected type specifier, got {:?}", token))),
+        None => return Err(ParserErr("No more tokens".to_string())),
+    };
+
+    // `const int` and `int const` are the same type
+    is_const |= parse_const_qualifiers(tokens);
+
+    let mut parsed_type = if is_const {
+        AstType::Const(Box::new(specifier))
+    } else {
+        specifier
+    };
+
+    while tokens.first() == Some(&Token::Asterisk) {
+        tokens.remove(0);
+        parsed_type = AstType::Pointer(Box::new(parsed_type));
+
+        if parse_const_qualifiers(tokens) {
+            parsed_type = AstType::Const(Box::new(parsed_type));
+        }
+    }
+
+    Ok(parsed_type)
+}
+
+/// Consumes a run of `const` keywords and reports whether there was any.
+fn parse_const_qualifiers(tokens: &mut Vec<Token>) -> bool {
+    let mut found = false;
+
+    while tokens.first() == Some(&Token::Const) {
+        tokens.remove(0);
+        found = true;
+    }
+
+    found
+}
+
+fn parse_identifier(tokens: &mut Vec<Token>) -> Result<String, ParserErr> {
+    let identifier = if let Some(Token::Identifier(identifier_name)) = tokens.first() {
+        identifier_name.clone()
+    } else {
+        return Err(ParserErr("Syntax error!".to_string()));
+    };
+    tokens.remove(0);
+
+    Ok(identifier)
 }
 
-fn parse_statement(tokens: &mut Vec<Token>) -> Result<AstStatement, ParserErr> {
+fn parse_statement(tokens: &mut Vec<Token>, context: &ParserContext) -> Result<AstStatement, ParserErr> {
+    let position = context.position_of_next(tokens);
+
     if tokens.first() == Some(&Token::Return) {
         tokens.remove(0);
 
-        let return_val = match parse_expression(tokens, 0) {
+        let return_val = match parse_expression(tokens, 0, context) {
             Ok(exp) => {
                 exp
             }
@@ -113,7 +372,10 @@ fn parse_statement(tokens: &mut Vec<Token>) -> Result<AstStatement, ParserErr> {
             _ => tokens.remove(0),
         };
 
-        Ok(AstStatement::Return(return_val))
+        Ok(AstStatement::Return(return_val, position))
+    }
+    else if tokens.first() == Some(&Token::Asm) {
+        parse_inline_asm(tokens, context)
     }
     else if tokens.first() == Some(&Token::Semicolon) {
         tokens.remove(0);
@@ -121,18 +383,117 @@ fn parse_statement(tokens: &mut Vec<Token>) -> Result<AstStatement, ParserErr> {
         Ok(AstStatement::Null)
     }
     else {
-        let expression = parse_expression(tokens, 0)?;
+        let expression = parse_expression(tokens, 0, context)?;
 
         expect(&Token::Semicolon, tokens)?;
         tokens.remove(0);
 
-        Ok(AstStatement::Expression(expression))
+        Ok(AstStatement::Expression(expression, position))
+    }
+}
+
+/// ```
+/// <asm> ::= "asm" [ "volatile" ] "(" <string> { <string> }
+///           [ ":" [ <operands> ] [ ":" [ <operands> ] [ ":" [ <clobbers> ] ] ] ] ")" ";"
+/// <operands> ::= <operand> { "," <operand> }
+/// <clobbers> ::= <string> { "," <string> }
+/// ```
+/// Basic asm (without any `:`) doesn't treat `%` specially, so its template gets every `%`
+/// doubled here and the rest of the compiler only has to deal with extended asm.
+fn parse_inline_asm(tokens: &mut Vec<Token>, context: &ParserContext) -> Result<AstStatement, ParserErr> {
+    let position = context.position_of_next(tokens);
+
+    expect(&Token::Asm, tokens)?;
+    tokens.remove(0);
+
+    // every asm statement is treated as volatile, so the qualifier changes nothing
+    if tokens.first() == Some(&Token::Volatile) {
+        tokens.remove(0);
+    }
+
+    expect(&Token::OpenParen, tokens)?;
+    tokens.remove(0);
+
+    // adjacent string literals are concatenated, which is how multi-line templates are written
+    let mut template = parse_string_literal(tokens)?;
+    while let Some(Token::StringLiteral(_)) = tokens.first() {
+        template.push_str(&parse_string_literal(tokens)?);
+    }
+
+    let mut outputs = vec![];
+    let mut inputs = vec![];
+    let mut clobbers = vec![];
+
+    if tokens.first() != Some(&Token::Colon) {
+        template = template.replace('%', "%%");
+    } else {
+        tokens.remove(0);
+        outputs = parse_asm_operands(tokens, context)?;
+
+        if tokens.first() == Some(&Token::Colon) {
+            tokens.remove(0);
+            inputs = parse_asm_operands(tokens, context)?;
+
+            if tokens.first() == Some(&Token::Colon) {
+                tokens.remove(0);
+
+                if let Some(Token::StringLiteral(_)) = tokens.first() {
+                    clobbers.push(parse_string_literal(tokens)?);
+
+                    while tokens.first() == Some(&Token::Comma) {
+                        tokens.remove(0);
+                        clobbers.push(parse_string_literal(tokens)?);
+                    }
+                }
+            }
+        }
+    }
+
+    expect_sequence_with_remove(&vec![Token::CloseParen, Token::Semicolon], tokens)?;
+
+    Ok(AstStatement::InlineAsm(template, outputs, inputs, clobbers, position))
+}
+
+/// ```<operand> ::= <string> "(" <exp> ")"```
+///
+/// The list may be empty, which is how `asm("..." : : "r"(x))` skips the outputs.
+fn parse_asm_operands(tokens: &mut Vec<Token>, context: &ParserContext) -> Result<Vec<AstAsmOperand>, ParserErr> {
+    let mut operands = vec![];
+
+    while let Some(Token::StringLiteral(_)) = tokens.first() {
+        let constraint = parse_string_literal(tokens)?;
+
+        expect(&Token::OpenParen, tokens)?;
+        tokens.remove(0);
+
+        let expression = parse_expression(tokens, 0, context)?;
+
+        expect(&Token::CloseParen, tokens)?;
+        tokens.remove(0);
+
+        operands.push(AstAsmOperand::Operand(constraint, expression));
+
+        if tokens.first() != Some(&Token::Comma) {
+            break;
+        }
+        tokens.remove(0);
+    }
+
+    Ok(operands)
+}
+
+fn parse_string_literal(tokens: &mut Vec<Token>) -> Result<String, ParserErr> {
+    expect(&Token::StringLiteral(String::new()), tokens)?;
+
+    match tokens.remove(0) {
+        Token::StringLiteral(string) => Ok(string),
+        _ => unreachable!(),
     }
 }
 
 /// ```<exp> ::= <int> | <unop> <exp> | "(" <exp> ")"```
-fn parse_expression(tokens: &mut Vec<Token>, min_prec: u8) -> Result<AstExpression, ParserErr> {
-    let mut left = parse_factor(tokens)?;
+fn parse_expression(tokens: &mut Vec<Token>, min_prec: u8, context: &ParserContext) -> Result<AstExpression, ParserErr> {
+    let mut left = parse_factor(tokens, context)?;
     tokens.remove(0);
 
     while let Some(
@@ -150,21 +511,35 @@ fn parse_expression(tokens: &mut Vec<Token>, min_prec: u8) -> Result<AstExpressi
         | token @ Token::LessThanEqual
         | token @ Token::GreaterThan
         | token @ Token::GreaterThanEqual
+        | token @ Token::PlusEqual
+        | token @ Token::HyphenEqual
+        | token @ Token::AsteriskEqual
+        | token @ Token::ForwardSlashEqual
+        | token @ Token::PercentEqual
     ) = tokens.first()
     {
         let curr_prec = binary_op_precedence(token);
         if curr_prec >= min_prec {
             match token {
                 Token::Equal => {
+                    let position = context.position_of_next(tokens);
                     tokens.remove(0);
-                    let right = parse_expression(tokens, curr_prec)?;
-                    left = Assignment(Box::new(left), Box::new(right));
+                    let right = parse_expression(tokens, curr_prec, context)?;
+                    left = Assignment(Box::new(left), Box::new(right), position);
+                },
+                Token::PlusEqual | Token::HyphenEqual | Token::AsteriskEqual | Token::ForwardSlashEqual | Token::PercentEqual => {
+                    let position = context.position_of_next(tokens);
+                    let operator = parse_compound_assignment_operator(tokens)?;
+                    tokens.remove(0);
+                    let right = parse_expression(tokens, curr_prec, context)?;
+                    left = AstExpression::CompoundAssignment(operator, Box::new(left), Box::new(right), position);
                 },
                 _ => {
+                    let position = context.position_of_next(tokens);
                     let operator = parse_binary_operator(tokens)?;
                     tokens.remove(0);
-                    let right = parse_expression(tokens, curr_prec + 1)?;
-                    left = AstExpression::Binary(operator, Box::new(left), Box::new(right));
+                    let right = parse_expression(tokens, curr_prec + 1, context)?;
+                    left = AstExpression::Binary(operator, Box::new(left), Box::new(right), position);
                 }
             }
         } else {
@@ -175,23 +550,42 @@ fn parse_expression(tokens: &mut Vec<Token>, min_prec: u8) -> Result<AstExpressi
     Ok(left)
 }
 
-fn parse_factor(tokens: &mut Vec<Token>) -> Result<AstExpression, ParserErr> {
+fn parse_factor(tokens: &mut Vec<Token>, context: &ParserContext) -> Result<AstExpression, ParserErr> {
     match tokens.first() {
         Some(token) => match token {
-            Token::Identifier(identifier) => Ok(AstExpression::Var(identifier.clone())),
+            Token::Identifier(identifier) if context.is_typedef_name(identifier) => Err(ParserErr(format!(
+                "expected expression, got type name {}",
+                identifier
+            ))),
+            Token::Identifier(identifier) => {
+                let var = AstExpression::Var(identifier.clone(), context.position_of_next(tokens));
+                Ok(parse_postfix_operators(tokens, var, context))
+            },
             Token::Constant(num) => Ok(AstExpression::Constant(num.clone())),
             Token::Tilde | Token::Hyphen | Token::LogicalNot => {
+                let position = context.position_of_next(tokens);
                 let operator = parse_unary_operator(tokens)?;
                 tokens.remove(0);
-                let inner_expr = parse_factor(tokens)?;
-                Ok(AstExpression::Unary(operator, Box::new(inner_expr)))
+                let inner_expr = parse_factor(tokens, context)?;
+                Ok(AstExpression::Unary(operator, Box::new(inner_expr), position))
+            }
+            Token::DoublePlus | Token::DoubleHyphen => {
+                let position = context.position_of_next(tokens);
+                let operator = match token {
+                    Token::DoublePlus => AstIncrementOp::PreIncrement,
+                    _ => AstIncrementOp::PreDecrement,
+                };
+                tokens.remove(0);
+                let inner_expr = parse_factor(tokens, context)?;
+                Ok(AstExpression::Increment(operator, Box::new(inner_expr), position))
             }
             Token::OpenParen => {
                 tokens.remove(0);
-                let inner_expr = parse_expression(tokens, 0)?;
+                let inner_expr = parse_expression(tokens, 0, context)?;
                 expect(&Token::CloseParen, tokens)?;
 
-                Ok(inner_expr)
+                let parenthesized = AstExpression::Parenthesized(Box::new(inner_expr));
+                Ok(parse_postfix_operators(tokens, parenthesized, context))
             }
             _ => Err(ParserErr(format!(
                 "expected {:?}, got {:?}",
@@ -203,6 +597,27 @@ fn parse_factor(tokens: &mut Vec<Token>) -> Result<AstExpression, ParserErr> {
     }
 }
 
+/// Wraps `operand` in any `++`/`--` that directly follow it.
+///
+/// Like the rest of [`parse_factor`], this leaves the last token of the factor in place, so
+/// `tokens[0]` is the operand's last token when called and the last postfix operator after.
+fn parse_postfix_operators(tokens: &mut Vec<Token>, operand: AstExpression, context: &ParserContext) -> AstExpression {
+    let mut result = operand;
+
+    while let Some(token @ (Token::DoublePlus | Token::DoubleHyphen)) = tokens.get(1) {
+        let operator = match token {
+            Token::DoublePlus => AstIncrementOp::PostIncrement,
+            _ => AstIncrementOp::PostDecrement,
+        };
+        tokens.remove(0);
+
+        let position = context.position_of_next(tokens);
+        result = AstExpression::Increment(operator, Box::new(result), position);
+    }
+
+    result
+}
+
 fn parse_unary_operator(tokens: &Vec<Token>) -> Result<AstUnaryOp, ParserErr> {
     match tokens.first() {
         Some(token) => match token {
@@ -243,6 +658,23 @@ fn parse_binary_operator(tokens: &Vec<Token>) -> Result<AstBinaryOp, ParserErr>
     }
 }
 
+fn parse_compound_assignment_operator(tokens: &[Token]) -> Result<AstBinaryOp, ParserErr> {
+    match tokens.first() {
+        Some(token) => match token {
+            Token::PlusEqual => Ok(AstBinaryOp::Add),
+            Token::HyphenEqual => Ok(AstBinaryOp::Subtract),
+            Token::AsteriskEqual => Ok(AstBinaryOp::Multiply),
+            Token::ForwardSlashEqual => Ok(AstBinaryOp::Divide),
+            Token::PercentEqual => Ok(AstBinaryOp::Remainder),
+            _ => Err(ParserErr(format!(
+                "expected token signifying compound assignment, got {:?}",
+                token
+            ))),
+        },
+        None => Err(ParserErr("No more tokens".to_string())),
+    }
+}
+
 fn binary_op_precedence(binary_op: &Token) -> u8 {
     match binary_op {
         Token::Asterisk | Token::ForwardSlash | Token::Percent => 50,
@@ -251,7 +683,12 @@ fn binary_op_precedence(binary_op: &Token) -> u8 {
         Token::LogicalEqual | Token::LogicalNotEqual => 35,
         Token::LogicalAnd => 10,
         Token::LogicalOr => 5,
-        Token::Equal => 1,
+        Token::Equal
+        | Token::PlusEqual
+        | Token::HyphenEqual
+        | Token::AsteriskEqual
+        | Token::ForwardSlashEqual
+        | Token::PercentEqual => 1,
         _ => unreachable!(),
     }
 }
@@ -266,6 +703,7 @@ pub fn expect(expected: &Token, tokens: &Vec<Token>) -> Result<(), ParserErr> {
     match (expected, actual) {
         (Token::Identifier(_), Token::Identifier(_)) => Ok(()),
         (Token::Constant(_), Token::Constant(_)) => Ok(()),
+        (Token::StringLiteral(_), Token::StringLiteral(_)) => Ok(()),
         _ => match expected == actual {
             true => Ok(()),
             false => Err(ParserErr(format!(
@@ -297,10 +735,12 @@ pub fn expect_sequence_with_remove(
 #[cfg(test)]
 mod tests {
     use crate::compiler::parser::{
-        expect, parse_expression, parse_function, parse_program, parse_statement, ParserErr,
+        expect, parse_block_item, parse_expression, parse_function, parse_program, parse_program_with_context, parse_statement, ParserContext, ParserErr,
     };
+    use crate::compiler::standard::Standard;
     use crate::compiler::token::Token;
-    use crate::storage::ast::{AstBlockItem, AstExpression, AstFunctionDefinition, AstProgram, AstStatement};
+    use crate::storage::ast::{AstAsmOperand, AstBinaryOp, AstBlockItem, AstDeclaration, AstEnumerator, AstExpression, AstFunctionDefinition, AstIncrementOp, AstProgram, AstStatement, AstType, AstUnaryOp};
+    use crate::storage::position::Position;
 
     #[test]
     fn expect_basic_pass() {
@@ -329,13 +769,13 @@ mod tests {
     fn parse_expression_basic_pass() {
         let mut tokens = vec![Token::Constant(15), Token::Semicolon];
 
-        let expr = parse_expression(&mut tokens, 0);
+        let expr = parse_expression(&mut tokens, 0, &ParserContext::default());
 
         assert_eq!(expr, Ok(AstExpression::Constant(15)));
 
         let mut tokens = vec![Token::Constant(15)];
 
-        let expr = parse_expression(&mut tokens, 0);
+        let expr = parse_expression(&mut tokens, 0, &ParserContext::default());
 
         assert_eq!(expr, Ok(AstExpression::Constant(15)));
     }
@@ -344,7 +784,7 @@ mod tests {
     fn parse_expression_basic_fail() {
         let mut tokens = vec![Token::Semicolon];
 
-        let expr = parse_expression(&mut tokens, 0);
+        let expr = parse_expression(&mut tokens, 0, &ParserContext::default());
 
         assert_eq!(
             expr,
@@ -365,11 +805,11 @@ mod tests {
             Token::CloseBrace,
         ];
 
-        let statement = parse_statement(&mut tokens);
+        let statement = parse_statement(&mut tokens, &ParserContext::default());
 
         assert_eq!(
             statement,
-            Ok(AstStatement::Return(AstExpression::Constant(2)))
+            Ok(AstStatement::Return(AstExpression::Constant(2), Position::default()))
         );
         assert_eq!(tokens, vec![Token::CloseBrace]);
         assert_eq!(tokens.len(), 1);
@@ -379,11 +819,11 @@ mod tests {
     fn parse_statement_pass_with_no_more_tokens() {
         let mut tokens = vec![Token::Return, Token::Constant(2), Token::Semicolon];
 
-        let statement = parse_statement(&mut tokens);
+        let statement = parse_statement(&mut tokens, &ParserContext::default());
 
         assert_eq!(
             statement,
-            Ok(AstStatement::Return(AstExpression::Constant(2)))
+            Ok(AstStatement::Return(AstExpression::Constant(2), Position::default()))
         );
         assert_eq!(tokens.len(), 0);
         assert_eq!(tokens, vec![]);
@@ -393,7 +833,7 @@ mod tests {
     fn parse_statement_fail_with_incorrect_token_at_beginning() {
         let mut tokens = vec![Token::Integer, Token::Constant(2), Token::Semicolon];
 
-        let statement = parse_statement(&mut tokens);
+        let statement = parse_statement(&mut tokens, &ParserContext::default());
 
         assert_eq!(
             statement,
@@ -418,11 +858,11 @@ mod tests {
             Token::Semicolon,
         ];
 
-        let statement = parse_statement(&mut tokens);
+        let statement = parse_statement(&mut tokens, &ParserContext::default());
 
         assert_eq!(
             statement.unwrap(),
-            AstStatement::Return(AstExpression::Var("main".to_string()))
+            AstStatement::Return(AstExpression::Var("main".to_string(), Position::default()), Position::default())
         );
         assert_eq!(
             tokens,
@@ -446,13 +886,14 @@ mod tests {
             Token::CloseBrace,
         ];
 
-        let statement = parse_function(&mut tokens);
+        let statement = parse_function(&mut tokens, &mut ParserContext::default());
 
         assert_eq!(
             statement,
             Ok(AstFunctionDefinition::Function(
                 "main".to_string(),
-                vec![AstBlockItem::Statement(AstStatement::Return(AstExpression::Constant(2)))]
+                vec![AstBlockItem::Statement(AstStatement::Return(AstExpression::Constant(2), Position::default()))],
+                Position::default()
             ))
         );
         assert_eq!(tokens, vec![]);
@@ -473,7 +914,7 @@ mod tests {
             Token::CloseBrace,
         ];
 
-        let statement = parse_function(&mut tokens);
+        let statement = parse_function(&mut tokens, &mut ParserContext::default());
 
         assert_eq!(
             statement,
@@ -517,9 +958,10 @@ mod tests {
 
         assert_eq!(
             statement,
-            Ok(AstProgram::Program(AstFunctionDefinition::Function(
+            Ok(AstProgram::Program(vec![], AstFunctionDefinition::Function(
                 "main".to_string(),
-                vec![AstBlockItem::Statement(AstStatement::Return(AstExpression::Constant(2)))]
+                vec![AstBlockItem::Statement(AstStatement::Return(AstExpression::Constant(2), Position::default()))],
+                Position::default()
             )))
         );
     }
@@ -544,4 +986,378 @@ mod tests {
 
         assert_eq!(statement, Err(ParserErr("Syntax error!".to_string())));
     }
+
+    #[test]
+    fn parse_block_item_typedef_name_starts_declaration() {
+        let mut tokens = vec![
+            Token::Typedef,
+            Token::Integer,
+            Token::Identifier("T".to_string()),
+            Token::Semicolon,
+            Token::Identifier("T".to_string()),
+            Token::Identifier("x".to_string()),
+            Token::Semicolon,
+        ];
+        let mut context = ParserContext::default();
+
+        assert_eq!(
+            parse_block_item(&mut tokens, &mut context),
+            Ok(AstBlockItem::Declaration(AstDeclaration::Typedef("T".to_string(), AstType::Int, Position::default())))
+        );
+        assert_eq!(
+            parse_block_item(&mut tokens, &mut context),
+            Ok(AstBlockItem::Declaration(AstDeclaration::Declaration(
+                "x".to_string(),
+                AstType::Typedef("T".to_string()),
+                None,
+                Position::default()
+            )))
+        );
+        assert_eq!(tokens.len(), 0);
+    }
+
+    #[test]
+    fn parse_block_item_typedef_name_is_not_an_expression() {
+        let mut tokens = vec![
+            Token::Identifier("x".to_string()),
+            Token::Equal,
+            Token::Identifier("T".to_string()),
+            Token::Semicolon,
+        ];
+        let mut context = ParserContext::default();
+        context.typedef_names.insert("T".to_string());
+
+        assert_eq!(
+            parse_block_item(&mut tokens, &mut context),
+            Err(ParserErr("expected expression, got type name T".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_block_item_enum_definition() {
+        let mut tokens = vec![
+            Token::Enum,
+            Token::Identifier("Color".to_string()),
+            Token::OpenBrace,
+            Token::Identifier("RED".to_string()),
+            Token::Comma,
+            Token::Identifier("GREEN".to_string()),
+            Token::Equal,
+            Token::Constant(5),
+            Token::Comma,
+            Token::Identifier("BLUE".to_string()),
+            Token::Comma,
+            Token::CloseBrace,
+            Token::Semicolon,
+            Token::Enum,
+            Token::Identifier("Color".to_string()),
+            Token::Identifier("c".to_string()),
+            Token::Equal,
+            Token::Identifier("BLUE".to_string()),
+            Token::Semicolon,
+        ];
+        let mut context = ParserContext::default();
+
+        assert_eq!(
+            parse_block_item(&mut tokens, &mut context),
+            Ok(AstBlockItem::Declaration(AstDeclaration::Enum(
+                Some("Color".to_string()),
+                vec![
+                    AstEnumerator::Enumerator("RED".to_string(), None, Position::default()),
+                    AstEnumerator::Enumerator("GREEN".to_string(), Some(AstExpression::Constant(5)), Position::default()),
+                    AstEnumerator::Enumerator("BLUE".to_string(), None, Position::default()),
+                ],
+                Position::default()
+            )))
+        );
+        assert_eq!(
+            parse_block_item(&mut tokens, &mut context),
+            Ok(AstBlockItem::Declaration(AstDeclaration::Declaration(
+                "c".to_string(),
+                AstType::Enum("Color".to_string()),
+                Some(AstExpression::Var("BLUE".to_string(), Position::default())),
+                Position::default()
+            )))
+        );
+        assert_eq!(tokens.len(), 0);
+    }
+
+    #[test]
+    fn parse_block_item_const_and_pointer_types() {
+        let mut tokens = vec![
+            Token::Integer,
+            Token::Const,
+            Token::Asterisk,
+            Token::Asterisk,
+            Token::Const,
+            Token::Identifier("p".to_string()),
+            Token::Semicolon,
+        ];
+
+        assert_eq!(
+            parse_block_item(&mut tokens, &mut ParserContext::default()),
+            Ok(AstBlockItem::Declaration(AstDeclaration::Declaration(
+                "p".to_string(),
+                AstType::Const(Box::new(AstType::Pointer(Box::new(AstType::Pointer(Box::new(
+                    AstType::Const(Box::new(AstType::Int))
+                )))))),
+                None,
+                Position::default()
+            )))
+        );
+    }
+
+    #[test]
+    fn parse_expression_increments_and_compound_assignment() {
+        // x -= -y++
+        let mut tokens = vec![
+            Token::Identifier("x".to_string()),
+            Token::HyphenEqual,
+            Token::Hyphen,
+            Token::Identifier("y".to_string()),
+            Token::DoublePlus,
+            Token::Semicolon,
+        ];
+        let positions = (1..=tokens.len()).map(|column| Position::new(1, column)).collect();
+
+        assert_eq!(
+            parse_expression(&mut tokens, 0, &ParserContext::new(positions, Standard::C17)),
+            Ok(AstExpression::CompoundAssignment(
+                AstBinaryOp::Subtract,
+                Box::new(AstExpression::Var("x".to_string(), Position::new(1, 1))),
+                Box::new(AstExpression::Unary(
+                    AstUnaryOp::Negate,
+                    Box::new(AstExpression::Increment(
+                        AstIncrementOp::PostIncrement,
+                        Box::new(AstExpression::Var("y".to_string(), Position::new(1, 4))),
+                        Position::new(1, 5)
+                    )),
+                    Position::new(1, 3)
+                )),
+                Position::new(1, 2)
+            ))
+        );
+        assert_eq!(tokens, vec![Token::Semicolon]);
+
+        // --(x)
+        let mut tokens = vec![
+            Token::DoubleHyphen,
+            Token::OpenParen,
+            Token::Identifier("x".to_string()),
+            Token::CloseParen,
+        ];
+
+        assert_eq!(
+            parse_expression(&mut tokens, 0, &ParserContext::default()),
+            Ok(AstExpression::Increment(
+                AstIncrementOp::PreDecrement,
+                Box::new(AstExpression::Parenthesized(Box::new(AstExpression::Var("x".to_string(), Position::default())))),
+                Position::default()
+            ))
+        );
+        assert_eq!(tokens.len(), 0);
+    }
+
+    #[test]
+    fn parse_function_c89_rejects_declarations_after_statements() {
+        let tokens = vec![
+            Token::Integer,
+            Token::Identifier("main".to_string()),
+            Token::OpenParen,
+            Token::Void,
+            Token::CloseParen,
+            Token::OpenBrace,
+            Token::Semicolon,
+            Token::Integer,
+            Token::Identifier("x".to_string()),
+            Token::Semicolon,
+            Token::CloseBrace,
+        ];
+
+        assert_eq!(
+            parse_function(&mut tokens.clone(), &mut ParserContext::new(vec![], Standard::C89)),
+            Err(ParserErr("declarations after statements are not allowed in C89".to_string()))
+        );
+        assert!(parse_function(&mut tokens.clone(), &mut ParserContext::new(vec![], Standard::C99)).is_ok());
+    }
+
+    #[test]
+    fn parse_block_item_c89_rejects_enum_trailing_comma() {
+        let tokens = vec![
+            Token::Enum,
+            Token::OpenBrace,
+            Token::Identifier("A".to_string()),
+            Token::Comma,
+            Token::CloseBrace,
+            Token::Semicolon,
+        ];
+
+        assert_eq!(
+            parse_block_item(&mut tokens.clone(), &mut ParserContext::new(vec![], Standard::C89)),
+            Err(ParserErr("trailing commas in enumerator lists are not allowed in C89".to_string()))
+        );
+        assert!(parse_block_item(&mut tokens.clone(), &mut ParserContext::new(vec![], Standard::C99)).is_ok());
+    }
+
+    #[test]
+    fn parse_program_static_assert_at_file_and_block_scope() {
+        let static_assert = vec![
+            Token::StaticAssert,
+            Token::OpenParen,
+            Token::Constant(1),
+            Token::Comma,
+            Token::StringLiteral("message".to_string()),
+            Token::CloseParen,
+            Token::Semicolon,
+        ];
+
+        let mut tokens = static_assert.clone();
+        tokens.append(&mut vec![
+            Token::Integer,
+            Token::Identifier("main".to_string()),
+            Token::OpenParen,
+            Token::Void,
+            Token::CloseParen,
+            Token::OpenBrace,
+        ]);
+        tokens.append(&mut static_assert.clone());
+        tokens.push(Token::CloseBrace);
+        tokens.append(&mut static_assert.clone());
+
+        let positions = (1..=tokens.len()).map(|column| Position::new(1, column)).collect();
+        let assertion = |column| AstDeclaration::StaticAssert(AstExpression::Constant(1), "message".to_string(), Position::new(1, column));
+
+        assert_eq!(
+            parse_program_with_context(&mut tokens, ParserContext::new(positions, Standard::C11)),
+            Ok(AstProgram::Program(
+                vec![assertion(1), assertion(22)],
+                AstFunctionDefinition::Function(
+                    "main".to_string(),
+                    vec![AstBlockItem::Declaration(assertion(14))],
+                    Position::new(1, 9)
+                )
+            ))
+        );
+
+        assert_eq!(
+            parse_block_item(&mut static_assert.clone(), &mut ParserContext::new(vec![], Standard::C99)),
+            Err(ParserErr("static assertions are not allowed in C99".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_statement_extended_inline_asm() {
+        let mut tokens = vec![
+            Token::Asm,
+            Token::Volatile,
+            Token::OpenParen,
+            Token::StringLiteral("movl %1, %0\n\t".to_string()),
+            Token::StringLiteral("addl %%eax, %0".to_string()),
+            Token::Colon,
+            Token::StringLiteral("=r".to_string()),
+            Token::OpenParen,
+            Token::Identifier("x".to_string()),
+            Token::CloseParen,
+            Token::Colon,
+            Token::StringLiteral("r".to_string()),
+            Token::OpenParen,
+            Token::Identifier("y".to_string()),
+            Token::Plus,
+            Token::Constant(1),
+            Token::CloseParen,
+            Token::Comma,
+            Token::StringLiteral("m".to_string()),
+            Token::OpenParen,
+            Token::Identifier("z".to_string()),
+            Token::CloseParen,
+            Token::Colon,
+            Token::StringLiteral("eax".to_string()),
+            Token::Comma,
+            Token::StringLiteral("cc".to_string()),
+            Token::CloseParen,
+            Token::Semicolon,
+        ];
+
+        assert_eq!(
+            parse_statement(&mut tokens, &ParserContext::default()),
+            Ok(AstStatement::InlineAsm(
+                "movl %1, %0\n\taddl %%eax, %0".to_string(),
+                vec![AstAsmOperand::Operand("=r".to_string(), AstExpression::Var("x".to_string(), Position::default()))],
+                vec![
+                    AstAsmOperand::Operand(
+                        "r".to_string(),
+                        AstExpression::Binary(
+                            AstBinaryOp::Add,
+                            Box::new(AstExpression::Var("y".to_string(), Position::default())),
+                            Box::new(AstExpression::Constant(1)),
+                            Position::default(),
+                        )
+                    ),
+                    AstAsmOperand::Operand("m".to_string(), AstExpression::Var("z".to_string(), Position::default())),
+                ],
+                vec!["eax".to_string(), "cc".to_string()],
+                Position::default(),
+            ))
+        );
+        assert_eq!(tokens, vec![]);
+    }
+
+    #[test]
+    fn parse_statement_inline_asm_with_empty_sections() {
+        let mut tokens = vec![
+            Token::Asm,
+            Token::OpenParen,
+            Token::StringLiteral("movl $1, %%eax".to_string()),
+            Token::Colon,
+            Token::Colon,
+            Token::Colon,
+            Token::StringLiteral("eax".to_string()),
+            Token::CloseParen,
+            Token::Semicolon,
+        ];
+
+        assert_eq!(
+            parse_statement(&mut tokens, &ParserContext::default()),
+            Ok(AstStatement::InlineAsm("movl $1, %%eax".to_string(), vec![], vec![], vec!["eax".to_string()], Position::default()))
+        );
+    }
+
+    #[test]
+    fn parse_statement_basic_inline_asm_escapes_percent() {
+        let mut tokens = vec![
+            Token::Asm,
+            Token::OpenParen,
+            Token::StringLiteral("xorl %eax, %eax".to_string()),
+            Token::CloseParen,
+            Token::Semicolon,
+        ];
+
+        assert_eq!(
+            parse_statement(&mut tokens, &ParserContext::default()),
+            Ok(AstStatement::InlineAsm("xorl %%eax, %%eax".to_string(), vec![], vec![], vec![], Position::default()))
+        );
+    }
+
+    #[test]
+    fn parse_block_item_records_identifier_positions() {
+        // int x = y;
+        let mut tokens = vec![
+            Token::Integer,
+            Token::Identifier("x".to_string()),
+            Token::Equal,
+            Token::Identifier("y".to_string()),
+            Token::Semicolon,
+        ];
+        let positions = vec![Position::new(2, 5), Position::new(2, 9), Position::new(2, 11), Position::new(2, 13), Position::new(2, 14)];
+
+        assert_eq!(
+            parse_block_item(&mut tokens, &mut ParserContext::new(positions, Standard::default())),
+            Ok(AstBlockItem::Declaration(AstDeclaration::Declaration(
+                "x".to_string(),
+                AstType::Int,
+                Some(AstExpression::Var("y".to_string(), Position::new(2, 13))),
+                Position::new(2, 9)
+            )))
+        );
+    }
 }
@@ -0,0 +1,179 @@
+//! Compile-time evaluation of integer constant expressions.
+//!
+//! Works on resolved expressions, where enumerators have already been replaced by their
+//! values, so any variable that is left makes the expression non-constant.
+
+use std::fmt;
+use crate::storage::ast::{AstBinaryOp, AstExpression, AstUnaryOp};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConstantError {
+    NotConstant,
+    DivisionByZero,
+    Overflow,
+}
+
+impl fmt::Display for ConstantError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConstantError::NotConstant => write!(f, "expression is not an integer constant expression"),
+            ConstantError::DivisionByZero => write!(f, "division by zero in constant expression"),
+            ConstantError::Overflow => write!(f, "integer overflow in constant expression"),
+        }
+    }
+}
+
+/// Evaluates `ast_expression` with C's `int` semantics.
+///
+/// Signed overflow is undefined behaviour at run time, so in a constant expression it is an
+/// error rather than wrapping around. `&&` and `||` short-circuit like they do at run time,
+/// so `0 && 1 / 0` is a valid constant expression.
+pub fn evaluate_constant_expression(ast_expression: &AstExpression) -> Result<i32, ConstantError> {
+    match ast_expression {
+        AstExpression::Constant(num) => Ok(*num),
+        AstExpression::Parenthesized(expr) => evaluate_constant_expression(expr),
+        AstExpression::Unary(un_op, expr, _) => {
+            let value = evaluate_constant_expression(expr)?;
+
+            match un_op {
+                AstUnaryOp::Negate => value.checked_neg().ok_or(ConstantError::Overflow),
+                AstUnaryOp::Complement => Ok(!value),
+                AstUnaryOp::Not => Ok((value == 0) as i32),
+            }
+        }
+        AstExpression::Binary(AstBinaryOp::And, left, right, _) => {
+            if evaluate_constant_expression(left)? == 0 {
+                Ok(0)
+            } else {
+                Ok((evaluate_constant_expression(right)? != 0) as i32)
+            }
+        }
+        AstExpression::Binary(AstBinaryOp::Or, left, right, _) => {
+            if evaluate_constant_expression(left)? != 0 {
+                Ok(1)
+            } else {
+                Ok((evaluate_constant_expression(right)? != 0) as i32)
+            }
+        }
+        AstExpression::Binary(bin_op, left, right, _) => {
+            let left = evaluate_constant_expression(left)?;
+            let right = evaluate_constant_expression(right)?;
+
+            evaluate_binary_op(bin_op, left, right)
+        }
+        AstExpression::Var(..)
+        | AstExpression::Assignment(..)
+        | AstExpression::CompoundAssignment(..)
+        | AstExpression::Increment(..) => Err(ConstantError::NotConstant),
+    }
+}
+
+fn evaluate_binary_op(bin_op: &AstBinaryOp, left: i32, right: i32) -> Result<i32, ConstantError> {
+    match bin_op {
+        AstBinaryOp::Add => left.checked_add(right).ok_or(ConstantError::Overflow),
+        AstBinaryOp::Subtract => left.checked_sub(right).ok_or(ConstantError::Overflow),
+        AstBinaryOp::Multiply => left.checked_mul(right).ok_or(ConstantError::Overflow),
+        AstBinaryOp::Divide | AstBinaryOp::Remainder if right == 0 => Err(ConstantError::DivisionByZero),
+        // INT_MIN / -1 doesn't fit, and C11 makes INT_MIN % -1 undefined as well
+        AstBinaryOp::Divide => left.checked_div(right).ok_or(ConstantError::Overflow),
+        AstBinaryOp::Remainder => left.checked_rem(right).ok_or(ConstantError::Overflow),
+        AstBinaryOp::Equal => Ok((left == right) as i32),
+        AstBinaryOp::NotEqual => Ok((left != right) as i32),
+        AstBinaryOp::LessThan => Ok((left < right) as i32),
+        AstBinaryOp::LessOrEqual => Ok((left <= right) as i32),
+        AstBinaryOp::GreaterThan => Ok((left > right) as i32),
+        AstBinaryOp::GreaterOrEqual => Ok((left >= right) as i32),
+        AstBinaryOp::And => Ok((left != 0 && right != 0) as i32),
+        AstBinaryOp::Or => Ok((left != 0 || right != 0) as i32),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::semantics::constant_evaluation::{evaluate_constant_expression, ConstantError};
+    use crate::storage::ast::{AstBinaryOp, AstExpression, AstUnaryOp};
+    use crate::storage::position::Position;
+
+    fn constant(num: i32) -> Box<AstExpression> {
+        Box::new(AstExpression::Constant(num))
+    }
+
+    fn binary(bin_op: AstBinaryOp, left: i32, right: i32) -> AstExpression {
+        AstExpression::Binary(bin_op, constant(left), constant(right), Position::default())
+    }
+
+    #[test]
+    fn evaluate_unary_operators() {
+        assert_eq!(evaluate_constant_expression(&AstExpression::Unary(AstUnaryOp::Negate, constant(5), Position::default())), Ok(-5));
+        assert_eq!(evaluate_constant_expression(&AstExpression::Unary(AstUnaryOp::Complement, constant(5), Position::default())), Ok(-6));
+        assert_eq!(evaluate_constant_expression(&AstExpression::Unary(AstUnaryOp::Not, constant(5), Position::default())), Ok(0));
+        assert_eq!(evaluate_constant_expression(&AstExpression::Unary(AstUnaryOp::Not, constant(0), Position::default())), Ok(1));
+        assert_eq!(
+            evaluate_constant_expression(&AstExpression::Unary(AstUnaryOp::Negate, constant(i32::MIN), Position::default())),
+            Err(ConstantError::Overflow)
+        );
+    }
+
+    #[test]
+    fn evaluate_arithmetic_operators() {
+        assert_eq!(evaluate_constant_expression(&binary(AstBinaryOp::Add, 2, 3)), Ok(5));
+        assert_eq!(evaluate_constant_expression(&binary(AstBinaryOp::Subtract, 2, 3)), Ok(-1));
+        assert_eq!(evaluate_constant_expression(&binary(AstBinaryOp::Multiply, -4, 3)), Ok(-12));
+        // division truncates towards zero
+        assert_eq!(evaluate_constant_expression(&binary(AstBinaryOp::Divide, -7, 2)), Ok(-3));
+        assert_eq!(evaluate_constant_expression(&binary(AstBinaryOp::Remainder, -7, 2)), Ok(-1));
+
+        assert_eq!(evaluate_constant_expression(&binary(AstBinaryOp::Add, i32::MAX, 1)), Err(ConstantError::Overflow));
+        assert_eq!(evaluate_constant_expression(&binary(AstBinaryOp::Subtract, i32::MIN, 1)), Err(ConstantError::Overflow));
+        assert_eq!(evaluate_constant_expression(&binary(AstBinaryOp::Multiply, 65536, 65536)), Err(ConstantError::Overflow));
+        assert_eq!(evaluate_constant_expression(&binary(AstBinaryOp::Divide, 1, 0)), Err(ConstantError::DivisionByZero));
+        assert_eq!(evaluate_constant_expression(&binary(AstBinaryOp::Remainder, 1, 0)), Err(ConstantError::DivisionByZero));
+        assert_eq!(evaluate_constant_expression(&binary(AstBinaryOp::Divide, i32::MIN, -1)), Err(ConstantError::Overflow));
+        assert_eq!(evaluate_constant_expression(&binary(AstBinaryOp::Remainder, i32::MIN, -1)), Err(ConstantError::Overflow));
+    }
+
+    #[test]
+    fn evaluate_relational_operators() {
+        assert_eq!(evaluate_constant_expression(&binary(AstBinaryOp::Equal, 2, 2)), Ok(1));
+        assert_eq!(evaluate_constant_expression(&binary(AstBinaryOp::NotEqual, 2, 2)), Ok(0));
+        assert_eq!(evaluate_constant_expression(&binary(AstBinaryOp::LessThan, -1, 0)), Ok(1));
+        assert_eq!(evaluate_constant_expression(&binary(AstBinaryOp::LessOrEqual, 1, 0)), Ok(0));
+        assert_eq!(evaluate_constant_expression(&binary(AstBinaryOp::GreaterThan, 1, 0)), Ok(1));
+        assert_eq!(evaluate_constant_expression(&binary(AstBinaryOp::GreaterOrEqual, 0, 0)), Ok(1));
+    }
+
+    #[test]
+    fn evaluate_logical_operators_short_circuit() {
+        let division_by_zero = Box::new(binary(AstBinaryOp::Divide, 1, 0));
+
+        assert_eq!(evaluate_constant_expression(&binary(AstBinaryOp::And, 2, 3)), Ok(1));
+        assert_eq!(evaluate_constant_expression(&binary(AstBinaryOp::Or, 0, 0)), Ok(0));
+        assert_eq!(
+            evaluate_constant_expression(&AstExpression::Binary(AstBinaryOp::And, constant(0), division_by_zero.clone(), Position::default())),
+            Ok(0)
+        );
+        assert_eq!(
+            evaluate_constant_expression(&AstExpression::Binary(AstBinaryOp::Or, constant(7), division_by_zero.clone(), Position::default())),
+            Ok(1)
+        );
+        assert_eq!(
+            evaluate_constant_expression(&AstExpression::Binary(AstBinaryOp::And, constant(1), division_by_zero, Position::default())),
+            Err(ConstantError::DivisionByZero)
+        );
+    }
+
+    #[test]
+    fn evaluate_non_constant_expressions() {
+        let var = Box::new(AstExpression::Var("x.0".to_string(), Position::default()));
+
+        assert_eq!(evaluate_constant_expression(&var), Err(ConstantError::NotConstant));
+        assert_eq!(
+            evaluate_constant_expression(&AstExpression::Assignment(var.clone(), constant(1), Position::default())),
+            Err(ConstantError::NotConstant)
+        );
+        assert_eq!(
+            evaluate_constant_expression(&AstExpression::Binary(AstBinaryOp::Multiply, constant(0), var, Position::default())),
+            Err(ConstantError::NotConstant)
+        );
+    }
+}
@@ -0,0 +1,126 @@
+//! Semantic errors and the collector that gathers them across a whole program.
+
+use std::fmt;
+use crate::compiler::semantics::constant_evaluation::ConstantError;
+use crate::storage::position::Position;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SemanticError {
+    UndeclaredVariable(String, Position), // UndeclaredVariable(identifier, use)
+    DuplicateVariable(String, Position, Position), // DuplicateVariable(identifier, redeclaration, previous declaration)
+    DuplicateEnumTag(String, Position, Position), // DuplicateEnumTag(tag, redefinition, previous definition)
+    InvalidLValue(Position),
+    UndeclaredType(String, Position), // UndeclaredType(type name, use)
+    InvalidConstantExpression(ConstantError, Position),
+    AssignmentToConst(String, Position), // AssignmentToConst(identifier, position)
+    StaticAssertionFailed(String, Position), // StaticAssertionFailed(message, position)
+    InvalidInlineAsm(String, Position), // InvalidInlineAsm(reason, position)
+}
+
+impl SemanticError {
+    /// Where the error was found.
+    pub fn position(&self) -> Position {
+        match self {
+            SemanticError::UndeclaredVariable(_, position)
+            | SemanticError::DuplicateVariable(_, position, _)
+            | SemanticError::DuplicateEnumTag(_, position, _)
+            | SemanticError::InvalidLValue(position)
+            | SemanticError::UndeclaredType(_, position)
+            | SemanticError::InvalidConstantExpression(_, position)
+            | SemanticError::AssignmentToConst(_, position)
+            | SemanticError::StaticAssertionFailed(_, position)
+            | SemanticError::InvalidInlineAsm(_, position) => *position,
+        }
+    }
+
+    /// A second location that explains the error, like the declaration a duplicate clashes with.
+    pub fn note(&self) -> Option<(String, Position)> {
+        match self {
+            SemanticError::DuplicateVariable(identifier, _, previous) => {
+                Some((format!("previous declaration of '{}' was here", identifier), *previous))
+            }
+            SemanticError::DuplicateEnumTag(tag, _, previous) => {
+                Some((format!("'enum {}' was first defined here", tag), *previous))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SemanticError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SemanticError::UndeclaredVariable(identifier, _) => write!(f, "'{}' undeclared", identifier),
+            SemanticError::DuplicateVariable(identifier, _, _) => write!(f, "redeclaration of '{}'", identifier),
+            SemanticError::DuplicateEnumTag(tag, _, _) => write!(f, "redefinition of 'enum {}'", tag),
+            SemanticError::InvalidLValue(_) => write!(f, "expression is not assignable"),
+            SemanticError::UndeclaredType(type_name, _) => write!(f, "unknown type '{}'", type_name),
+            SemanticError::InvalidConstantExpression(err, _) => write!(f, "{}", err),
+            SemanticError::AssignmentToConst(identifier, _) => write!(f, "assignment to read-only variable '{}'", identifier),
+            SemanticError::StaticAssertionFailed(message, _) => write!(f, "static assertion failed: {:?}", message),
+            SemanticError::InvalidInlineAsm(reason, _) => write!(f, "invalid inline asm: {}", reason),
+        }
+    }
+}
+
+/// Formats `error` the way gcc does, `file:line:column: error: message`, followed by a line
+/// for its note if it has one.
+pub fn format_error(file: &str, error: &SemanticError) -> String {
+    let mut result = format!("{}:{}: error: {}", file, error.position(), error);
+
+    if let Some((note, position)) = error.note() {
+        result.push_str(&format!("\n{}:{}: note: {}", file, position, note));
+    }
+
+    result
+}
+
+/// Collects semantic errors instead of stopping at the first one, so a single run reports
+/// everything that is wrong with a program.
+#[derive(Debug, Default)]
+pub struct Diagnostics {
+    errors: Vec<SemanticError>,
+}
+
+impl Diagnostics {
+    pub fn new() -> Self {
+        Diagnostics { errors: vec![] }
+    }
+
+    pub fn report(&mut self, error: SemanticError) {
+        self.errors.push(error);
+    }
+
+    pub fn errors(&self) -> &Vec<SemanticError> {
+        &self.errors
+    }
+
+    pub fn into_errors(self) -> Vec<SemanticError> {
+        self.errors
+    }
+
+    /// Whether `identifier` was already reported as undeclared, each one is only reported for
+    /// its first use.
+    pub fn reported_undeclared(&self, identifier: &str) -> bool {
+        self.errors.iter().any(|err| matches!(err, SemanticError::UndeclaredVariable(reported, _) if reported == identifier))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::semantics::diagnostics::{format_error, SemanticError};
+    use crate::storage::position::Position;
+
+    #[test]
+    fn duplicate_variable_points_to_previous_declaration() {
+        let error = SemanticError::DuplicateVariable("x".to_string(), Position::new(3, 9), Position::new(2, 9));
+
+        assert_eq!(error.to_string(), "redeclaration of 'x'");
+        assert_eq!(error.position(), Position::new(3, 9));
+        assert_eq!(error.note(), Some(("previous declaration of 'x' was here".to_string(), Position::new(2, 9))));
+        assert_eq!(
+            format_error("main.c", &error),
+            "main.c:3:9: error: redeclaration of 'x'\nmain.c:2:9: note: previous declaration of 'x' was here"
+        );
+    }
+}
@@ -0,0 +1,361 @@
+//! Lint checks for suspicious but legal C, run by `mdc lint`.
+//!
+//! Unlike the warnings pass these look at how an expression is written rather than at how
+//! its variables are used, which is why the parser keeps parentheses in the AST.
+
+use std::fmt;
+use crate::compiler::optimization::range_analysis::{Interval, Ranges};
+use crate::compiler::semantics::constant_evaluation::evaluate_constant_expression;
+use crate::storage::ast::{AstAsmOperand, AstBinaryOp, AstBlockItem, AstDeclaration, AstExpression, AstFunctionDefinition, AstProgram, AstStatement, AstUnaryOp};
+use crate::storage::position::Position;
+use crate::storage::symbol_table::SymbolTable;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Lint {
+    AssignmentAsCondition(Position), // AssignmentAsCondition(assignment)
+    SelfAssignment(String, Position), // SelfAssignment(identifier, assignment)
+    ConstantComparison(bool, Position), // ConstantComparison(result, comparison)
+    LogicalNotParentheses(Position), // LogicalNotParentheses(comparison)
+    RedundantParentheses(Position), // RedundantParentheses(assignment)
+}
+
+impl Lint {
+    /// A stable name for the check, printed after the message.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Lint::AssignmentAsCondition(_) => "assignment-as-condition",
+            Lint::SelfAssignment(..) => "self-assignment",
+            Lint::ConstantComparison(..) => "constant-comparison",
+            Lint::LogicalNotParentheses(_) => "logical-not-parentheses",
+            Lint::RedundantParentheses(_) => "redundant-parentheses",
+        }
+    }
+
+    pub fn position(&self) -> Position {
+        match self {
+            Lint::AssignmentAsCondition(position)
+            | Lint::SelfAssignment(_, position)
+            | Lint::ConstantComparison(_, position)
+            | Lint::LogicalNotParentheses(position)
+            | Lint::RedundantParentheses(position) => *position,
+        }
+    }
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Lint::AssignmentAsCondition(_) => write!(f, "assignment used as a condition, wrap it in a second pair of parentheses if this is intended"),
+            Lint::SelfAssignment(identifier, _) => write!(f, "explicitly assigning variable '{}' to itself", identifier),
+            Lint::ConstantComparison(result, _) => write!(f, "comparison is always {} due to limited range of operands", result),
+            Lint::LogicalNotParentheses(_) => write!(f, "logical not is only applied to the left hand side of the comparison"),
+            Lint::RedundantParentheses(_) => write!(f, "redundant parentheses around assignment"),
+        }
+    }
+}
+
+/// Formats `lint` like a gcc warning, `file:line:column: warning: message [name]`.
+pub fn format_lint(file: &str, lint: &Lint) -> String {
+    format!("{}:{}: warning: {} [{}]", file, lint.position(), lint, lint.name())
+}
+
+/// Runs every lint check over `ast_program`, which has to be resolved already with `symbols`
+/// as its symbol table, and returns the findings ordered by position.
+pub fn lint_program(ast_program: &AstProgram, symbols: &SymbolTable) -> Vec<Lint> {
+    lint_program_with_ranges(ast_program, symbols, &Ranges::new())
+}
+
+/// Like `lint_program`, but a variable can only have the values in `ranges`, what
+/// `variable_ranges` found for the TACKY of the same program.
+pub fn lint_program_with_ranges(ast_program: &AstProgram, symbols: &SymbolTable, ranges: &Ranges) -> Vec<Lint> {
+    let mut lints = vec![];
+
+    match ast_program {
+        AstProgram::Program(_, AstFunctionDefinition::Function(_, body, _)) => {
+            for block_item in body {
+                lint_block_item(block_item, symbols, ranges, &mut lints);
+            }
+        }
+    }
+
+    lints.sort_by_key(|lint| (lint.position().line, lint.position().column));
+
+    lints
+}
+
+fn lint_block_item(ast_block_item: &AstBlockItem, symbols: &SymbolTable, ranges: &Ranges, lints: &mut Vec<Lint>) {
+    match ast_block_item {
+        AstBlockItem::Declaration(AstDeclaration::Declaration(_, _, Some(init), position)) => lint_full_expression(init, *position, symbols, ranges, lints),
+        AstBlockItem::Declaration(_) => (),
+        AstBlockItem::Statement(AstStatement::Return(expr, position))
+        | AstBlockItem::Statement(AstStatement::Expression(expr, position)) => lint_full_expression(expr, *position, symbols, ranges, lints),
+        AstBlockItem::Statement(AstStatement::InlineAsm(_, _, inputs, _, position)) => {
+            for AstAsmOperand::Operand(_, expr) in inputs {
+                lint_expression(expr, *position, symbols, ranges, lints);
+            }
+        }
+        AstBlockItem::Statement(AstStatement::Null) => (),
+    }
+}
+
+/// Lints an expression that isn't part of a bigger one. `position` is where its statement
+/// starts, used for findings in expressions without a position of their own, like `1 < 2`.
+fn lint_full_expression(ast_expression: &AstExpression, position: Position, symbols: &SymbolTable, ranges: &Ranges, lints: &mut Vec<Lint>) {
+    check_redundant_parentheses(ast_expression, lints);
+    lint_expression(ast_expression, position, symbols, ranges, lints);
+}
+
+fn lint_expression(ast_expression: &AstExpression, position: Position, symbols: &SymbolTable, ranges: &Ranges, lints: &mut Vec<Lint>) {
+    match ast_expression {
+        AstExpression::Constant(_) | AstExpression::Var(..) | AstExpression::Increment(..) => (),
+        AstExpression::Parenthesized(expr) => lint_expression(expr, position, symbols, ranges, lints),
+        AstExpression::Unary(AstUnaryOp::Not, expr, _) => {
+            check_condition(expr, lints);
+            lint_expression(expr, position, symbols, ranges, lints);
+        }
+        AstExpression::Unary(_, expr, _) => lint_expression(expr, position, symbols, ranges, lints),
+        AstExpression::Binary(bin_op, left, right, _) => {
+            if matches!(bin_op, AstBinaryOp::And | AstBinaryOp::Or) {
+                check_condition(left, lints);
+                check_condition(right, lints);
+            }
+
+            if is_comparison(bin_op) {
+                let comparison_position = expression_position(ast_expression).unwrap_or(position);
+
+                if let AstExpression::Unary(AstUnaryOp::Not, ..) = &**left {
+                    lints.push(Lint::LogicalNotParentheses(comparison_position));
+                }
+
+                if let Some(result) = constant_comparison(bin_op, left, right, ranges) {
+                    lints.push(Lint::ConstantComparison(result, comparison_position));
+                }
+            }
+
+            lint_expression(left, position, symbols, ranges, lints);
+            lint_expression(right, position, symbols, ranges, lints);
+        }
+        AstExpression::Assignment(left, right, assignment_position) => {
+            if let (AstExpression::Var(target, _), AstExpression::Var(source, _)) = (&**left, strip_parentheses(right)) {
+                if target == source {
+                    let name = symbols.get(target).map_or(target.as_str(), |symbol| symbol.name.as_str());
+                    lints.push(Lint::SelfAssignment(name.to_string(), *assignment_position));
+                }
+            }
+
+            check_redundant_parentheses(right, lints);
+            lint_expression(right, position, symbols, ranges, lints);
+        }
+        AstExpression::CompoundAssignment(_, _, right, _) => {
+            check_redundant_parentheses(right, lints);
+            lint_expression(right, position, symbols, ranges, lints);
+        }
+    }
+}
+
+/// Flags an assignment used as the operand of `!`, `&&` or `||`.
+///
+/// The operand of `&&` and `||` needs one pair of parentheses around an assignment anyway, so
+/// like gcc's `if ((a = b))` it takes a second pair to show the assignment is intended.
+fn check_condition(ast_expression: &AstExpression, lints: &mut Vec<Lint>) {
+    let operand = match ast_expression {
+        AstExpression::Parenthesized(inner) => &**inner,
+        other => other,
+    };
+
+    if let AstExpression::Assignment(_, _, position) = operand {
+        lints.push(Lint::AssignmentAsCondition(*position));
+    }
+}
+
+/// Flags parentheses around an assignment whose value is used where no parentheses are needed.
+fn check_redundant_parentheses(ast_expression: &AstExpression, lints: &mut Vec<Lint>) {
+    if let AstExpression::Parenthesized(inner) = ast_expression {
+        match strip_parentheses(inner) {
+            AstExpression::Assignment(_, _, position) | AstExpression::CompoundAssignment(_, _, _, position) => {
+                lints.push(Lint::RedundantParentheses(*position))
+            }
+            _ => (),
+        }
+    }
+}
+
+fn strip_parentheses(ast_expression: &AstExpression) -> &AstExpression {
+    match ast_expression {
+        AstExpression::Parenthesized(inner) => strip_parentheses(inner),
+        other => other,
+    }
+}
+
+fn is_comparison(bin_op: &AstBinaryOp) -> bool {
+    matches!(
+        bin_op,
+        AstBinaryOp::Equal
+            | AstBinaryOp::NotEqual
+            | AstBinaryOp::LessThan
+            | AstBinaryOp::LessOrEqual
+            | AstBinaryOp::GreaterThan
+            | AstBinaryOp::GreaterOrEqual
+    )
+}
+
+/// The position of the leftmost part of `ast_expression` that has one.
+fn expression_position(ast_expression: &AstExpression) -> Option<Position> {
+    match ast_expression {
+        AstExpression::Constant(_) => None,
+        AstExpression::Var(_, position) => Some(*position),
+        AstExpression::Unary(_, expr, _) | AstExpression::Parenthesized(expr) => expression_position(expr),
+        AstExpression::Binary(_, left, right, _) => expression_position(left).or_else(|| expression_position(right)),
+        AstExpression::Assignment(left, _, position)
+        | AstExpression::CompoundAssignment(_, left, _, position)
+        | AstExpression::Increment(_, left, position) => expression_position(left).or(Some(*position)),
+    }
+}
+
+/// The smallest and largest value `ast_expression` can have.
+fn value_range(ast_expression: &AstExpression, ranges: &Ranges) -> (i64, i64) {
+    if let Ok(value) = evaluate_constant_expression(ast_expression) {
+        return (value as i64, value as i64);
+    }
+
+    match strip_parentheses(ast_expression) {
+        AstExpression::Var(name, _) => {
+            let interval = ranges.get(name).copied().unwrap_or(Interval::FULL);
+            (interval.low as i64, interval.high as i64)
+        }
+        AstExpression::Unary(AstUnaryOp::Not, ..) => (0, 1),
+        AstExpression::Binary(bin_op, ..) if is_comparison(bin_op) || matches!(bin_op, AstBinaryOp::And | AstBinaryOp::Or) => (0, 1),
+        AstExpression::Binary(AstBinaryOp::Remainder, left, right, _) => match evaluate_constant_expression(right) {
+            Ok(divisor) if divisor != 0 => {
+                // the remainder is smaller than the divisor and takes the sign of the dividend
+                let bound = (divisor as i64).abs() - 1;
+
+                if value_range(left, ranges).0 >= 0 {
+                    (0, bound)
+                } else {
+                    (-bound, bound)
+                }
+            }
+            _ => (i32::MIN as i64, i32::MAX as i64),
+        },
+        _ => (i32::MIN as i64, i32::MAX as i64),
+    }
+}
+
+/// The result of a comparison that is decided by the ranges of its operands alone, `None` if
+/// it depends on their values. Comparisons of two constants are left alone, they are written
+/// that way on purpose.
+fn constant_comparison(bin_op: &AstBinaryOp, left: &AstExpression, right: &AstExpression, ranges: &Ranges) -> Option<bool> {
+    if evaluate_constant_expression(left).is_ok() && evaluate_constant_expression(right).is_ok() {
+        return None;
+    }
+
+    let (left_min, left_max) = value_range(left, ranges);
+    let (right_min, right_max) = value_range(right, ranges);
+    let disjoint = left_max < right_min || right_max < left_min;
+
+    match bin_op {
+        AstBinaryOp::LessThan if left_max < right_min => Some(true),
+        AstBinaryOp::LessThan if left_min >= right_max => Some(false),
+        AstBinaryOp::LessOrEqual if left_max <= right_min => Some(true),
+        AstBinaryOp::LessOrEqual if left_min > right_max => Some(false),
+        AstBinaryOp::GreaterThan if left_min > right_max => Some(true),
+        AstBinaryOp::GreaterThan if left_max <= right_min => Some(false),
+        AstBinaryOp::GreaterOrEqual if left_min >= right_max => Some(true),
+        AstBinaryOp::GreaterOrEqual if left_max < right_min => Some(false),
+        AstBinaryOp::Equal if disjoint => Some(false),
+        AstBinaryOp::NotEqual if disjoint => Some(true),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::parser::{parse_program_with_context, ParserContext};
+    use crate::compiler::optimization::range_analysis::variable_ranges;
+    use crate::compiler::semantics::lint::{format_lint, lint_program, lint_program_with_ranges, Lint};
+    use crate::compiler::semantics::variable_resolution::resolve_program;
+    use crate::compiler::standard::Standard;
+    use crate::compiler::tackygen::emit_tacky;
+    use crate::compiler::tokenizer::tokenize_with_positions;
+    use crate::storage::position::Position;
+    use crate::storage::tacky::Program;
+
+    fn lints_for(source: &str) -> Vec<Lint> {
+        let (mut tokens, positions) = tokenize_with_positions(source, Standard::C17).unwrap();
+        let ast = parse_program_with_context(&mut tokens, ParserContext::new(positions, Standard::C17)).unwrap();
+
+        let (resolved_ast, symbols) = resolve_program(ast, Standard::C17).unwrap();
+
+        lint_program(&resolved_ast, &symbols)
+    }
+
+    #[test]
+    fn lint_assignments_in_conditions_and_to_themselves() {
+        let source = "int main(void) {\n    int a = 1;\n    int b = (a = 2) && !(b = 3);\n    int c = ((a = 4)) || b;\n    a = (a);\n    return a + c;\n}";
+
+        assert_eq!(
+            lints_for(source),
+            vec![
+                Lint::AssignmentAsCondition(Position::new(3, 16)),
+                Lint::AssignmentAsCondition(Position::new(3, 28)),
+                Lint::SelfAssignment("a".to_string(), Position::new(5, 7)),
+            ]
+        );
+    }
+
+    #[test]
+    fn lint_comparisons_decided_by_operand_ranges() {
+        let source = "int main(void) {\n    int a = 5;\n    int b = !a == 2;\n    int c = (a % 3) < 3;\n    int d = a <= 2147483647;\n    int e = 1 < 2;\n    return (!a) == b && a != 2 && c + d + e;\n}";
+
+        assert_eq!(
+            lints_for(source),
+            vec![
+                Lint::LogicalNotParentheses(Position::new(3, 14)),
+                Lint::ConstantComparison(false, Position::new(3, 14)),
+                Lint::ConstantComparison(true, Position::new(4, 14)),
+                Lint::ConstantComparison(true, Position::new(5, 13)),
+            ]
+        );
+    }
+
+    #[test]
+    fn lint_comparisons_decided_by_variable_ranges() {
+        let source = "int main(void) {\n    int a = 5;\n    int b = a % 4 + 10;\n    int c = b > 9;\n    return a != 2 && c < 2;\n}";
+        let (mut tokens, positions) = tokenize_with_positions(source, Standard::C17).unwrap();
+        let ast = parse_program_with_context(&mut tokens, ParserContext::new(positions, Standard::C17)).unwrap();
+        let (resolved_ast, symbols) = resolve_program(ast, Standard::C17).unwrap();
+
+        let ranges = match emit_tacky(resolved_ast.clone()) {
+            Program::Program(function) => variable_ranges(&function),
+        };
+
+        // b is between 10 and 13, and a is always 5
+        assert_eq!(
+            lint_program_with_ranges(&resolved_ast, &symbols, &ranges),
+            vec![
+                Lint::ConstantComparison(true, Position::new(4, 13)),
+                Lint::ConstantComparison(true, Position::new(5, 12)),
+                Lint::ConstantComparison(true, Position::new(5, 22)),
+            ]
+        );
+        assert!(lint_program(&resolved_ast, &symbols).is_empty());
+    }
+
+    #[test]
+    fn lint_redundant_parentheses_around_assignments() {
+        let source = "int main(void) {\n    int a;\n    int b = (a = 1);\n    (a += 2);\n    b = ((a = 3));\n    return (b = a);\n}";
+        let lints = lints_for(source);
+
+        assert_eq!(
+            lints,
+            vec![
+                Lint::RedundantParentheses(Position::new(3, 16)),
+                Lint::RedundantParentheses(Position::new(4, 8)),
+                Lint::RedundantParentheses(Position::new(5, 13)),
+                Lint::RedundantParentheses(Position::new(6, 15)),
+            ]
+        );
+        assert_eq!(format_lint("main.c", &lints[0]), "main.c:3:16: warning: redundant parentheses around assignment [redundant-parentheses]");
+    }
+}
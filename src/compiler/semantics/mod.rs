@@ -1 +1,5 @@
-pub mod variable_resolution;
\ No newline at end of file
+pub mod constant_evaluation;
+pub mod diagnostics;
+pub mod lint;
+pub mod variable_resolution;
+pub mod warnings;
@@ -1,96 +1,775 @@
 use std::collections::HashMap;
-use crate::storage::ast::{AstBlockItem, AstDeclaration, AstExpression, AstFunctionDefinition, AstProgram, AstStatement};
+use crate::compiler::assembly::instruction_fixup::ASM_OPERAND_REGISTERS;
+use crate::storage::assembly::AssemblyRegister;
+use crate::storage::ast::{AstAsmOperand, AstBlockItem, AstDeclaration, AstEnumerator, AstExpression, AstFunctionDefinition, AstProgram, AstStatement, AstType};
+use crate::compiler::semantics::constant_evaluation::{evaluate_constant_expression, ConstantError};
+use crate::compiler::semantics::diagnostics::{Diagnostics, SemanticError};
+use crate::compiler::standard::Standard;
+use crate::storage::position::Position;
+use crate::storage::symbol_table::{StorageClass, Symbol, SymbolTable};
 
+/// What an ordinary identifier refers to in the current scope.
+///
+/// Variables, typedef names and enumerators share one namespace in C, so they
+/// live in the same map and a redeclaration of any of them is a duplicate.
 #[derive(Debug, Clone, PartialEq)]
-pub enum SemanticError {
-    UndeclaredVariable,
-    DuplicateVariable,
-    InvalidLValue,
+enum IdentifierEntry {
+    Variable(String, AstType), // Variable(unique_name, type)
+    Typedef(AstType), // Typedef(aliased type)
+    EnumConstant(i32),
 }
 
-pub fn resolve_program(ast_program: AstProgram) -> Result<AstProgram, SemanticError> {
-    match ast_program {
-        AstProgram::Program(function_definitions) => {
-            Ok(AstProgram::Program(resolve_function(function_definitions)?))
+/// Identifiers and enum tags visible while resolving a function body.
+///
+/// Both remember where they were declared, so duplicates can point back to the original.
+/// Every successful declaration also ends up in `symbols`.
+struct Scope {
+    identifiers: HashMap<String, (IdentifierEntry, Position)>,
+    enum_tags: HashMap<String, Position>,
+    standard: Standard,
+    depth: usize,
+    symbols: SymbolTable,
+}
+
+impl Scope {
+    /// A file scope.
+    fn new(standard: Standard) -> Self {
+        Scope { identifiers: HashMap::new(), enum_tags: HashMap::new(), standard, depth: 0, symbols: SymbolTable::new() }
+    }
+
+    /// The scope of a function body.
+    fn function(standard: Standard) -> Self {
+        Scope { depth: 1, ..Scope::new(standard) }
+    }
+
+    fn lookup(&self, identifier: &str) -> Option<&IdentifierEntry> {
+        self.identifiers.get(identifier).map(|(entry, _)| entry)
+    }
+
+    /// Declares `identifier`, unless it already is, in which case the duplicate is reported
+    /// and the original declaration stays in place.
+    fn declare(&mut self, identifier: String, entry: IdentifierEntry, position: Position, diagnostics: &mut Diagnostics) {
+        match self.identifiers.get(&identifier) {
+            Some((_, previous)) => diagnostics.report(SemanticError::DuplicateVariable(identifier, position, *previous)),
+            None => {
+                let (unique_name, symbol_type, storage_class) = match &entry {
+                    IdentifierEntry::Variable(unique_name, variable_type) => (unique_name.clone(), variable_type.clone(), StorageClass::Automatic),
+                    IdentifierEntry::Typedef(aliased_type) => (identifier.clone(), aliased_type.clone(), StorageClass::Typedef),
+                    IdentifierEntry::EnumConstant(value) => (identifier.clone(), AstType::Int, StorageClass::Constant(*value)),
+                };
+
+                self.symbols.insert(Symbol {
+                    name: identifier.clone(),
+                    unique_name,
+                    symbol_type,
+                    storage_class,
+                    scope_depth: self.depth,
+                    position,
+                });
+                self.identifiers.insert(identifier, (entry, position));
+            }
         }
     }
 }
 
-fn resolve_function(ast_function_def: AstFunctionDefinition) -> Result<AstFunctionDefinition, SemanticError> {
+/// Resolves every identifier in the program and collects them into a symbol table.
+///
+/// Resolution carries on past errors, so the result holds every error in the program rather
+/// than just the first one.
+pub fn resolve_program(ast_program: AstProgram, standard: Standard) -> Result<(AstProgram, SymbolTable), Vec<SemanticError>> {
+    let mut diagnostics = Diagnostics::new();
+    let mut symbols = SymbolTable::new();
+
+    let resolved_program = match ast_program {
+        AstProgram::Program(declarations, function_definitions) => {
+            let mut file_scope = Scope::new(standard);
+
+            let declarations = declarations
+                .into_iter()
+                .map(|declaration| resolve_declaration(declaration, &mut file_scope, &mut diagnostics))
+                .collect();
+            symbols.append(file_scope.symbols);
+
+            AstProgram::Program(declarations, resolve_function(function_definitions, standard, &mut symbols, &mut diagnostics))
+        }
+    };
+
+    if diagnostics.errors().is_empty() {
+        Ok((resolved_program, symbols))
+    } else {
+        Err(diagnostics.into_errors())
+    }
+}
+
+fn resolve_function(ast_function_def: AstFunctionDefinition, standard: Standard, symbols: &mut SymbolTable, diagnostics: &mut Diagnostics) -> AstFunctionDefinition {
     match ast_function_def {
-        AstFunctionDefinition::Function(identifier, body) => {
-            let mut variable_map: HashMap<String, String> = HashMap::new();
+        AstFunctionDefinition::Function(identifier, body, position) => {
+            let mut scope = Scope::function(standard);
 
             let mut new_body = vec![];
 
             for block_item in body {
                 match block_item {
                     AstBlockItem::Declaration(declaration) => {
-                        new_body.push(AstBlockItem::Declaration(resolve_declaration(declaration, &mut variable_map)?))
+                        new_body.push(AstBlockItem::Declaration(resolve_declaration(declaration, &mut scope, diagnostics)))
                     },
                     AstBlockItem::Statement(statement) => {
-                        new_body.push(AstBlockItem::Statement(resolve_statement(statement, &variable_map)?))
+                        new_body.push(AstBlockItem::Statement(resolve_statement(statement, &scope, diagnostics)))
                     }
                 }
             };
 
-            Ok(AstFunctionDefinition::Function(identifier, new_body))
+            symbols.append(scope.symbols);
+
+            AstFunctionDefinition::Function(identifier, new_body, position)
         }
     }
 }
 
-fn resolve_statement(ast_statement: AstStatement, variable_map: &HashMap<String, String>) -> Result<AstStatement, SemanticError> {
+fn resolve_statement(ast_statement: AstStatement, scope: &Scope, diagnostics: &mut Diagnostics) -> AstStatement {
     match ast_statement {
-        AstStatement::Return(expr) => Ok(AstStatement::Return(resolve_expression(expr, &variable_map)?)),
-        AstStatement::Expression(expr) => Ok(AstStatement::Expression(resolve_expression(expr, &variable_map)?)),
-        AstStatement::Null => Ok(AstStatement::Null),
+        AstStatement::Return(expr, position) => AstStatement::Return(resolve_expression(expr, scope, diagnostics), position),
+        AstStatement::Expression(expr, position) => AstStatement::Expression(resolve_expression(expr, scope, diagnostics), position),
+        AstStatement::InlineAsm(template, outputs, inputs, clobbers, position) => {
+            let outputs = outputs
+                .into_iter()
+                .map(|AstAsmOperand::Operand(constraint, expr)| {
+                    validate_asm_constraint(&constraint, true, position, diagnostics);
+                    let expr = resolve_modifiable_lvalue(expr, scope, position, diagnostics);
+                    AstAsmOperand::Operand(constraint, expr)
+                })
+                .collect::<Vec<_>>();
+
+            let inputs = inputs
+                .into_iter()
+                .map(|AstAsmOperand::Operand(constraint, expr)| {
+                    validate_asm_constraint(&constraint, false, position, diagnostics);
+                    let expr = resolve_expression(expr, scope, diagnostics);
+
+                    // an immediate has to be known when the assembly is emitted
+                    if constraint == "i" {
+                        let value = evaluate_constant(&expr, position, diagnostics);
+                        AstAsmOperand::Operand(constraint, AstExpression::Constant(value))
+                    } else {
+                        AstAsmOperand::Operand(constraint, expr)
+                    }
+                })
+                .collect::<Vec<_>>();
+
+            validate_asm_template(&template, outputs.len() + inputs.len(), position, diagnostics);
+
+            for clobber in &clobbers {
+                if clobber != "memory" && clobber != "cc" && AssemblyRegister::from_name(clobber).is_none() {
+                    diagnostics.report(SemanticError::InvalidInlineAsm(format!("unknown or unsupported clobber '{}'", clobber), position))
+                }
+            }
+
+            validate_asm_registers(outputs.iter().chain(&inputs), &clobbers, position, diagnostics);
+
+            AstStatement::InlineAsm(template, outputs, inputs, clobbers, position)
+        }
+        AstStatement::Null => AstStatement::Null,
     }
 }
 
-fn resolve_declaration(ast_declaration: AstDeclaration, variable_map: &mut HashMap<String, String>) -> Result<AstDeclaration, SemanticError> {
-    match ast_declaration {
-        AstDeclaration::Declaration(name, init) => {
-            if variable_map.contains_key(&name) {
-                return Err(SemanticError::DuplicateVariable)
+/// Accepts the constraints the back end knows how to satisfy: `r` (register), `m` (memory),
+/// `i` (immediate, inputs only) and `g` (any of them).
+///
+/// Outputs start with `=` (write only) or `+` (read and write), optionally followed by `&`,
+/// which changes nothing because every operand gets its own register anyway.
+fn validate_asm_constraint(constraint: &str, is_output: bool, position: Position, diagnostics: &mut Diagnostics) {
+    let letters = if is_output {
+        match constraint.strip_prefix('=').or_else(|| constraint.strip_prefix('+')) {
+            Some(rest) => rest.strip_prefix('&').unwrap_or(rest),
+            None => {
+                diagnostics.report(SemanticError::InvalidInlineAsm(format!("output constraint '{}' must start with '=' or '+'", constraint), position));
+                return
             }
+        }
+    } else {
+        constraint
+    };
+
+    let allowed = if is_output { "rmg" } else { "rmig" };
+
+    if letters.is_empty() || !letters.chars().all(|c| allowed.contains(c)) {
+        diagnostics.report(SemanticError::InvalidInlineAsm(format!("unsupported constraint '{}'", constraint), position))
+    }
+}
+
+/// Checks that the operands that may end up in a register fit into the registers fix-up hands
+/// out to inline asm, less the clobbered ones, which is what gcc calls impossible constraints.
+///
+/// Every operand whose constraint takes a register counts, even one that could stay in memory,
+/// because after register allocation it may already sit in one of them. A constant that can be
+/// an immediate doesn't.
+fn validate_asm_registers<'a>(operands: impl Iterator<Item = &'a AstAsmOperand>, clobbers: &[String], position: Position, diagnostics: &mut Diagnostics) {
+    let needed = operands
+        .filter(|AstAsmOperand::Operand(constraint, expr)| {
+            let immediate = constraint.contains(['i', 'g']) && matches!(expr, AstExpression::Constant(_));
+            constraint.contains(['r', 'g']) && !immediate
+        })
+        .count();
+
+    let clobbered = clobbers.iter().filter_map(|name| AssemblyRegister::from_name(name)).collect::<Vec<_>>();
+    let available = ASM_OPERAND_REGISTERS.iter().filter(|register| !clobbered.contains(register)).count();
 
+    if needed > available {
+        diagnostics.report(SemanticError::InvalidInlineAsm(
+            format!("impossible constraints, {} operands need a register and only {} are available", needed, available),
+            position,
+        ))
+    }
+}
+
+/// Checks that every `%` in the template is either `%%` or refers to an existing operand.
+fn validate_asm_template(template: &str, operand_count: usize, position: Position, diagnostics: &mut Diagnostics) {
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            continue;
+        }
+
+        if chars.peek() == Some(&'%') {
+            chars.next();
+            continue;
+        }
+
+        let mut digits = String::new();
+        while let Some(digit) = chars.next_if(|c| c.is_ascii_digit()) {
+            digits.push(digit);
+        }
+
+        match digits.parse::<usize>() {
+            Ok(number) if number < operand_count => {}
+            Ok(number) => diagnostics.report(SemanticError::InvalidInlineAsm(format!("operand number %{} out of range", number), position)),
+            Err(_) => diagnostics.report(SemanticError::InvalidInlineAsm("'%' must be followed by an operand number or '%'".to_string(), position)),
+        }
+    }
+}
+
+/// Evaluates a resolved constant expression, reporting it and using 0 in its place if it isn't one.
+fn evaluate_constant(ast_expression: &AstExpression, position: Position, diagnostics: &mut Diagnostics) -> i32 {
+    evaluate_constant_expression(ast_expression).unwrap_or_else(|err| {
+        diagnostics.report(SemanticError::InvalidConstantExpression(err, position));
+        0
+    })
+}
+
+fn resolve_declaration(ast_declaration: AstDeclaration, scope: &mut Scope, diagnostics: &mut Diagnostics) -> AstDeclaration {
+    match ast_declaration {
+        AstDeclaration::Declaration(name, declared_type, init, position) => {
+            let resolved_type = resolve_type(declared_type, scope, position, diagnostics);
             let unique_name = format!("{}.0", name.clone());
 
-            variable_map.insert(name.clone(), unique_name.clone());
+            scope.declare(name, IdentifierEntry::Variable(unique_name.clone(), resolved_type.clone()), position, diagnostics);
+
+            let init = init.map(|expr| resolve_expression(expr, scope, diagnostics));
 
+            AstDeclaration::Declaration(unique_name, resolved_type, init, position)
+        }
+        AstDeclaration::Typedef(name, aliased_type, position) => {
+            let resolved_type = resolve_type(aliased_type, scope, position, diagnostics);
+
+            match scope.identifiers.get(&name) {
+                // C11 allows repeating a typedef as long as it names the same type
+                Some((IdentifierEntry::Typedef(previous), _)) if *previous == resolved_type && scope.standard >= Standard::C11 => (),
+                _ => scope.declare(name.clone(), IdentifierEntry::Typedef(resolved_type.clone()), position, diagnostics),
+            }
+
+            AstDeclaration::Typedef(name, resolved_type, position)
+        }
+        AstDeclaration::Enum(tag, enumerators, position) => {
+            if let Some(tag) = &tag {
+                match scope.enum_tags.get(tag) {
+                    Some(previous) => diagnostics.report(SemanticError::DuplicateEnumTag(tag.clone(), position, *previous)),
+                    None => {
+                        scope.enum_tags.insert(tag.clone(), position);
+                    }
+                }
+            }
+
+            let mut resolved_enumerators = vec![];
+            let mut next_value = Some(0);
 
-            if let Some(expr) = init {
-                Ok(AstDeclaration::Declaration(unique_name, Some(resolve_expression(expr, &variable_map)?)))
-            } else {
-                Ok(AstDeclaration::Declaration(unique_name, init))
+            for enumerator in enumerators {
+                match enumerator {
+                    AstEnumerator::Enumerator(name, value, enumerator_position) => {
+                        let value = match value {
+                            Some(expr) => evaluate_constant(&resolve_expression(expr, scope, diagnostics), enumerator_position, diagnostics),
+                            None => next_value.unwrap_or_else(|| {
+                                // the previous enumerator was INT_MAX, there is no implicit next value
+                                diagnostics.report(SemanticError::InvalidConstantExpression(ConstantError::Overflow, enumerator_position));
+                                0
+                            }),
+                        };
+
+                        next_value = value.checked_add(1);
+                        scope.declare(name.clone(), IdentifierEntry::EnumConstant(value), enumerator_position, diagnostics);
+                        resolved_enumerators.push(AstEnumerator::Enumerator(name, Some(AstExpression::Constant(value)), enumerator_position));
+                    }
+                }
             }
+
+            AstDeclaration::Enum(tag, resolved_enumerators, position)
+        }
+        AstDeclaration::StaticAssert(condition, message, position) => {
+            let condition = resolve_expression(condition, scope, diagnostics);
+
+            match evaluate_constant_expression(&condition) {
+                Ok(0) => diagnostics.report(SemanticError::StaticAssertionFailed(message.clone(), position)),
+                Ok(_) => (),
+                Err(err) => diagnostics.report(SemanticError::InvalidConstantExpression(err, position)),
+            }
+
+            AstDeclaration::StaticAssert(condition, message, position)
         }
     }
 
 }
 
-fn resolve_expression(ast_expression: AstExpression, variable_map: &HashMap<String, String>) -> Result<AstExpression, SemanticError> {
-    match ast_expression {
-        AstExpression::Assignment(left, right) => {
-            if let AstExpression::Var(_) = *left {
-                Ok(AstExpression::Assignment(Box::new(resolve_expression(*left, variable_map)?), Box::new(resolve_expression(*right, variable_map)?)))
-            } else {
-                Err(SemanticError::InvalidLValue)
+/// Replaces typedef names with the type they alias and checks that enum tags exist.
+///
+/// Unknown types are reported and treated as `int`, so the declaration still declares its name.
+fn resolve_type(ast_type: AstType, scope: &Scope, position: Position, diagnostics: &mut Diagnostics) -> AstType {
+    match ast_type {
+        AstType::Int => AstType::Int,
+        AstType::Enum(tag) => {
+            if !scope.enum_tags.contains_key(&tag) {
+                diagnostics.report(SemanticError::UndeclaredType(format!("enum {}", tag), position));
             }
+
+            AstType::Enum(tag)
         }
-        AstExpression::Var(identifier) => {
-            if variable_map.contains_key(&identifier) {
-                Ok(AstExpression::Var(variable_map.get(&identifier).unwrap().clone()))
-            } else {
-                Err(SemanticError::UndeclaredVariable)
+        AstType::Typedef(name) => match scope.lookup(&name) {
+            Some(IdentifierEntry::Typedef(aliased_type)) => aliased_type.clone(),
+            _ => {
+                diagnostics.report(SemanticError::UndeclaredType(name, position));
+                AstType::Int
             }
         },
-        AstExpression::Binary(bin_op, left, right) => {
-            Ok(AstExpression::Binary(bin_op, Box::new(resolve_expression(*left, variable_map)?), Box::new(resolve_expression(*right, variable_map)?)))
+        AstType::Pointer(referenced) => AstType::Pointer(Box::new(resolve_type(*referenced, scope, position, diagnostics))),
+        AstType::Const(qualified) => {
+            // `typedef const int T; const T x;` is still just `const int`
+            match resolve_type(*qualified, scope, position, diagnostics) {
+                already_const @ AstType::Const(_) => already_const,
+                other => AstType::Const(Box::new(other)),
+            }
+        }
+    }
+}
+
+/// Resolves the target of an assignment, compound assignment or increment.
+///
+/// The target has to name a variable that isn't `const`. Enumerators resolve to constants,
+/// so the variable check happens after resolution. Parentheses around the target, as in
+/// `(x) = 1`, don't change what it names and are dropped.
+fn resolve_modifiable_lvalue(ast_expression: AstExpression, scope: &Scope, position: Position, diagnostics: &mut Diagnostics) -> AstExpression {
+    let mut ast_expression = ast_expression;
+
+    while let AstExpression::Parenthesized(inner) = ast_expression {
+        ast_expression = *inner;
+    }
+
+    if let AstExpression::Var(identifier, _) = &ast_expression {
+        if let Some(IdentifierEntry::Variable(_, AstType::Const(_))) = scope.lookup(identifier) {
+            diagnostics.report(SemanticError::AssignmentToConst(identifier.clone(), position));
+        }
+    }
+
+    let resolved = resolve_expression(ast_expression, scope, diagnostics);
+
+    if !matches!(resolved, AstExpression::Var(..)) {
+        diagnostics.report(SemanticError::InvalidLValue(position));
+    }
+
+    resolved
+}
+
+fn resolve_expression(ast_expression: AstExpression, scope: &Scope, diagnostics: &mut Diagnostics) -> AstExpression {
+    match ast_expression {
+        AstExpression::Assignment(left, right, position) => {
+            let left = resolve_modifiable_lvalue(*left, scope, position, diagnostics);
+            AstExpression::Assignment(Box::new(left), Box::new(resolve_expression(*right, scope, diagnostics)), position)
+        }
+        AstExpression::CompoundAssignment(bin_op, left, right, position) => {
+            let left = resolve_modifiable_lvalue(*left, scope, position, diagnostics);
+            AstExpression::CompoundAssignment(bin_op, Box::new(left), Box::new(resolve_expression(*right, scope, diagnostics)), position)
         }
-        AstExpression::Unary(un_op, expr) => {
-            Ok(AstExpression::Unary(un_op, Box::new(resolve_expression(*expr, variable_map)?)))
+        AstExpression::Increment(inc_op, expr, position) => {
+            AstExpression::Increment(inc_op, Box::new(resolve_modifiable_lvalue(*expr, scope, position, diagnostics)), position)
+        }
+        AstExpression::Var(identifier, position) => {
+            match scope.lookup(&identifier) {
+                Some(IdentifierEntry::Variable(unique_name, _)) => AstExpression::Var(unique_name.clone(), position),
+                Some(IdentifierEntry::EnumConstant(value)) => AstExpression::Constant(*value),
+                Some(IdentifierEntry::Typedef(_)) | None => {
+                    if !diagnostics.reported_undeclared(&identifier) {
+                        diagnostics.report(SemanticError::UndeclaredVariable(identifier.clone(), position));
+                    }
+
+                    AstExpression::Var(identifier, position)
+                }
+            }
         },
-        _ => Ok(ast_expression)
+        AstExpression::Binary(bin_op, left, right, position) => {
+            let left = resolve_expression(*left, scope, diagnostics);
+            let right = resolve_expression(*right, scope, diagnostics);
+            AstExpression::Binary(bin_op, Box::new(left), Box::new(right), position)
+        }
+        AstExpression::Unary(un_op, expr, position) => {
+            AstExpression::Unary(un_op, Box::new(resolve_expression(*expr, scope, diagnostics)), position)
+        },
+        AstExpression::Parenthesized(expr) => {
+            AstExpression::Parenthesized(Box::new(resolve_expression(*expr, scope, diagnostics)))
+        }
+        _ => ast_expression
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::semantics::constant_evaluation::ConstantError;
+    use crate::compiler::semantics::diagnostics::{Diagnostics, SemanticError};
+    use crate::compiler::semantics::variable_resolution::{resolve_declaration, resolve_expression, resolve_program, resolve_statement, Scope};
+    use crate::compiler::standard::Standard;
+    use crate::storage::ast::{AstAsmOperand, AstBinaryOp, AstBlockItem, AstDeclaration, AstEnumerator, AstExpression, AstFunctionDefinition, AstIncrementOp, AstProgram, AstStatement, AstType};
+    use crate::storage::position::Position;
+    use crate::storage::symbol_table::{StorageClass, Symbol};
+
+    /// Runs `resolve` with a fresh collector and turns whatever it reported into an error,
+    /// the same way `resolve_program` does.
+    fn collect<T>(resolve: impl FnOnce(&mut Diagnostics) -> T) -> Result<T, Vec<SemanticError>> {
+        let mut diagnostics = Diagnostics::new();
+        let resolved = resolve(&mut diagnostics);
+
+        if diagnostics.errors().is_empty() {
+            Ok(resolved)
+        } else {
+            Err(diagnostics.into_errors())
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn resolve_enum_implicit_and_explicit_values() {
+        let mut scope = Scope::new(Standard::default());
+        let declaration = AstDeclaration::Enum(Some("Color".to_string()), vec![
+            AstEnumerator::Enumerator("RED".to_string(), None, Position::default()),
+            AstEnumerator::Enumerator("GREEN".to_string(), Some(AstExpression::Constant(5)), Position::default()),
+            AstEnumerator::Enumerator("BLUE".to_string(), None, Position::default()),
+            AstEnumerator::Enumerator("WHITE".to_string(), Some(AstExpression::Binary(
+                AstBinaryOp::Multiply,
+                Box::new(AstExpression::Var("BLUE".to_string(), Position::default())),
+                Box::new(AstExpression::Constant(2)),
+                Position::default(),
+            )), Position::default()),
+        ], Position::default());
+
+        assert_eq!(
+            collect(|diagnostics| resolve_declaration(declaration, &mut scope, diagnostics)),
+            Ok(AstDeclaration::Enum(Some("Color".to_string()), vec![
+                AstEnumerator::Enumerator("RED".to_string(), Some(AstExpression::Constant(0)), Position::default()),
+                AstEnumerator::Enumerator("GREEN".to_string(), Some(AstExpression::Constant(5)), Position::default()),
+                AstEnumerator::Enumerator("BLUE".to_string(), Some(AstExpression::Constant(6)), Position::default()),
+                AstEnumerator::Enumerator("WHITE".to_string(), Some(AstExpression::Constant(12)), Position::default()),
+            ], Position::default()))
+        );
+        assert_eq!(
+            collect(|diagnostics| resolve_expression(AstExpression::Var("BLUE".to_string(), Position::default()), &scope, diagnostics)),
+            Ok(AstExpression::Constant(6))
+        );
+        assert_eq!(
+            collect(|diagnostics| resolve_expression(AstExpression::Assignment(
+                Box::new(AstExpression::Var("BLUE".to_string(), Position::default())),
+                Box::new(AstExpression::Constant(1)),
+                Position::default(),
+            ), &scope, diagnostics)),
+            Err(vec![SemanticError::InvalidLValue(Position::default())])
+        );
+    }
+
+    #[test]
+    fn resolve_enum_implicit_value_overflow() {
+        let mut scope = Scope::new(Standard::default());
+        let declaration = AstDeclaration::Enum(None, vec![
+            AstEnumerator::Enumerator("LAST".to_string(), Some(AstExpression::Constant(i32::MAX)), Position::default()),
+            AstEnumerator::Enumerator("TOO_FAR".to_string(), None, Position::default()),
+        ], Position::default());
+
+        assert_eq!(
+            collect(|diagnostics| resolve_declaration(declaration, &mut scope, diagnostics)),
+            Err(vec![SemanticError::InvalidConstantExpression(ConstantError::Overflow, Position::default())])
+        );
+    }
+
+    #[test]
+    fn resolve_typedef_declaration() {
+        let mut scope = Scope::new(Standard::default());
+
+        assert_eq!(
+            collect(|diagnostics| resolve_declaration(AstDeclaration::Typedef("T".to_string(), AstType::Int, Position::default()), &mut scope, diagnostics)),
+            Ok(AstDeclaration::Typedef("T".to_string(), AstType::Int, Position::default()))
+        );
+        assert_eq!(
+            collect(|diagnostics| resolve_declaration(AstDeclaration::Declaration("x".to_string(), AstType::Typedef("T".to_string()), None, Position::default()), &mut scope, diagnostics)),
+            Ok(AstDeclaration::Declaration("x.0".to_string(), AstType::Int, None, Position::default()))
+        );
+        assert_eq!(
+            collect(|diagnostics| resolve_declaration(AstDeclaration::Declaration("y".to_string(), AstType::Typedef("U".to_string()), None, Position::default()), &mut scope, diagnostics)),
+            Err(vec![SemanticError::UndeclaredType("U".to_string(), Position::default())])
+        );
+        assert_eq!(
+            collect(|diagnostics| resolve_declaration(AstDeclaration::Declaration("c".to_string(), AstType::Enum("Color".to_string()), None, Position::default()), &mut scope, diagnostics)),
+            Err(vec![SemanticError::UndeclaredType("enum Color".to_string(), Position::default())])
+        );
+        assert_eq!(
+            collect(|diagnostics| resolve_expression(AstExpression::Var("T".to_string(), Position::default()), &scope, diagnostics)),
+            Err(vec![SemanticError::UndeclaredVariable("T".to_string(), Position::default())])
+        );
+    }
+
+    #[test]
+    fn resolve_const_assignment_is_rejected() {
+        let mut scope = Scope::new(Standard::default());
+        collect(|diagnostics| resolve_declaration(AstDeclaration::Typedef("T".to_string(), AstType::Const(Box::new(AstType::Int)), Position::default()), &mut scope, diagnostics)).unwrap();
+
+        assert_eq!(
+            collect(|diagnostics| resolve_declaration(AstDeclaration::Declaration(
+                "x".to_string(),
+                AstType::Const(Box::new(AstType::Typedef("T".to_string()))),
+                Some(AstExpression::Constant(1)),
+                Position::default()
+            ), &mut scope, diagnostics)),
+            Ok(AstDeclaration::Declaration("x.0".to_string(), AstType::Const(Box::new(AstType::Int)), Some(AstExpression::Constant(1)), Position::default()))
+        );
+
+        let position = Position::new(3, 7);
+
+        assert_eq!(
+            collect(|diagnostics| resolve_expression(AstExpression::Assignment(
+                Box::new(AstExpression::Var("x".to_string(), Position::default())),
+                Box::new(AstExpression::Constant(2)),
+                position,
+            ), &scope, diagnostics)),
+            Err(vec![SemanticError::AssignmentToConst("x".to_string(), position)])
+        );
+        assert_eq!(
+            collect(|diagnostics| resolve_expression(AstExpression::CompoundAssignment(
+                AstBinaryOp::Add,
+                Box::new(AstExpression::Var("x".to_string(), Position::default())),
+                Box::new(AstExpression::Constant(2)),
+                position,
+            ), &scope, diagnostics)),
+            Err(vec![SemanticError::AssignmentToConst("x".to_string(), position)])
+        );
+        assert_eq!(
+            collect(|diagnostics| resolve_expression(AstExpression::Increment(
+                AstIncrementOp::PostDecrement,
+                Box::new(AstExpression::Var("x".to_string(), Position::default())),
+                position,
+            ), &scope, diagnostics)),
+            Err(vec![SemanticError::AssignmentToConst("x".to_string(), position)])
+        );
+    }
+
+    #[test]
+    fn resolve_pointer_to_const_is_assignable() {
+        let mut scope = Scope::new(Standard::default());
+        let pointer_to_const = AstType::Pointer(Box::new(AstType::Const(Box::new(AstType::Int))));
+        let const_pointer = AstType::Const(Box::new(AstType::Pointer(Box::new(AstType::Int))));
+
+        collect(|diagnostics| resolve_declaration(AstDeclaration::Declaration("p".to_string(), pointer_to_const, None, Position::default()), &mut scope, diagnostics)).unwrap();
+        collect(|diagnostics| resolve_declaration(AstDeclaration::Declaration("q".to_string(), const_pointer, None, Position::default()), &mut scope, diagnostics)).unwrap();
+
+        assert_eq!(
+            collect(|diagnostics| resolve_expression(AstExpression::Assignment(
+                Box::new(AstExpression::Var("p".to_string(), Position::default())),
+                Box::new(AstExpression::Constant(0)),
+                Position::default(),
+            ), &scope, diagnostics)),
+            Ok(AstExpression::Assignment(
+                Box::new(AstExpression::Var("p.0".to_string(), Position::default())),
+                Box::new(AstExpression::Constant(0)),
+                Position::default(),
+            ))
+        );
+        assert_eq!(
+            collect(|diagnostics| resolve_expression(AstExpression::Assignment(
+                Box::new(AstExpression::Var("q".to_string(), Position::default())),
+                Box::new(AstExpression::Constant(0)),
+                Position::default(),
+            ), &scope, diagnostics)),
+            Err(vec![SemanticError::AssignmentToConst("q".to_string(), Position::default())])
+        );
+    }
+
+    #[test]
+    fn resolve_typedef_redefinition_depends_on_standard() {
+        for (standard, expected) in [(Standard::C99, Err(vec![SemanticError::DuplicateVariable("T".to_string(), Position::default(), Position::default())])), (Standard::C11, Ok(()))] {
+            let mut scope = Scope::new(standard);
+            collect(|diagnostics| resolve_declaration(AstDeclaration::Typedef("T".to_string(), AstType::Int, Position::default()), &mut scope, diagnostics)).unwrap();
+
+            assert_eq!(
+                collect(|diagnostics| resolve_declaration(AstDeclaration::Typedef("T".to_string(), AstType::Int, Position::default()), &mut scope, diagnostics)).map(|_| ()),
+                expected
+            );
+            assert_eq!(
+                collect(|diagnostics| resolve_declaration(AstDeclaration::Typedef("T".to_string(), AstType::Const(Box::new(AstType::Int)), Position::default()), &mut scope, diagnostics)),
+                Err(vec![SemanticError::DuplicateVariable("T".to_string(), Position::default(), Position::default())])
+            );
+        }
+    }
+
+    #[test]
+    fn resolve_static_assert() {
+        let mut scope = Scope::new(Standard::default());
+        let position = Position::new(2, 5);
+        collect(|diagnostics| resolve_declaration(AstDeclaration::Enum(None, vec![AstEnumerator::Enumerator("SIZE".to_string(), Some(AstExpression::Constant(4)), Position::default())], Position::default()), &mut scope, diagnostics)).unwrap();
+        collect(|diagnostics| resolve_declaration(AstDeclaration::Declaration("x".to_string(), AstType::Int, None, Position::default()), &mut scope, diagnostics)).unwrap();
+
+        let size_is = |expected| AstExpression::Binary(
+            AstBinaryOp::Equal,
+            Box::new(AstExpression::Var("SIZE".to_string(), Position::default())),
+            Box::new(AstExpression::Constant(expected)),
+            Position::default(),
+        );
+
+        assert!(collect(|diagnostics| resolve_declaration(AstDeclaration::StaticAssert(size_is(4), "size".to_string(), position), &mut scope, diagnostics)).is_ok());
+        assert_eq!(
+            collect(|diagnostics| resolve_declaration(AstDeclaration::StaticAssert(size_is(8), "size must be 8".to_string(), position), &mut scope, diagnostics)),
+            Err(vec![SemanticError::StaticAssertionFailed("size must be 8".to_string(), position)])
+        );
+        assert_eq!(
+            collect(|diagnostics| resolve_declaration(AstDeclaration::StaticAssert(AstExpression::Var("x".to_string(), Position::default()), "x".to_string(), position), &mut scope, diagnostics)),
+            Err(vec![SemanticError::InvalidConstantExpression(ConstantError::NotConstant, position)])
+        );
+    }
+
+    #[test]
+    fn resolve_inline_asm() {
+        let mut scope = Scope::new(Standard::default());
+        let position = Position::new(3, 5);
+        collect(|diagnostics| resolve_declaration(AstDeclaration::Enum(None, vec![AstEnumerator::Enumerator("SHIFT".to_string(), Some(AstExpression::Constant(2)), Position::default())], Position::default()), &mut scope, diagnostics)).unwrap();
+        collect(|diagnostics| resolve_declaration(AstDeclaration::Declaration("x".to_string(), AstType::Int, None, Position::default()), &mut scope, diagnostics)).unwrap();
+        collect(|diagnostics| resolve_declaration(AstDeclaration::Declaration("k".to_string(), AstType::Const(Box::new(AstType::Int)), None, Position::default()), &mut scope, diagnostics)).unwrap();
+
+        let inline_asm = |template: &str, output: (&str, &str), input: &str, clobber: &str| AstStatement::InlineAsm(
+            template.to_string(),
+            vec![AstAsmOperand::Operand(output.0.to_string(), AstExpression::Var(output.1.to_string(), Position::default()))],
+            vec![AstAsmOperand::Operand(input.to_string(), AstExpression::Var("SHIFT".to_string(), Position::default()))],
+            vec![clobber.to_string()],
+            position,
+        );
+
+        assert_eq!(
+            collect(|diagnostics| resolve_statement(inline_asm("sall %1, %0", ("+r", "x"), "i", "cc"), &scope, diagnostics)),
+            Ok(AstStatement::InlineAsm(
+                "sall %1, %0".to_string(),
+                vec![AstAsmOperand::Operand("+r".to_string(), AstExpression::Var("x.0".to_string(), Position::default()))],
+                vec![AstAsmOperand::Operand("i".to_string(), AstExpression::Constant(2))],
+                vec!["cc".to_string()],
+                position,
+            ))
+        );
+        assert_eq!(
+            collect(|diagnostics| resolve_statement(inline_asm("sall %1, %0", ("+r", "k"), "i", "cc"), &scope, diagnostics)),
+            Err(vec![SemanticError::AssignmentToConst("k".to_string(), position)])
+        );
+        assert_eq!(
+            collect(|diagnostics| resolve_statement(inline_asm("sall %1, %0", ("r", "x"), "i", "cc"), &scope, diagnostics)),
+            Err(vec![SemanticError::InvalidInlineAsm("output constraint 'r' must start with '=' or '+'".to_string(), position)])
+        );
+        assert_eq!(
+            collect(|diagnostics| resolve_statement(inline_asm("sall %1, %0", ("=r", "x"), "q", "cc"), &scope, diagnostics)),
+            Err(vec![SemanticError::InvalidInlineAsm("unsupported constraint 'q'".to_string(), position)])
+        );
+        assert_eq!(
+            collect(|diagnostics| resolve_statement(inline_asm("sall %2, %0", ("=r", "x"), "r", "cc"), &scope, diagnostics)),
+            Err(vec![SemanticError::InvalidInlineAsm("operand number %2 out of range".to_string(), position)])
+        );
+        assert_eq!(
+            collect(|diagnostics| resolve_statement(inline_asm("sall %1, %0", ("=r", "x"), "r", "rsp"), &scope, diagnostics)),
+            Err(vec![SemanticError::InvalidInlineAsm("unknown or unsupported clobber 'rsp'".to_string(), position)])
+        );
+
+        // eight of the nine operand registers are left, the immediate needs none of them
+        let register_operands = |count: usize, constraint: &str| (0..count)
+            .map(|_| AstAsmOperand::Operand(constraint.to_string(), AstExpression::Var("x".to_string(), Position::default())))
+            .chain([AstAsmOperand::Operand("g".to_string(), AstExpression::Constant(1))])
+            .collect::<Vec<_>>();
+        let clobbering_rcx = |inputs| AstStatement::InlineAsm("".to_string(), vec![], inputs, vec!["rcx".to_string()], position);
+
+        assert!(collect(|diagnostics| resolve_statement(clobbering_rcx(register_operands(8, "r")), &scope, diagnostics)).is_ok());
+        assert_eq!(
+            collect(|diagnostics| resolve_statement(clobbering_rcx(register_operands(9, "rm")), &scope, diagnostics)),
+            Err(vec![SemanticError::InvalidInlineAsm("impossible constraints, 9 operands need a register and only 8 are available".to_string(), position)])
+        );
+    }
+
+    #[test]
+    fn resolve_program_collects_every_error() {
+        // int main(void) {
+        //     int x = 1;
+        //     int x = a + a;
+        //     return b;
+        // }
+        let var = |name: &str, line, column| Box::new(AstExpression::Var(name.to_string(), Position::new(line, column)));
+        let program = AstProgram::Program(vec![], AstFunctionDefinition::Function("main".to_string(), vec![
+            AstBlockItem::Declaration(AstDeclaration::Declaration("x".to_string(), AstType::Int, Some(AstExpression::Constant(1)), Position::new(2, 9))),
+            AstBlockItem::Declaration(AstDeclaration::Declaration(
+                "x".to_string(),
+                AstType::Int,
+                Some(AstExpression::Binary(AstBinaryOp::Add, var("a", 3, 13), var("a", 3, 17), Position::new(3, 15))),
+                Position::new(3, 9),
+            )),
+            AstBlockItem::Statement(AstStatement::Return(*var("b", 4, 12), Position::new(4, 5))),
+        ], Position::new(1, 5)));
+
+        assert_eq!(
+            resolve_program(program, Standard::default()),
+            Err(vec![
+                SemanticError::DuplicateVariable("x".to_string(), Position::new(3, 9), Position::new(2, 9)),
+                SemanticError::UndeclaredVariable("a".to_string(), Position::new(3, 13)),
+                SemanticError::UndeclaredVariable("b".to_string(), Position::new(4, 12)),
+            ])
+        );
+    }
+
+    #[test]
+    fn resolve_program_builds_symbol_table() {
+        // _Static_assert(1, "file scope");
+        // int main(void) {
+        //     enum { ZERO } e;
+        //     int x = ZERO;
+        //     return x;
+        // }
+        let program = AstProgram::Program(
+            vec![AstDeclaration::StaticAssert(AstExpression::Constant(1), "file scope".to_string(), Position::new(1, 1))],
+            AstFunctionDefinition::Function("main".to_string(), vec![
+                AstBlockItem::Declaration(AstDeclaration::Enum(None, vec![
+                    AstEnumerator::Enumerator("ZERO".to_string(), None, Position::new(3, 12)),
+                ], Position::new(3, 5))),
+                AstBlockItem::Declaration(AstDeclaration::Declaration(
+                    "x".to_string(),
+                    AstType::Int,
+                    Some(AstExpression::Var("ZERO".to_string(), Position::new(4, 13))),
+                    Position::new(4, 9),
+                )),
+                AstBlockItem::Statement(AstStatement::Return(AstExpression::Var("x".to_string(), Position::new(5, 12)), Position::new(5, 5))),
+            ], Position::new(2, 5)),
+        );
+
+        let (_, symbols) = resolve_program(program, Standard::default()).unwrap();
+
+        assert_eq!(
+            symbols.iter().cloned().collect::<Vec<_>>(),
+            vec![
+                Symbol { name: "ZERO".to_string(), unique_name: "ZERO".to_string(), symbol_type: AstType::Int, storage_class: StorageClass::Constant(0), scope_depth: 1, position: Position::new(3, 12) },
+                Symbol { name: "x".to_string(), unique_name: "x.0".to_string(), symbol_type: AstType::Int, storage_class: StorageClass::Automatic, scope_depth: 1, position: Position::new(4, 9) },
+            ]
+        );
+        assert_eq!(symbols.get("x.0").map(|symbol| symbol.name.as_str()), Some("x"));
+        assert_eq!(symbols.variables().count(), 1);
+    }
+}
@@ -0,0 +1,441 @@
+//! Warnings about code that is legal C but most likely a mistake.
+//!
+//! The checks run on the resolved AST, once every semantic error has been ruled out. Each
+//! warning has a stable name, which the `-W` flags use to enable, disable or promote it.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+use crate::compiler::standard::Standard;
+use crate::storage::ast::{AstAsmOperand, AstBlockItem, AstDeclaration, AstExpression, AstFunctionDefinition, AstProgram, AstStatement};
+use crate::storage::position::Position;
+use crate::storage::symbol_table::{StorageClass, SymbolTable};
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum WarningKind {
+    UnusedVariable,
+    Uninitialized,
+    UnreachableCode,
+    ReturnType,
+}
+
+impl WarningKind {
+    pub const ALL: [WarningKind; 4] = [
+        WarningKind::UnusedVariable,
+        WarningKind::Uninitialized,
+        WarningKind::UnreachableCode,
+        WarningKind::ReturnType,
+    ];
+
+    /// The name used on the command line, the same one gcc uses for the warning.
+    pub fn name(&self) -> &'static str {
+        match self {
+            WarningKind::UnusedVariable => "unused-variable",
+            WarningKind::Uninitialized => "uninitialized",
+            WarningKind::UnreachableCode => "unreachable-code",
+            WarningKind::ReturnType => "return-type",
+        }
+    }
+}
+
+impl FromStr for WarningKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        WarningKind::ALL
+            .into_iter()
+            .find(|kind| kind.name() == s)
+            .ok_or_else(|| format!("unknown warning '{}'", s))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Warning {
+    UnusedVariable(String, Position), // UnusedVariable(identifier, declaration)
+    Uninitialized(String, Position), // Uninitialized(identifier, first use)
+    UnreachableCode(Position), // UnreachableCode(first unreachable block item)
+    MissingReturn(String, Position), // MissingReturn(function, position)
+}
+
+impl Warning {
+    pub fn kind(&self) -> WarningKind {
+        match self {
+            Warning::UnusedVariable(..) => WarningKind::UnusedVariable,
+            Warning::Uninitialized(..) => WarningKind::Uninitialized,
+            Warning::UnreachableCode(..) => WarningKind::UnreachableCode,
+            Warning::MissingReturn(..) => WarningKind::ReturnType,
+        }
+    }
+
+    pub fn position(&self) -> Position {
+        match self {
+            Warning::UnusedVariable(_, position)
+            | Warning::Uninitialized(_, position)
+            | Warning::UnreachableCode(position)
+            | Warning::MissingReturn(_, position) => *position,
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Warning::UnusedVariable(identifier, _) => write!(f, "unused variable '{}'", identifier),
+            Warning::Uninitialized(identifier, _) => write!(f, "'{}' is used uninitialized", identifier),
+            Warning::UnreachableCode(_) => write!(f, "code will never be executed"),
+            Warning::MissingReturn(function, _) => write!(f, "control reaches end of non-void function '{}'", function),
+        }
+    }
+}
+
+/// A single `-W` flag. Flags are applied in the order they were given, so later ones win.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WarningFlag {
+    All, // -Wall
+    Enable(WarningKind), // -W<name>
+    Disable(WarningKind), // -Wno-<name>
+    Error(Option<WarningKind>), // -Werror, -Werror=<name>
+    NoError(WarningKind), // -Wno-error=<name>
+}
+
+impl FromStr for WarningFlag {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "all" {
+            Ok(WarningFlag::All)
+        } else if s == "error" {
+            Ok(WarningFlag::Error(None))
+        } else if let Some(name) = s.strip_prefix("error=") {
+            Ok(WarningFlag::Error(Some(name.parse()?)))
+        } else if let Some(name) = s.strip_prefix("no-error=") {
+            Ok(WarningFlag::NoError(name.parse()?))
+        } else if let Some(name) = s.strip_prefix("no-") {
+            Ok(WarningFlag::Disable(name.parse()?))
+        } else {
+            Ok(WarningFlag::Enable(s.parse()?))
+        }
+    }
+}
+
+/// Which warnings get reported, and which of those fail the compilation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WarningSettings {
+    enabled: HashSet<WarningKind>,
+    errors: HashSet<WarningKind>,
+}
+
+impl Default for WarningSettings {
+    /// Every warning is reported and none of them is an error.
+    fn default() -> Self {
+        WarningSettings { enabled: WarningKind::ALL.into_iter().collect(), errors: HashSet::new() }
+    }
+}
+
+impl WarningSettings {
+    pub fn from_flags(flags: &[WarningFlag]) -> Self {
+        let mut settings = WarningSettings::default();
+
+        for flag in flags {
+            match flag {
+                WarningFlag::All => settings.enabled.extend(WarningKind::ALL),
+                WarningFlag::Enable(kind) => {
+                    settings.enabled.insert(*kind);
+                }
+                WarningFlag::Disable(kind) => {
+                    settings.enabled.remove(kind);
+                }
+                WarningFlag::Error(None) => settings.errors.extend(WarningKind::ALL),
+                // like gcc, promoting a single warning also enables it
+                WarningFlag::Error(Some(kind)) => {
+                    settings.enabled.insert(*kind);
+                    settings.errors.insert(*kind);
+                }
+                WarningFlag::NoError(kind) => {
+                    settings.errors.remove(kind);
+                }
+            }
+        }
+
+        settings
+    }
+
+    pub fn is_enabled(&self, kind: WarningKind) -> bool {
+        self.enabled.contains(&kind)
+    }
+
+    pub fn is_error(&self, kind: WarningKind) -> bool {
+        self.is_enabled(kind) && self.errors.contains(&kind)
+    }
+}
+
+/// Formats `warning` like gcc, naming the flag that controls it at the end.
+pub fn format_warning(file: &str, warning: &Warning, settings: &WarningSettings) -> String {
+    let kind = warning.kind();
+
+    if settings.is_error(kind) {
+        format!("{}:{}: error: {} [-Werror={}]", file, warning.position(), warning, kind.name())
+    } else {
+        format!("{}:{}: warning: {} [-W{}]", file, warning.position(), warning, kind.name())
+    }
+}
+
+/// Collects the enabled warnings for `ast_program`, ordered by position. `symbols` is the
+/// table built while resolving it.
+pub fn check_program(ast_program: &AstProgram, symbols: &SymbolTable, standard: Standard, settings: &WarningSettings) -> Vec<Warning> {
+    let mut warnings = vec![];
+
+    match ast_program {
+        AstProgram::Program(_, function) => check_function(function, symbols, standard, &mut warnings),
+    }
+
+    warnings.retain(|warning| settings.is_enabled(warning.kind()));
+    warnings.sort_by_key(|warning| (warning.position().line, warning.position().column));
+
+    warnings
+}
+
+/// What is known about the variables of a function while walking its body in order.
+///
+/// There is no control flow besides `return` yet, so walking the block items in order visits
+/// every use and assignment in the order they execute.
+struct VariableUsage<'a> {
+    symbols: &'a SymbolTable,
+    declared: HashSet<String>,
+    referenced: HashSet<String>,
+    assigned: HashSet<String>,
+    reported_uninitialized: HashSet<String>,
+}
+
+impl<'a> VariableUsage<'a> {
+    fn new(symbols: &'a SymbolTable) -> Self {
+        VariableUsage { symbols, declared: HashSet::new(), referenced: HashSet::new(), assigned: HashSet::new(), reported_uninitialized: HashSet::new() }
+    }
+
+    fn read(&mut self, identifier: &str, position: Position, warnings: &mut Vec<Warning>) {
+        self.referenced.insert(identifier.to_string());
+
+        if let Some(symbol) = self.symbols.get(identifier) {
+            if symbol.storage_class == StorageClass::Automatic
+                && !self.assigned.contains(identifier)
+                && self.reported_uninitialized.insert(identifier.to_string())
+            {
+                warnings.push(Warning::Uninitialized(symbol.name.clone(), position));
+            }
+        }
+    }
+
+    fn assign(&mut self, identifier: &str) {
+        self.referenced.insert(identifier.to_string());
+        self.assigned.insert(identifier.to_string());
+    }
+}
+
+fn check_function(ast_function: &AstFunctionDefinition, symbols: &SymbolTable, standard: Standard, warnings: &mut Vec<Warning>) {
+    match ast_function {
+        AstFunctionDefinition::Function(identifier, body, position) => {
+            let mut usage = VariableUsage::new(symbols);
+            let mut returned = false;
+
+            for block_item in body {
+                if returned {
+                    // only the first unreachable item is reported, the rest follow from it
+                    if let Some(position) = code_position(block_item) {
+                        warnings.push(Warning::UnreachableCode(position));
+                        break;
+                    }
+                }
+
+                match block_item {
+                    AstBlockItem::Declaration(declaration) => check_declaration(declaration, &mut usage, warnings),
+                    AstBlockItem::Statement(statement) => {
+                        check_statement(statement, &mut usage, warnings);
+                        returned = returned || matches!(statement, AstStatement::Return(..));
+                    }
+                }
+            }
+
+            for variable in symbols.variables() {
+                if usage.declared.contains(&variable.unique_name) && !usage.referenced.contains(&variable.unique_name) {
+                    warnings.push(Warning::UnusedVariable(variable.name.clone(), variable.position));
+                }
+            }
+
+            // since C99 reaching the end of main returns 0
+            let implicit_return = identifier == "main" && standard >= Standard::C99;
+
+            if !returned && !implicit_return {
+                warnings.push(Warning::MissingReturn(identifier.clone(), *position));
+            }
+        }
+    }
+}
+
+/// Where a block item that generates code starts, `None` for the ones that don't.
+fn code_position(ast_block_item: &AstBlockItem) -> Option<Position> {
+    match ast_block_item {
+        AstBlockItem::Declaration(AstDeclaration::Declaration(_, _, Some(_), position)) => Some(*position),
+        AstBlockItem::Declaration(_) => None,
+        AstBlockItem::Statement(AstStatement::Return(_, position))
+        | AstBlockItem::Statement(AstStatement::Expression(_, position))
+        | AstBlockItem::Statement(AstStatement::InlineAsm(.., position)) => Some(*position),
+        AstBlockItem::Statement(AstStatement::Null) => None,
+    }
+}
+
+fn check_declaration(ast_declaration: &AstDeclaration, usage: &mut VariableUsage, warnings: &mut Vec<Warning>) {
+    if let AstDeclaration::Declaration(identifier, _, init, _) = ast_declaration {
+        usage.declared.insert(identifier.clone());
+
+        // the variable is in scope in its own initializer, so `int a = a;` reads it uninitialized
+        if let Some(expr) = init {
+            check_expression(expr, usage, warnings);
+            usage.assigned.insert(identifier.clone());
+        }
+    }
+}
+
+fn check_statement(ast_statement: &AstStatement, usage: &mut VariableUsage, warnings: &mut Vec<Warning>) {
+    match ast_statement {
+        AstStatement::Return(expr, _) | AstStatement::Expression(expr, _) => check_expression(expr, usage, warnings),
+        AstStatement::InlineAsm(_, outputs, inputs, _, position) => {
+            for AstAsmOperand::Operand(_, expr) in inputs {
+                check_expression(expr, usage, warnings);
+            }
+
+            for AstAsmOperand::Operand(constraint, expr) in outputs {
+                if let AstExpression::Var(identifier, _) = expr {
+                    if constraint.starts_with('+') {
+                        usage.read(identifier, *position, warnings);
+                    }
+                    usage.assign(identifier);
+                }
+            }
+        }
+        AstStatement::Null => (),
+    }
+}
+
+fn check_expression(ast_expression: &AstExpression, usage: &mut VariableUsage, warnings: &mut Vec<Warning>) {
+    match ast_expression {
+        AstExpression::Constant(_) => (),
+        AstExpression::Var(identifier, position) => usage.read(identifier, *position, warnings),
+        AstExpression::Unary(_, expr, _) | AstExpression::Parenthesized(expr) => check_expression(expr, usage, warnings),
+        AstExpression::Binary(_, left, right, _) => {
+            check_expression(left, usage, warnings);
+            check_expression(right, usage, warnings);
+        }
+        AstExpression::Assignment(left, right, _) => {
+            check_expression(right, usage, warnings);
+
+            if let AstExpression::Var(identifier, _) = &**left {
+                usage.assign(identifier);
+            }
+        }
+        // these read the target before writing it
+        AstExpression::CompoundAssignment(_, left, right, _) => {
+            check_expression(left, usage, warnings);
+            check_expression(right, usage, warnings);
+
+            if let AstExpression::Var(identifier, _) = &**left {
+                usage.assign(identifier);
+            }
+        }
+        AstExpression::Increment(_, expr, _) => {
+            check_expression(expr, usage, warnings);
+
+            if let AstExpression::Var(identifier, _) = &**expr {
+                usage.assign(identifier);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::parser::{parse_program_with_context, ParserContext};
+    use crate::compiler::semantics::variable_resolution::resolve_program;
+    use crate::compiler::semantics::warnings::{check_program, format_warning, Warning, WarningFlag, WarningKind, WarningSettings};
+    use crate::compiler::standard::Standard;
+    use crate::compiler::tokenizer::tokenize_with_positions;
+    use crate::storage::position::Position;
+
+    fn warnings_for(source: &str, standard: Standard) -> Vec<Warning> {
+        let (mut tokens, positions) = tokenize_with_positions(source, standard).unwrap();
+        let ast = parse_program_with_context(&mut tokens, ParserContext::new(positions, standard)).unwrap();
+        let (resolved_ast, symbols) = resolve_program(ast, standard).unwrap();
+
+        check_program(&resolved_ast, &symbols, standard, &WarningSettings::default())
+    }
+
+    #[test]
+    fn warn_unused_and_uninitialized_variables() {
+        let source = "int main(void) {\n    int a;\n    int b;\n    int c = 1;\n    c += a;\n    a = 2;\n    return a + c;\n}";
+
+        assert_eq!(
+            warnings_for(source, Standard::C17),
+            vec![
+                Warning::UnusedVariable("b".to_string(), Position::new(3, 9)),
+                Warning::Uninitialized("a".to_string(), Position::new(5, 10)),
+            ]
+        );
+    }
+
+    #[test]
+    fn warn_self_initialization_and_increment_of_uninitialized() {
+        let source = "int main(void) {\n    int a = a;\n    int b;\n    b++;\n    return a + b;\n}";
+
+        assert_eq!(
+            warnings_for(source, Standard::C17),
+            vec![
+                Warning::Uninitialized("a".to_string(), Position::new(2, 13)),
+                Warning::Uninitialized("b".to_string(), Position::new(4, 5)),
+            ]
+        );
+    }
+
+    #[test]
+    fn warn_unreachable_code_after_return() {
+        let source = "int main(void) {\n    return 0;\n    ;\n    int a;\n    int b = 2;\n    return b;\n}";
+
+        assert_eq!(
+            warnings_for(source, Standard::C17),
+            vec![
+                Warning::UnusedVariable("a".to_string(), Position::new(4, 9)),
+                Warning::UnreachableCode(Position::new(5, 9)),
+            ]
+        );
+    }
+
+    #[test]
+    fn warn_missing_return_depends_on_function_and_standard() {
+        assert_eq!(warnings_for("int main(void) {\n}", Standard::C99), vec![]);
+        assert_eq!(
+            warnings_for("int main(void) {\n}", Standard::C89),
+            vec![Warning::MissingReturn("main".to_string(), Position::new(1, 5))]
+        );
+        assert_eq!(
+            warnings_for("int answer(void) {\n    42;\n}", Standard::C17),
+            vec![Warning::MissingReturn("answer".to_string(), Position::new(1, 5))]
+        );
+    }
+
+    #[test]
+    fn warning_flags_apply_in_order() {
+        let flags = ["no-unused-variable", "error", "no-error=uninitialized", "error=unused-variable"]
+            .map(|flag| flag.parse::<WarningFlag>().unwrap());
+        let settings = WarningSettings::from_flags(&flags);
+
+        assert!(settings.is_error(WarningKind::UnusedVariable));
+        assert!(settings.is_enabled(WarningKind::Uninitialized) && !settings.is_error(WarningKind::Uninitialized));
+        assert!(settings.is_error(WarningKind::ReturnType));
+        assert_eq!("no-such-warning".parse::<WarningFlag>(), Err("unknown warning 'such-warning'".to_string()));
+
+        let warning = Warning::UnusedVariable("b".to_string(), Position::new(3, 9));
+        assert_eq!(format_warning("main.c", &warning, &settings), "main.c:3:9: error: unused variable 'b' [-Werror=unused-variable]");
+        assert_eq!(
+            format_warning("main.c", &warning, &WarningSettings::default()),
+            "main.c:3:9: warning: unused variable 'b' [-Wunused-variable]"
+        );
+    }
+}
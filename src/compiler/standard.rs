@@ -0,0 +1,49 @@
+//! C language standard revisions the compiler can be restricted to.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Ordered by publication date, so `standard < Standard::C99` asks whether a C99
+/// feature is missing.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Default)]
+pub enum Standard {
+    C89,
+    C99,
+    C11,
+    #[default]
+    C17,
+}
+
+impl Standard {
+    /// Error message for using `feature` when it was introduced after this standard.
+    pub fn not_allowed(&self, feature: &str) -> String {
+        format!("{} are not allowed in {}", feature, self)
+    }
+}
+
+impl FromStr for Standard {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "c89" | "c90" => Ok(Standard::C89),
+            "c99" => Ok(Standard::C99),
+            "c11" => Ok(Standard::C11),
+            "c17" | "c18" => Ok(Standard::C17),
+            _ => Err(format!("unknown language standard '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for Standard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Standard::C89 => "C89",
+            Standard::C99 => "C99",
+            Standard::C11 => "C11",
+            Standard::C17 => "C17",
+        };
+
+        write!(f, "{}", name)
+    }
+}
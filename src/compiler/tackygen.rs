@@ -4,30 +4,50 @@ use crate::{
         tacky::{FunctionDefinition, Instruction, Program, UnaryOp, Val},
     },
 };
-use std::sync::atomic::{AtomicUsize, Ordering};
-use crate::storage::ast::{AstBinaryOp, AstBlockItem, AstDeclaration};
+use crate::compiler::name_generator::NameGenerator;
+use crate::storage::ast::{AstAsmOperand, AstBinaryOp, AstBlockItem, AstDeclaration, AstIncrementOp};
+use crate::storage::position::Position;
+use crate::storage::symbol_table::SymbolTable;
 use crate::storage::tacky::BinaryOp;
 
+/// Settings that change the generated code.
+#[derive(Debug, Clone, Default)]
+pub struct TackyOptions {
+    /// Check signed overflow and division at runtime, like gcc's `-fsanitize=undefined`.
+    pub sanitize_undefined: bool,
+    /// The file runtime errors point to.
+    pub source_file: String,
+}
+
 pub fn emit_tacky(program: AstProgram) -> Program {
+    emit_tacky_with_options(program, &SymbolTable::new(), &TackyOptions::default())
+}
+
+/// Generates TACKY for `program`. Temporaries and labels are numbered from 0 for every call,
+/// skipping the names of the variables in `symbols`.
+pub fn emit_tacky_with_options(program: AstProgram, symbols: &SymbolTable, options: &TackyOptions) -> Program {
+    let mut names = NameGenerator::avoiding(symbols);
+
     match program {
-        AstProgram::Program(program) => {
-            Program::Program(emit_tacky_function(program))
+        // file-scope declarations are static asserts, which generate no code
+        AstProgram::Program(_, program) => {
+            Program::Program(emit_tacky_function(program, options, &mut names))
         }
     }
 }
 
-fn emit_tacky_function(function: AstFunctionDefinition) -> FunctionDefinition {
+fn emit_tacky_function(function: AstFunctionDefinition, options: &TackyOptions, names: &mut NameGenerator) -> FunctionDefinition {
     match function {
-        AstFunctionDefinition::Function(name, body) => {
+        AstFunctionDefinition::Function(name, body, _) => {
             let mut result_instructions = vec![];
 
             for block_item in body {
                 match block_item {
                     AstBlockItem::Declaration(declaration) => {
-                        result_instructions.append(&mut emit_tacky_declaration(declaration));
+                        result_instructions.append(&mut emit_tacky_declaration(declaration, options, names));
                     },
                     AstBlockItem::Statement(statement) => {
-                        result_instructions.append(&mut emit_tacky_statement(statement));
+                        result_instructions.append(&mut emit_tacky_statement(statement, options, names));
                     }
                 }
             }
@@ -39,125 +59,325 @@ fn emit_tacky_function(function: AstFunctionDefinition) -> FunctionDefinition {
     }
 }
 
-fn emit_tacky_statement(statement: AstStatement) -> Vec<Instruction> {
+fn emit_tacky_statement(statement: AstStatement, options: &TackyOptions, names: &mut NameGenerator) -> Vec<Instruction> {
     match statement {
-        AstStatement::Return(expression) => {
-            let (mut instructions, var) = emit_tacky_expression(expression);
+        AstStatement::Return(expression, _) => {
+            let (mut instructions, var) = emit_tacky_expression(expression, options, names);
             instructions.push(Instruction::Return(var));
             instructions
         }
-        AstStatement::Expression(expression) => {
-            let (instructions, _var) = emit_tacky_expression(expression);
+        AstStatement::Expression(expression, _) => {
+            let (instructions, _var) = emit_tacky_expression(expression, options, names);
+            instructions
+        }
+        AstStatement::InlineAsm(template, outputs, inputs, clobbers, _) => {
+            let mut instructions = vec![];
+
+            let outputs = outputs
+                .into_iter()
+                .map(|AstAsmOperand::Operand(constraint, lvalue)| match lvalue {
+                    AstExpression::Var(var_name, _) => (constraint, Val::Var(var_name)),
+                    _ => unreachable!()
+                })
+                .collect();
+
+            let inputs = inputs
+                .into_iter()
+                .map(|AstAsmOperand::Operand(constraint, expression)| {
+                    let (mut input_instructions, val) = emit_tacky_expression(expression, options, names);
+                    instructions.append(&mut input_instructions);
+
+                    // a memory-only operand needs a memory location even for a constant
+                    match val {
+                        Val::Constant(_) if !constraint.contains(['r', 'i', 'g']) => {
+                            let tmp = Val::Var(names.temporary());
+                            instructions.push(Instruction::Copy(val, tmp.clone()));
+                            (constraint, tmp)
+                        }
+                        val => (constraint, val)
+                    }
+                })
+                .collect();
+
+            instructions.push(Instruction::InlineAsm(template, outputs, inputs, clobbers));
             instructions
         }
         AstStatement::Null => vec![]
     }
 }
 
-fn emit_tacky_declaration(declaration: AstDeclaration) -> Vec<Instruction> {
+fn emit_tacky_declaration(declaration: AstDeclaration, options: &TackyOptions, names: &mut NameGenerator) -> Vec<Instruction> {
     match declaration {
-        AstDeclaration::Declaration(identifier, init) => {
+        AstDeclaration::Declaration(identifier, _, init, _) => {
             if let None = init {
                 vec![]
             } else {
-                let (mut instructions, var) = emit_tacky_expression(init.unwrap());
+                let (mut instructions, var) = emit_tacky_expression(init.unwrap(), options, names);
 
                 instructions.push(Instruction::Copy(var, Val::Var(identifier)));
                 instructions
             }
         }
+        // typedefs and enumerators only exist at compile time, references to
+        // enumerators were already replaced by constants
+        AstDeclaration::Typedef(..) | AstDeclaration::Enum(..) | AstDeclaration::StaticAssert(..) => vec![]
     }
 }
 
-fn emit_tacky_expression(expression: AstExpression) -> (Vec<Instruction>, Val) {
-    pub static VAR_COUNTER: AtomicUsize = AtomicUsize::new(0);
-    static AND_COUNTER: AtomicUsize = AtomicUsize::new(0);
-    static OR_COUNTER: AtomicUsize = AtomicUsize::new(0);
-
+fn emit_tacky_expression(expression: AstExpression, options: &TackyOptions, names: &mut NameGenerator) -> (Vec<Instruction>, Val) {
     match expression {
         AstExpression::Constant(num) => (vec![], Val::Constant(num)),
-        AstExpression::Unary(unary_op, inner_exp) => {
-            let (mut inner_instructions, v) = emit_tacky_expression(*inner_exp);
+        AstExpression::Unary(AstUnaryOp::Not, inner_exp, position) if is_condition(&inner_exp) => {
+            match strip_parentheses(*inner_exp) {
+                // `!(a < b)` is `a >= b`
+                AstExpression::Binary(bin_op, left, right, _) if is_comparison(&bin_op) => {
+                    let (mut instructions, v1) = emit_tacky_expression(*left, options, names);
+                    let (mut right_instructions, v2) = emit_tacky_expression(*right, options, names);
+                    let dst = Val::Var(names.temporary());
+
+                    instructions.append(&mut right_instructions);
+                    instructions.push(Instruction::Binary(negate_comparison(convert_binary_op(bin_op)), v1, v2, dst.clone()));
+                    (instructions, dst)
+                }
+                inner => emit_tacky_jumping(AstExpression::Unary(AstUnaryOp::Not, Box::new(inner), position), false, "not", options, names),
+            }
+        }
+        AstExpression::Unary(unary_op, inner_exp, position) => {
+            let (mut inner_instructions, v) = emit_tacky_expression(*inner_exp, options, names);
 
-            let dst_name = format!("tmp.{}", VAR_COUNTER.fetch_add(1, Ordering::Relaxed));
+            let dst_name = names.temporary();
 
             let dst = Val::Var(dst_name);
             let tacky_op = convert_unary_op(unary_op);
-            inner_instructions.push(Instruction::Unary(tacky_op, v, dst.clone()));
+
+            if tacky_op == UnaryOp::Negate && options.sanitize_undefined {
+                let message = runtime_error(options, position, "signed integer overflow");
+                inner_instructions.push(Instruction::CheckedUnary(tacky_op, v, dst.clone(), message));
+            } else {
+                inner_instructions.push(Instruction::Unary(tacky_op, v, dst.clone()));
+            }
             (inner_instructions, dst)
         }
-        AstExpression::Binary(AstBinaryOp::And, left, right) => {
-            let label_counter = AND_COUNTER.fetch_add(1, Ordering::Relaxed);
-
-            let jump_name = format!("and_false{}", label_counter);
-
-            let (mut left_instructions ,v1) = emit_tacky_expression(*left);
-            left_instructions.push(Instruction::JumpIfZero(v1, jump_name.clone()));
-            let (mut right_instructions ,v2) = emit_tacky_expression(*right);
-            right_instructions.push(Instruction::JumpIfZero(v2, jump_name.clone()));
+        AstExpression::Binary(AstBinaryOp::And, ..) => emit_tacky_jumping(expression, false, "and", options, names),
+        AstExpression::Binary(AstBinaryOp::Or, ..) => emit_tacky_jumping(expression, true, "or", options, names),
+        AstExpression::Binary(bin_op, left, right, position) => {
+            let (mut left_instructions ,v1) = emit_tacky_expression(*left, options, names);
+            let (mut right_instructions, v2) = emit_tacky_expression(*right, options, names);
+            let dst_name = names.temporary();
+            let dst = Val::Var(dst_name);
+            let tacky_op = convert_binary_op(bin_op);
 
             left_instructions.append(&mut right_instructions);
+            left_instructions.append(&mut emit_tacky_binary(tacky_op, v1, v2, dst.clone(), position, options, names));
+            (left_instructions, dst)
+        },
+        AstExpression::Var(identifier, _) => (vec![], Val::Var(identifier)),
+        AstExpression::Assignment(var , rhs, _) => {
+            let (mut instructions, result) = emit_tacky_expression(*rhs, options, names);
 
-            let res_name = format!("tmp.{}", VAR_COUNTER.fetch_add(1, Ordering::Relaxed));
-            let res = Val::Var(res_name);
+            match *var {
+                AstExpression::Var(var_name, _) => {
+                    instructions.push(Instruction::Copy(result, Val::Var(var_name.clone())));
+                    (instructions, Val::Var(var_name.clone()))
+                }
+                _ => unreachable!()
+            }
+        }
+        AstExpression::CompoundAssignment(bin_op, var, rhs, position) => {
+            let (mut instructions, result) = emit_tacky_expression(*rhs, options, names);
 
-            left_instructions.push(Instruction::Copy(Val::Constant(1), res.clone()));
-            left_instructions.push(Instruction::Jump(format!("and_false_end{}", label_counter).to_string()));
-            left_instructions.push(Instruction::Label(jump_name));
-            left_instructions.push(Instruction::Copy(Val::Constant(0), res.clone()));
-            left_instructions.push(Instruction::Label(format!("and_false_end{}", label_counter).to_string()));
+            match *var {
+                AstExpression::Var(var_name, _) => {
+                    let dst = Val::Var(var_name);
+                    instructions.append(&mut emit_tacky_binary(convert_binary_op(bin_op), dst.clone(), result, dst.clone(), position, options, names));
+                    (instructions, dst)
+                }
+                _ => unreachable!()
+            }
+        }
+        AstExpression::Increment(inc_op, var, position) => {
+            let dst = match *var {
+                AstExpression::Var(var_name, _) => Val::Var(var_name),
+                _ => unreachable!()
+            };
 
-            (left_instructions, res)
-        },
-        AstExpression::Binary(AstBinaryOp::Or, left, right) => {
-            let label_counter = OR_COUNTER.fetch_add(1, Ordering::Relaxed);
+            let bin_op = match inc_op {
+                AstIncrementOp::PreIncrement | AstIncrementOp::PostIncrement => BinaryOp::Add,
+                AstIncrementOp::PreDecrement | AstIncrementOp::PostDecrement => BinaryOp::Subtract,
+            };
 
-            let jump_name = format!("or_false{}",label_counter);
+            let update = emit_tacky_binary(bin_op, dst.clone(), Val::Constant(1), dst.clone(), position, options, names);
 
-            let (mut left_instructions ,v1) = emit_tacky_expression(*left);
-            left_instructions.push(Instruction::JumpIfNotZero(v1, jump_name.clone()));
-            let (mut right_instructions ,v2) = emit_tacky_expression(*right);
-            right_instructions.push(Instruction::JumpIfNotZero(v2, jump_name.clone()));
+            match inc_op {
+                AstIncrementOp::PreIncrement | AstIncrementOp::PreDecrement => (update, dst),
+                AstIncrementOp::PostIncrement | AstIncrementOp::PostDecrement => {
+                    // the expression evaluates to the value from before the update
+                    let old_value = Val::Var(names.temporary());
 
-            left_instructions.append(&mut right_instructions);
+                    let mut instructions = vec![Instruction::Copy(dst, old_value.clone())];
+                    instructions.extend(update);
+                    (instructions, old_value)
+                }
+            }
+        }
+        AstExpression::Parenthesized(inner_exp) => emit_tacky_expression(*inner_exp, options, names),
+    }
+}
 
-            let res_name = format!("tmp.{}", VAR_COUNTER.fetch_add(1, Ordering::Relaxed));
-            let res = Val::Var(res_name);
+/// Emits `dst = src1 bin_op src2`, with the runtime checks `-fsanitize=undefined` asks for.
+///
+/// Overflow of `+`, `-` and `*` is caught by the backend from the overflow flag, division
+/// needs explicit checks for a zero divisor and for `INT_MIN / -1` before it runs. Checks
+/// that can't fail for constant operands are left out.
+fn emit_tacky_binary(bin_op: BinaryOp, src1: Val, src2: Val, dst: Val, position: Position, options: &TackyOptions, names: &mut NameGenerator) -> Vec<Instruction> {
+    if !options.sanitize_undefined {
+        return vec![Instruction::Binary(bin_op, src1, src2, dst)];
+    }
 
-            left_instructions.push(Instruction::Copy(Val::Constant(0), res.clone()));
-            left_instructions.push(Instruction::Jump(format!("or_false_end{}", label_counter).to_string()));
-            left_instructions.push(Instruction::Label(jump_name.clone()));
-            left_instructions.push(Instruction::Copy(Val::Constant(1), res.clone()));
-            left_instructions.push(Instruction::Label(format!("or_false_end{}", label_counter).to_string()));
+    match bin_op {
+        BinaryOp::Add | BinaryOp::Subtract | BinaryOp::Multiply => {
+            let message = runtime_error(options, position, "signed integer overflow");
+            vec![Instruction::CheckedBinary(bin_op, src1, src2, dst, message)]
+        }
+        BinaryOp::Divide | BinaryOp::Remainder => {
+            let mut instructions = vec![];
+
+            if !matches!(src2, Val::Constant(divisor) if divisor != 0) {
+                let passed = format!("check_passed{}", names.label_id("check_passed"));
+                instructions.push(Instruction::JumpIfNotZero(src2.clone(), passed.clone()));
+                instructions.push(Instruction::Trap(runtime_error(options, position, "division by zero")));
+                instructions.push(Instruction::Label(passed));
+            }
 
-            (left_instructions, res)
-        },
-        AstExpression::Binary(bin_op, left, right) => {
-            let (mut left_instructions ,v1) = emit_tacky_expression(*left);
-            let (mut right_instructions, v2) = emit_tacky_expression(*right);
-            let dst_name = format!("tmp.{}", VAR_COUNTER.fetch_add(1, Ordering::Relaxed));
-            let dst = Val::Var(dst_name);
-            let tacky_op = convert_binary_op(bin_op);
+            let can_overflow = !matches!(src1, Val::Constant(dividend) if dividend != i32::MIN)
+                && !matches!(src2, Val::Constant(divisor) if divisor != -1);
 
-            left_instructions.append(&mut right_instructions);
-            left_instructions.push(Instruction::Binary(tacky_op, v1, v2, dst.clone()));
-            (left_instructions, dst)
-        },
-        AstExpression::Var(identifier) => (vec![], Val::Var(identifier)),
-        AstExpression::Assignment(var , rhs) => {
-            let (mut instructions, result) = emit_tacky_expression(*rhs);
+            if can_overflow {
+                let passed = format!("check_passed{}", names.label_id("check_passed"));
+                let message = format!("division of {} by -1 cannot be represented in type 'int'", i32::MIN);
 
-            match *var {
-                AstExpression::Var(var_name) => {
-                    instructions.push(Instruction::Copy(result, Val::Var(var_name.clone())));
-                    (instructions, Val::Var(var_name.clone()))
-                }
-                _ => unreachable!()
+                instructions.push(Instruction::JumpIf(BinaryOp::NotEqual, src1.clone(), Val::Constant(i32::MIN), passed.clone()));
+                instructions.push(Instruction::JumpIf(BinaryOp::NotEqual, src2.clone(), Val::Constant(-1), passed.clone()));
+                instructions.push(Instruction::Trap(runtime_error(options, position, &message)));
+                instructions.push(Instruction::Label(passed));
             }
+
+            instructions.push(Instruction::Binary(bin_op, src1, src2, dst));
+            instructions
+        }
+        _ => vec![Instruction::Binary(bin_op, src1, src2, dst)],
+    }
+}
+
+/// Computes the 0 or 1 of `condition` with jumping code: the branches of
+/// `emit_tacky_condition` jump to `{prefix}_false{n}` when `condition` is `jump_when`, and
+/// both ways copy the matching constant into the result.
+fn emit_tacky_jumping(condition: AstExpression, jump_when: bool, prefix: &str, options: &TackyOptions, names: &mut NameGenerator) -> (Vec<Instruction>, Val) {
+    let label_counter = names.label_id(prefix);
+    let jump_name = format!("{}_false{}", prefix, label_counter);
+    let end_name = format!("{}_false_end{}", prefix, label_counter);
+
+    let mut instructions = emit_tacky_condition(condition, jump_when, jump_name.clone(), options, names);
+    let res = Val::Var(names.temporary());
+
+    instructions.push(Instruction::Copy(Val::Constant(!jump_when as i32), res.clone()));
+    instructions.push(Instruction::Jump(end_name.clone()));
+    instructions.push(Instruction::Label(jump_name));
+    instructions.push(Instruction::Copy(Val::Constant(jump_when as i32), res.clone()));
+    instructions.push(Instruction::Label(end_name));
+
+    (instructions, res)
+}
+
+/// Generates code that jumps to `target` when `condition` is `jump_when` and falls through
+/// otherwise. Comparisons branch on the flags of their `cmp` with `JumpIf`, `!` swaps which
+/// way is taken, and `&&` and `||` chain the jumps of their operands, so none of them computes
+/// a 0 or 1 that is tested again right away.
+fn emit_tacky_condition(condition: AstExpression, jump_when: bool, target: String, options: &TackyOptions, names: &mut NameGenerator) -> Vec<Instruction> {
+    match condition {
+        AstExpression::Parenthesized(inner) => emit_tacky_condition(*inner, jump_when, target, options, names),
+        AstExpression::Unary(AstUnaryOp::Not, inner, _) => emit_tacky_condition(*inner, !jump_when, target, options, names),
+        // `a && b` is false as soon as one operand is, `a || b` true as soon as one is
+        AstExpression::Binary(AstBinaryOp::And, left, right, _) if !jump_when => {
+            let mut instructions = emit_tacky_condition(*left, false, target.clone(), options, names);
+            instructions.append(&mut emit_tacky_condition(*right, false, target, options, names));
+            instructions
         }
+        AstExpression::Binary(AstBinaryOp::Or, left, right, _) if jump_when => {
+            let mut instructions = emit_tacky_condition(*left, true, target.clone(), options, names);
+            instructions.append(&mut emit_tacky_condition(*right, true, target, options, names));
+            instructions
+        }
+        // otherwise the left operand can decide the other way, which skips the right one
+        AstExpression::Binary(bin_op @ (AstBinaryOp::And | AstBinaryOp::Or), left, right, _) => {
+            let prefix = if bin_op == AstBinaryOp::And { "and_skip" } else { "or_skip" };
+            let skip_name = format!("{}{}", prefix, names.label_id(prefix));
+
+            let mut instructions = emit_tacky_condition(*left, !jump_when, skip_name.clone(), options, names);
+            instructions.append(&mut emit_tacky_condition(*right, jump_when, target, options, names));
+            instructions.push(Instruction::Label(skip_name));
+            instructions
+        }
+        AstExpression::Binary(bin_op, left, right, _) if is_comparison(&bin_op) => {
+            let (mut instructions, v1) = emit_tacky_expression(*left, options, names);
+            let (mut right_instructions, v2) = emit_tacky_expression(*right, options, names);
+            let comparison = convert_binary_op(bin_op);
+
+            instructions.append(&mut right_instructions);
+            instructions.push(Instruction::JumpIf(if jump_when { comparison } else { negate_comparison(comparison) }, v1, v2, target));
+            instructions
+        }
+        expression => {
+            let (mut instructions, v) = emit_tacky_expression(expression, options, names);
+            instructions.push(if jump_when { Instruction::JumpIfNotZero(v, target) } else { Instruction::JumpIfZero(v, target) });
+            instructions
+        }
+    }
+}
+
+/// Whether `expression` is a comparison, `!`, `&&` or `||`, which `emit_tacky_condition`
+/// lowers to jumps rather than to a value.
+fn is_condition(expression: &AstExpression) -> bool {
+    match expression {
+        AstExpression::Parenthesized(inner) => is_condition(inner),
+        AstExpression::Unary(AstUnaryOp::Not, ..) => true,
+        AstExpression::Binary(bin_op, ..) => is_comparison(bin_op) || matches!(bin_op, AstBinaryOp::And | AstBinaryOp::Or),
+        _ => false,
+    }
+}
+
+fn is_comparison(bin_op: &AstBinaryOp) -> bool {
+    matches!(
+        bin_op,
+        AstBinaryOp::Equal | AstBinaryOp::NotEqual | AstBinaryOp::LessThan | AstBinaryOp::LessOrEqual | AstBinaryOp::GreaterThan | AstBinaryOp::GreaterOrEqual
+    )
+}
+
+/// The comparison that holds exactly when `bin_op` doesn't.
+fn negate_comparison(bin_op: BinaryOp) -> BinaryOp {
+    match bin_op {
+        BinaryOp::Equal => BinaryOp::NotEqual,
+        BinaryOp::NotEqual => BinaryOp::Equal,
+        BinaryOp::LessThan => BinaryOp::GreaterOrEqual,
+        BinaryOp::LessOrEqual => BinaryOp::GreaterThan,
+        BinaryOp::GreaterThan => BinaryOp::LessOrEqual,
+        BinaryOp::GreaterOrEqual => BinaryOp::LessThan,
+        _ => unreachable!() // only comparisons are negated
+    }
+}
+
+fn strip_parentheses(expression: AstExpression) -> AstExpression {
+    match expression {
+        AstExpression::Parenthesized(inner) => strip_parentheses(*inner),
+        other => other,
     }
 }
 
+/// The message a runtime check prints when it fails, worded like gcc's sanitizer.
+fn runtime_error(options: &TackyOptions, position: Position, error: &str) -> String {
+    format!("{}:{}: runtime error: {}", options.source_file, position, error)
+}
+
 fn convert_unary_op(unary_op: AstUnaryOp) -> UnaryOp {
     match unary_op {
         AstUnaryOp::Complement => UnaryOp::Complement,
@@ -185,8 +405,10 @@ fn convert_binary_op(binary_op: AstBinaryOp) -> BinaryOp {
 
 #[cfg(test)]
 mod tests {
-    use crate::compiler::tackygen::{convert_binary_op, convert_unary_op, emit_tacky_expression};
-    use crate::storage::ast::{AstBinaryOp, AstExpression, AstUnaryOp};
+    use crate::compiler::name_generator::NameGenerator;
+    use crate::compiler::tackygen::{convert_binary_op, convert_unary_op, emit_tacky, emit_tacky_expression, emit_tacky_statement, TackyOptions};
+    use crate::storage::ast::{AstAsmOperand, AstBinaryOp, AstBlockItem, AstExpression, AstFunctionDefinition, AstIncrementOp, AstProgram, AstStatement, AstUnaryOp};
+    use crate::storage::position::Position;
     use crate::storage::tacky::{BinaryOp, Instruction, UnaryOp, Val};
 
     #[test]
@@ -222,7 +444,7 @@ mod tests {
     fn convert_constant_tacky_expression_test() {
         let expr = AstExpression::Constant(2);
 
-        let (tacky_instructions, val) = emit_tacky_expression(expr);
+        let (tacky_instructions, val) = emit_tacky_expression(expr, &TackyOptions::default(), &mut NameGenerator::new());
 
         assert_eq!(tacky_instructions.len(), 0);
         assert_eq!(val, Val::Constant(2));
@@ -230,9 +452,9 @@ mod tests {
 
     #[test]
     fn convert_simple_unary_tacky_expression_test() {
-        let expr = AstExpression::Unary(AstUnaryOp::Negate, Box::new(AstExpression::Constant(2)));
+        let expr = AstExpression::Unary(AstUnaryOp::Negate, Box::new(AstExpression::Constant(2)), Position::default());
 
-        let (tacky_instructions, val) = emit_tacky_expression(expr);
+        let (tacky_instructions, val) = emit_tacky_expression(expr, &TackyOptions::default(), &mut NameGenerator::new());
 
         assert_eq!(tacky_instructions.len(), 1);
         assert_eq!(tacky_instructions[0], Instruction::Unary(UnaryOp::Negate, Val::Constant(2), val));
@@ -241,10 +463,10 @@ mod tests {
 
     #[test]
     fn convert_simple_binary_tacky_expression_test() {
-        let expr = AstExpression::Binary(AstBinaryOp::Divide, Box::new(AstExpression::Constant(2)), Box::new(AstExpression::Constant(1)));
+        let expr = AstExpression::Binary(AstBinaryOp::Divide, Box::new(AstExpression::Constant(2)), Box::new(AstExpression::Constant(1)), Position::default());
 
 
-        let (tacky_instructions, val) = emit_tacky_expression(expr);
+        let (tacky_instructions, val) = emit_tacky_expression(expr, &TackyOptions::default(), &mut NameGenerator::new());
 
 
         assert_eq!(tacky_instructions.len(), 1);
@@ -253,10 +475,138 @@ mod tests {
 
     #[test]
     fn convert_var_tacky_expression_test() {
-        let expr = AstExpression::Var("some_identifier".to_string());
+        let expr = AstExpression::Var("some_identifier".to_string(), Position::default());
 
-        let (tacky_instructions, val) = emit_tacky_expression(expr);
+        let (tacky_instructions, val) = emit_tacky_expression(expr, &TackyOptions::default(), &mut NameGenerator::new());
         assert_eq!(tacky_instructions.len(), 0);
         assert_eq!(val, Val::Var("some_identifier".to_string()));
     }
+
+    #[test]
+    fn convert_postfix_increment_tacky_expression_test() {
+        let expr = AstExpression::Increment(AstIncrementOp::PostIncrement, Box::new(AstExpression::Var("x.0".to_string(), Position::default())), Position::default());
+
+        let (tacky_instructions, val) = emit_tacky_expression(expr, &TackyOptions::default(), &mut NameGenerator::new());
+
+        let x = Val::Var("x.0".to_string());
+        assert_eq!(tacky_instructions, vec![
+            Instruction::Copy(x.clone(), val),
+            Instruction::Binary(BinaryOp::Add, x.clone(), Val::Constant(1), x),
+        ]);
+    }
+
+    #[test]
+    fn convert_inline_asm_tacky_statement_test() {
+        let statement = AstStatement::InlineAsm(
+            "addl %1, %0\n\tsubl %2, %0".to_string(),
+            vec![AstAsmOperand::Operand("+r".to_string(), AstExpression::Var("x.0".to_string(), Position::default()))],
+            vec![
+                AstAsmOperand::Operand("ri".to_string(), AstExpression::Constant(1)),
+                AstAsmOperand::Operand("m".to_string(), AstExpression::Constant(2)),
+            ],
+            vec!["cc".to_string()],
+            Position::default(),
+        );
+
+        let tacky_instructions = emit_tacky_statement(statement, &TackyOptions::default(), &mut NameGenerator::new());
+
+        // the memory-only constant is copied into a temporary, the other one stays an immediate
+        let tmp = match &tacky_instructions[0] {
+            Instruction::Copy(Val::Constant(2), tmp @ Val::Var(_)) => tmp.clone(),
+            other => panic!("expected a copy into a temporary, got {:?}", other),
+        };
+        assert_eq!(tacky_instructions[1], Instruction::InlineAsm(
+            "addl %1, %0\n\tsubl %2, %0".to_string(),
+            vec![("+r".to_string(), Val::Var("x.0".to_string()))],
+            vec![("ri".to_string(), Val::Constant(1)), ("m".to_string(), tmp)],
+            vec!["cc".to_string()],
+        ));
+    }
+
+    #[test]
+    fn convert_sanitized_tacky_expression_test() {
+        let options = TackyOptions { sanitize_undefined: true, source_file: "main.c".to_string() };
+        let x = || Box::new(AstExpression::Var("x.0".to_string(), Position::default()));
+
+        let expr = AstExpression::Binary(AstBinaryOp::Add, x(), Box::new(AstExpression::Constant(1)), Position::new(2, 14));
+        let (tacky_instructions, val) = emit_tacky_expression(expr, &options, &mut NameGenerator::new());
+
+        assert_eq!(tacky_instructions, vec![Instruction::CheckedBinary(
+            BinaryOp::Add,
+            Val::Var("x.0".to_string()),
+            Val::Constant(1),
+            val,
+            "main.c:2:14: runtime error: signed integer overflow".to_string(),
+        )]);
+
+        // a constant divisor other than 0 and -1 needs no checks at all
+        let expr = AstExpression::Binary(AstBinaryOp::Divide, x(), Box::new(AstExpression::Constant(2)), Position::new(3, 14));
+        let (tacky_instructions, val) = emit_tacky_expression(expr, &options, &mut NameGenerator::new());
+
+        assert_eq!(tacky_instructions, vec![Instruction::Binary(BinaryOp::Divide, Val::Var("x.0".to_string()), Val::Constant(2), val)]);
+
+        let expr = AstExpression::Binary(AstBinaryOp::Remainder, Box::new(AstExpression::Constant(7)), x(), Position::new(4, 14));
+        let (tacky_instructions, _) = emit_tacky_expression(expr, &options, &mut NameGenerator::new());
+        let traps = tacky_instructions
+            .iter()
+            .filter_map(|instruction| match instruction {
+                Instruction::Trap(error) => Some(error.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(traps, vec!["main.c:4:14: runtime error: division by zero"]);
+    }
+
+    #[test]
+    fn convert_conditions_to_jumps_test() {
+        let var = |name: &str| Box::new(AstExpression::Var(name.to_string(), Position::default()));
+        let tacky_var = |name: &str| Val::Var(name.to_string());
+        let binary = |bin_op, left, right| Box::new(AstExpression::Binary(bin_op, left, right, Position::default()));
+
+        // (a || b) && !(c < 1)
+        let expr = binary(
+            AstBinaryOp::And,
+            Box::new(AstExpression::Parenthesized(binary(AstBinaryOp::Or, var("a.0"), var("b.0")))),
+            Box::new(AstExpression::Unary(AstUnaryOp::Not, binary(AstBinaryOp::LessThan, var("c.0"), Box::new(AstExpression::Constant(1))), Position::default())),
+        );
+        let (tacky_instructions, val) = emit_tacky_expression(*expr, &TackyOptions::default(), &mut NameGenerator::new());
+
+        assert_eq!(tacky_instructions, vec![
+            Instruction::JumpIfNotZero(tacky_var("a.0"), "or_skip0".to_string()),
+            Instruction::JumpIfZero(tacky_var("b.0"), "and_false0".to_string()),
+            Instruction::Label("or_skip0".to_string()),
+            Instruction::JumpIf(BinaryOp::LessThan, tacky_var("c.0"), Val::Constant(1), "and_false0".to_string()),
+            Instruction::Copy(Val::Constant(1), val.clone()),
+            Instruction::Jump("and_false_end0".to_string()),
+            Instruction::Label("and_false0".to_string()),
+            Instruction::Copy(Val::Constant(0), val.clone()),
+            Instruction::Label("and_false_end0".to_string()),
+        ]);
+
+        // a negated comparison still gives its value directly
+        let expr = AstExpression::Unary(AstUnaryOp::Not, binary(AstBinaryOp::Equal, var("a.0"), var("b.0")), Position::default());
+        let (tacky_instructions, val) = emit_tacky_expression(expr, &TackyOptions::default(), &mut NameGenerator::new());
+
+        assert_eq!(tacky_instructions, vec![Instruction::Binary(BinaryOp::NotEqual, tacky_var("a.0"), tacky_var("b.0"), val)]);
+    }
+
+    #[test]
+    fn emit_tacky_names_are_deterministic() {
+        // int main(void) { return -1 && 2; }
+        let program = || AstProgram::Program(vec![], AstFunctionDefinition::Function("main".to_string(), vec![
+            AstBlockItem::Statement(AstStatement::Return(AstExpression::Binary(
+                AstBinaryOp::And,
+                Box::new(AstExpression::Unary(AstUnaryOp::Negate, Box::new(AstExpression::Constant(1)), Position::default())),
+                Box::new(AstExpression::Constant(2)),
+                Position::default(),
+            ), Position::default())),
+        ], Position::default()));
+
+        let first = emit_tacky(program());
+
+        assert_eq!(first, emit_tacky(program()));
+        assert!(format!("{:?}", first).contains("\"tmp.0\""));
+        assert!(format!("{:?}", first).contains("\"and_false0\""));
+    }
 }
@@ -1,21 +1,30 @@
 //! C token definitions.
 
 /// C tokens.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum Token {
     // Tokens with contents
     Identifier(String),
     Constant(i32),
+    StringLiteral(String),
     // Keywords
     Integer,
     Void,
     Return,
+    Typedef,
+    Enum,
+    Const,
+    StaticAssert,
+    Asm,
+    Volatile,
     // Punctuation
     OpenParen,
     CloseParen,
     OpenBrace,
     CloseBrace,
     Semicolon,
+    Comma,
+    Colon,
     Hyphen,
     DoubleHyphen,
     Tilde,
@@ -25,6 +34,11 @@ pub enum Token {
     ForwardSlash,
     Percent,
     Equal,
+    PlusEqual,
+    HyphenEqual,
+    AsteriskEqual,
+    ForwardSlashEqual,
+    PercentEqual,
     // Logical tokens
     LogicalNot,
     LogicalAnd,
@@ -3,6 +3,8 @@
 use regex::Regex;
 
 use super::token::Token;
+use crate::compiler::standard::Standard;
+use crate::storage::position::Position;
 
 struct TokenDef {
     // pattern: String,
@@ -28,6 +30,13 @@ fn convert_identifier(s: &str) -> Token {
         "int" => Token::Integer,
         "return" => Token::Return,
         "void" => Token::Void,
+        "typedef" => Token::Typedef,
+        "enum" => Token::Enum,
+        "const" => Token::Const,
+        "_Static_assert" => Token::StaticAssert,
+        // GNU extensions, accepted in every standard since there are no gnu modes
+        "asm" | "__asm__" => Token::Asm,
+        "volatile" | "__volatile__" => Token::Volatile,
         _ => Token::Identifier(s.to_string()),
     }
 }
@@ -36,16 +45,42 @@ fn convert_int(s: &str) -> Token {
     Token::Constant(s.parse().unwrap())
 }
 
+/// Strips the quotes and replaces escape sequences with the characters they stand for
+fn convert_string_literal(s: &str) -> Token {
+    let mut result = String::new();
+    let mut chars = s[1..s.len() - 1].chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('0') => result.push('\0'),
+            Some(other) => result.push(other), // \\, \", \' and \?
+            None => unreachable!(), // the pattern doesn't allow a trailing backslash
+        }
+    }
+
+    Token::StringLiteral(result)
+}
+
 /// Define token patterns and converters
 fn token_definitions() -> Vec<TokenDef> {
     vec![
         TokenDef::new(r"[A-Za-z_][A-Za-z0-9_]*\b", Box::new(convert_identifier)),
         TokenDef::new(r"[0-9]+\b", Box::new(convert_int)),
+        TokenDef::new(r#""([^"\\\n]|\\.)*""#, Box::new(convert_string_literal)),
         TokenDef::new(r"\(", Box::new(|_| Token::OpenParen)),
         TokenDef::new(r"\)", Box::new(|_| Token::CloseParen)),
         TokenDef::new(r"\{", Box::new(|_| Token::OpenBrace)),
         TokenDef::new(r"\}", Box::new(|_| Token::CloseBrace)),
         TokenDef::new(r";", Box::new(|_| Token::Semicolon)),
+        TokenDef::new(r",", Box::new(|_| Token::Comma)),
+        TokenDef::new(r":", Box::new(|_| Token::Colon)),
         TokenDef::new(r"-", Box::new(|_| Token::Hyphen)),
         TokenDef::new(r"--", Box::new(|_| Token::DoubleHyphen)),
         TokenDef::new(r"~", Box::new(|_| Token::Tilde)),
@@ -55,6 +90,11 @@ fn token_definitions() -> Vec<TokenDef> {
         TokenDef::new(r"/", Box::new(|_| Token::ForwardSlash)),
         TokenDef::new(r"%", Box::new(|_| Token::Percent)),
         TokenDef::new(r"=", Box::new(|_| Token::Equal)),
+        TokenDef::new(r"\+=", Box::new(|_| Token::PlusEqual)),
+        TokenDef::new(r"-=", Box::new(|_| Token::HyphenEqual)),
+        TokenDef::new(r"\*=", Box::new(|_| Token::AsteriskEqual)),
+        TokenDef::new(r"/=", Box::new(|_| Token::ForwardSlashEqual)),
+        TokenDef::new(r"%=", Box::new(|_| Token::PercentEqual)),
         TokenDef::new(r"!", Box::new(|_| Token::LogicalNot)),
         TokenDef::new(r"&&", Box::new(|_| Token::LogicalAnd)),
         TokenDef::new(r"\|\|", Box::new(|_| Token::LogicalOr)),
@@ -88,15 +128,66 @@ fn find_match(input: &str, token_def: &TokenDef) -> Option<(String, Token)> {
     }
 }
 
+/// Count the length of a comment at the start of the input, if there is one.
+///
+/// `//` comments only exist since C99, so in C89 mode they are an error instead.
+fn count_leading_comment(s: &str, standard: Standard) -> Result<Option<usize>, TokenizeError> {
+    if let Some(rest) = s.strip_prefix("/*") {
+        match rest.find("*/") {
+            Some(end) => Ok(Some(end + 4)),
+            None => Err(TokenizeError("Unterminated comment".to_string())),
+        }
+    } else if s.starts_with("//") {
+        if standard < Standard::C99 {
+            return Err(TokenizeError(standard.not_allowed("'//' comments")));
+        }
+
+        Ok(Some(s.find('\n').unwrap_or(s.len())))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Moves `position` past `text`
+fn advance_position(position: &mut Position, text: &str) {
+    for c in text.chars() {
+        if c == '\n' {
+            position.line += 1;
+            position.column = 1;
+        } else {
+            position.column += 1;
+        }
+    }
+}
+
 // Main lexing function
-pub fn tokenize(mut input: &str) -> Result<Vec<Token>, TokenizeError> {
+pub fn tokenize(input: &str) -> Result<Vec<Token>, TokenizeError> {
+    tokenize_with_positions(input, Standard::default()).map(|(tokens, _)| tokens)
+}
+
+/// Same as [`tokenize`], but also returns where every token starts.
+/// The two vectors always have the same length.
+pub fn tokenize_with_positions(mut input: &str, standard: Standard) -> Result<(Vec<Token>, Vec<Position>), TokenizeError> {
     let token_defs = token_definitions();
     let mut tokens = Vec::new();
+    let mut positions = Vec::new();
+    let mut position = Position::new(1, 1);
 
     while !input.is_empty() {
-        // skip whitespace
-        if let Some(ws_len) = count_leading_whitespace(input) {
-            input = &input[ws_len..];
+        // skip whitespace and comments
+        loop {
+            if let Some(ws_len) = count_leading_whitespace(input) {
+                advance_position(&mut position, &input[..ws_len]);
+                input = &input[ws_len..];
+            }
+
+            match count_leading_comment(input, standard)? {
+                Some(comment_len) => {
+                    advance_position(&mut position, &input[..comment_len]);
+                    input = &input[comment_len..];
+                }
+                None => break,
+            }
         }
 
         // if file ends with whitespaces we should break here
@@ -122,16 +213,20 @@ pub fn tokenize(mut input: &str) -> Result<Vec<Token>, TokenizeError> {
             .unwrap();
 
         tokens.push(token);
+        positions.push(position);
+        advance_position(&mut position, &longest_match);
         input = &input[longest_match.len()..];
     }
 
-    Ok(tokens)
+    Ok((tokens, positions))
 }
 
 #[cfg(test)]
 mod tests {
     use crate::compiler::token::Token;
-    use crate::compiler::tokenizer::{tokenize, TokenizeError};
+    use crate::compiler::standard::Standard;
+    use crate::compiler::tokenizer::{tokenize, tokenize_with_positions, TokenizeError};
+    use crate::storage::position::Position;
 
     #[test]
     fn tokenizer_invalid_at_sing() {
@@ -320,4 +415,143 @@ mod tests {
             ])
         );
     }
+
+    #[test]
+    fn tokenizer_valid_positions() {
+        let code = "int main(void) {\n    return x += 1;\n}";
+
+        let (tokens, positions) = tokenize_with_positions(code, Standard::C17).unwrap();
+
+        assert_eq!(tokens.len(), positions.len());
+        assert_eq!(tokens[6], Token::Return);
+        assert_eq!(positions[6], Position::new(2, 5));
+        assert_eq!(tokens[8], Token::PlusEqual);
+        assert_eq!(positions[8], Position::new(2, 14));
+        assert_eq!(positions[11], Position::new(3, 1));
+    }
+
+    #[test]
+    fn tokenizer_valid_const_and_compound_assignment() {
+        let code = r"const int *p; x -= 1; y *= 2; z /= 3; w %= 4; v++;";
+
+        assert_eq!(
+            tokenize(code),
+            Ok(vec![
+                Token::Const,
+                Token::Integer,
+                Token::Asterisk,
+                Token::Identifier("p".to_string()),
+                Token::Semicolon,
+                Token::Identifier("x".to_string()),
+                Token::HyphenEqual,
+                Token::Constant(1),
+                Token::Semicolon,
+                Token::Identifier("y".to_string()),
+                Token::AsteriskEqual,
+                Token::Constant(2),
+                Token::Semicolon,
+                Token::Identifier("z".to_string()),
+                Token::ForwardSlashEqual,
+                Token::Constant(3),
+                Token::Semicolon,
+                Token::Identifier("w".to_string()),
+                Token::PercentEqual,
+                Token::Constant(4),
+                Token::Semicolon,
+                Token::Identifier("v".to_string()),
+                Token::DoublePlus,
+                Token::Semicolon,
+            ])
+        );
+    }
+
+    #[test]
+    fn tokenizer_valid_comments() {
+        let code = "int /* block\n comment */ main // line comment\n(void)";
+
+        let (tokens, positions) = tokenize_with_positions(code, Standard::C99).unwrap();
+
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Integer,
+                Token::Identifier("main".to_string()),
+                Token::OpenParen,
+                Token::Void,
+                Token::CloseParen,
+            ]
+        );
+        assert_eq!(positions[1], Position::new(2, 13));
+        assert_eq!(positions[2], Position::new(3, 1));
+    }
+
+    #[test]
+    fn tokenizer_invalid_line_comment_in_c89() {
+        let code = "int main // line comment\n(void)";
+
+        assert_eq!(
+            tokenize_with_positions(code, Standard::C89),
+            Err(TokenizeError("'//' comments are not allowed in C89".to_string()))
+        );
+        assert!(tokenize_with_positions("int /* fine */ main", Standard::C89).is_ok());
+    }
+
+    #[test]
+    fn tokenizer_invalid_unterminated_comment() {
+        assert_eq!(
+            tokenize("int main /* never closed"),
+            Err(TokenizeError("Unterminated comment".to_string()))
+        );
+    }
+
+    #[test]
+    fn tokenizer_valid_string_literal() {
+        let code = r#"_Static_assert(1, "tab\there \"quoted\"\n");"#;
+
+        assert_eq!(
+            tokenize(code),
+            Ok(vec![
+                Token::StaticAssert,
+                Token::OpenParen,
+                Token::Constant(1),
+                Token::Comma,
+                Token::StringLiteral("tab\there \"quoted\"\n".to_string()),
+                Token::CloseParen,
+                Token::Semicolon,
+            ])
+        );
+    }
+
+    #[test]
+    fn tokenizer_valid_inline_asm() {
+        let code = r#"__asm__ volatile("incl %0" : "+r"(x) :: "cc");"#;
+
+        assert_eq!(
+            tokenize(code),
+            Ok(vec![
+                Token::Asm,
+                Token::Volatile,
+                Token::OpenParen,
+                Token::StringLiteral("incl %0".to_string()),
+                Token::Colon,
+                Token::StringLiteral("+r".to_string()),
+                Token::OpenParen,
+                Token::Identifier("x".to_string()),
+                Token::CloseParen,
+                Token::Colon,
+                Token::Colon,
+                Token::StringLiteral("cc".to_string()),
+                Token::CloseParen,
+                Token::Semicolon,
+            ])
+        );
+    }
+
+    #[test]
+    fn tokenizer_invalid_unterminated_string_literal() {
+        assert_eq!(
+            tokenize("\"no end\n\""),
+            Err(TokenizeError("Unable to find match".to_string()))
+        );
+    }
 }
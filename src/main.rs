@@ -3,20 +3,26 @@ mod compiler;
 mod storage;
 mod ui;
 
-use crate::cli::setting::Settings;
-use crate::compiler::assembly::instruction_fixup::fixup_program;
-use crate::compiler::assembly::replace_pseudos::replace_pseudos;
+use crate::cli::setting::{normalize_args, Feature, Settings};
+use crate::compiler::cfg::Cfg;
+use crate::compiler::driver::{compile, generate_assembly, CompilerOptions};
 use crate::compiler::emit::emit_assembly;
-use crate::compiler::parser::parse_program;
-use crate::compiler::tackygen::emit_tacky;
-use crate::compiler::tokenizer::tokenize;
+use crate::compiler::optimization::range_analysis::variable_ranges;
+use crate::compiler::optimization::OptimizationOptions;
+use crate::compiler::parser::{parse_program, parse_program_with_context, ParserContext};
+use crate::compiler::tackygen::{emit_tacky, emit_tacky_with_options, TackyOptions};
+use crate::compiler::tokenizer::{tokenize, tokenize_with_positions};
 use crate::ui::ast_visualizer::run_ui;
-use compiler::assembly::codegen::gen;
 use std::fs;
 use std::path::PathBuf;
 use std::process::Command;
 use structopt::StructOpt;
+use crate::compiler::semantics::diagnostics::format_error;
+use crate::compiler::semantics::lint::{format_lint, lint_program_with_ranges};
 use crate::compiler::semantics::variable_resolution::resolve_program;
+use crate::compiler::semantics::warnings::{check_program, format_warning, WarningSettings};
+use crate::compiler::standard::Standard;
+use crate::storage::tacky::Program;
 
 pub fn main() -> iced::Result {
     run_ui()
@@ -49,17 +55,18 @@ fn debug_config() {
 }
 
 fn production_config() {
-    let mut options = Settings::from_args();
+    let mut options = Settings::from_iter(normalize_args(std::env::args()));
+    let source_file = options.file_path.display().to_string();
 
-    call_gcc_preprocessor(&mut options.file_path);
+    call_gcc_preprocessor(&mut options.file_path, options.standard);
 
     let source_code = fs::read_to_string(&options.file_path).unwrap();
     // println!("{}", source_code);
 
-    let mut tokens = match tokenize(source_code.as_str()) {
-        Ok(tokens) => {
+    let (mut tokens, positions) = match tokenize_with_positions(source_code.as_str(), options.standard) {
+        Ok((tokens, positions)) => {
             println!("Tokens {:?}", tokens);
-            tokens
+            (tokens, positions)
         }
         Err(err) => panic!("{:?}", err),
     };
@@ -68,7 +75,7 @@ fn production_config() {
         return;
     }
 
-    let ast = match parse_program(&mut tokens) {
+    let ast = match parse_program_with_context(&mut tokens, ParserContext::new(positions, options.standard)) {
         Ok(ast) => {
             println!("AST:\n{:?}", ast);
             ast
@@ -80,39 +87,100 @@ fn production_config() {
         return;
     }
 
-    let ast = match resolve_program(ast) {
-        Ok(ast) => {
+    let (ast, symbols) = match resolve_program(ast, options.standard) {
+        Ok((ast, symbols)) => {
             println!("AST:\n{:?}", ast);
-            ast
+            (ast, symbols)
+        }
+        Err(errors) => {
+            for error in &errors {
+                eprintln!("{}", format_error(&source_file, error));
+            }
+
+            remove_preprocessed_file(&options.file_path);
+            std::process::exit(1);
         }
-        Err(err) => panic!("{:?}", err),
     };
 
+    let warning_settings = WarningSettings::from_flags(&options.warnings);
+    let warnings = check_program(&ast, &symbols, options.standard, &warning_settings);
+
+    for warning in &warnings {
+        eprintln!("{}", format_warning(&source_file, warning, &warning_settings));
+    }
+
+    if warnings.iter().any(|warning| warning_settings.is_error(warning.kind())) {
+        remove_preprocessed_file(&options.file_path);
+        std::process::exit(1);
+    }
+
+    if options.lint {
+        let ranges = match emit_tacky_with_options(ast.clone(), &symbols, &TackyOptions::default()) {
+            Program::Program(function) => variable_ranges(&function),
+        };
+        let lints = lint_program_with_ranges(&ast, &symbols, &ranges);
+
+        for lint in &lints {
+            eprintln!("{}", format_lint(&source_file, lint));
+        }
+
+        remove_preprocessed_file(&options.file_path);
+        std::process::exit(if lints.is_empty() { 0 } else { 1 });
+    }
+
     if options.validate {
         return;
     }
 
-    let tacky_ast = emit_tacky(ast);
-    println!("TACKY AST:\n{:?}", tacky_ast);
+    let mut optimization = OptimizationOptions::for_level(options.optimization_level);
+    optimization.fold_constants |= options.fold_constants;
+    optimization.simplify_algebra |= options.simplify_algebra;
+    optimization.propagate_constants |= options.propagate_constants;
+    optimization.fold_ranges |= options.fold_ranges;
+    optimization.eliminate_unreachable_code |= options.eliminate_unreachable_code;
+    optimization.propagate_copies |= options.propagate_copies;
+    optimization.eliminate_common_subexpressions |= options.eliminate_common_subexpressions;
+    optimization.eliminate_dead_stores |= options.eliminate_dead_stores;
+    optimization.reduce_strength |= options.reduce_strength;
+    optimization.allocate_registers |= options.allocate_registers;
+
+    let compiler_options = CompilerOptions {
+        tacky: TackyOptions {
+            sanitize_undefined: options.features.contains(&Feature::SanitizeUndefined),
+            source_file: source_file.clone(),
+        },
+        optimization,
+    };
+    let output = compile(ast, &symbols, &compiler_options);
+    println!("TACKY AST:\n{:?}", output.tacky);
+    println!("Optimization statistics:\n{}", output.optimization_statistics);
+
+    if options.dump_cfg {
+        let cfg = match &output.tacky {
+            Program::Program(function) => Cfg::from_function(function.clone()),
+        };
+
+        options.file_path.set_extension("dot");
+        fs::write(&options.file_path, cfg.to_dot()).unwrap();
+        options.file_path.set_extension("i");
+    }
 
     if options.tacky {
         return;
     }
 
-    let codegen_ast = gen(tacky_ast);
-    // println!("Codegen AST:\n{:?}", codegen_ast);
-
-    let replace_pseudos_ast = replace_pseudos(codegen_ast);
-    // println!("Replace pseudos AST:\n{:?}", replace_pseudos_ast);
+    let output = generate_assembly(output.tacky, &compiler_options);
+    if options.stack_report {
+        println!("Stack frame:\n{}", output.stack_frame);
+    }
 
-    let fixup_ast = fixup_program(replace_pseudos_ast.1, replace_pseudos_ast.0);
-    println!("Replace pseudos AST:\n{:?}", fixup_ast);
+    println!("Replace pseudos AST:\n{:?}", output.assembly);
 
     if options.codegen {
         return;
     }
 
-    let assembly_source_code = emit_assembly(fixup_ast);
+    let assembly_source_code = emit_assembly(output.assembly);
     options.file_path.set_extension("s");
     fs::write(&options.file_path, &assembly_source_code).unwrap();
 
@@ -127,7 +195,7 @@ fn production_config() {
     call_gcc_assembler_and_linker(&mut options.file_path)
 }
 
-fn call_gcc_preprocessor(file_path: &mut PathBuf) {
+fn call_gcc_preprocessor(file_path: &mut PathBuf, standard: Standard) {
     let binding = file_path.clone();
     let original_file_path = match binding.to_str() {
         Some(file_path) => file_path,
@@ -139,6 +207,8 @@ fn call_gcc_preprocessor(file_path: &mut PathBuf) {
     let preprocessor_command = Command::new("gcc")
         .arg("-E")
         .arg("-P")
+        // the preprocessor strips comments, so it has to enforce the standard for them
+        .arg(format!("-std={}", standard.to_string().to_lowercase()))
         .arg(original_file_path)
         .arg("-o")
         .arg(file_path)
@@ -15,13 +15,19 @@ pub enum AssemblyInstruction {
     Binary(AssemblyBinaryOp, AssemblyOperand, AssemblyOperand),
     Cmp(AssemblyOperand, AssemblyOperand),
     Idiv(AssemblyOperand),
+    Imul(AssemblyOperand), // one-operand signed multiply of AX, the 64-bit product goes to DX:AX
     Cdq,
     Jmp(String), // Jmp(identifier)
     JmpCC(AssemblyCondition, String), // identifier
     SetCC(AssemblyCondition, AssemblyOperand),
     Label(String),
     AllocateStack(i32),
-    Ret
+    Ret,
+    InlineAsm(String, Vec<(String, AssemblyOperand)>, Vec<(String, AssemblyOperand)>, Vec<String>), // InlineAsm(template, outputs, inputs, clobbers)
+    Trap(String), // Trap(runtime error), prints the error and exits
+    TrapCC(AssemblyCondition, String), // TrapCC(condition, runtime error), traps if the condition holds
+    Push(AssemblyRegister), // saves the whole 64-bit register
+    Pop(AssemblyRegister),
 }
 
 #[derive(Debug, PartialEq)]
@@ -34,10 +40,13 @@ pub enum AssemblyUnaryOp {
 pub enum AssemblyBinaryOp {
     Add,
     Sub,
-    Mult
+    Mult,
+    Sal, // shifts take the count as the source
+    Sar,
+    Shr,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Clone)]
 pub enum AssemblyOperand {
     Imm(i32),
     Reg(AssemblyRegister),
@@ -53,12 +62,53 @@ pub enum AssemblyCondition {
     GE, // Greater or equal
     L, // Less
     LE, // Less or equal
+    O, // Overflow
 }
 
-#[derive(Debug, PartialEq)]
+/// The general-purpose registers, apart from `rsp` and `rbp` which hold the stack frame.
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy)]
 pub enum AssemblyRegister {
     AX,
+    BX,
+    CX,
     DX,
+    SI,
+    DI,
+    R8,
+    R9,
     R10,
-    R11
-}
\ No newline at end of file
+    R11,
+    R12,
+    R13,
+    R14,
+    R15,
+}
+
+impl AssemblyRegister {
+    /// The registers a function has to restore before it returns.
+    pub const CALLEE_SAVED: [AssemblyRegister; 5] =
+        [AssemblyRegister::BX, AssemblyRegister::R12, AssemblyRegister::R13, AssemblyRegister::R14, AssemblyRegister::R15];
+
+    /// Looks up a register by any of its names, the way inline asm clobber lists spell them.
+    ///
+    /// `rsp` and `rbp` aren't known, inline asm can't take the stack frame away.
+    pub fn from_name(name: &str) -> Option<AssemblyRegister> {
+        match name.trim_start_matches('%') {
+            "rax" | "eax" | "ax" | "al" => Some(AssemblyRegister::AX),
+            "rbx" | "ebx" | "bx" | "bl" => Some(AssemblyRegister::BX),
+            "rcx" | "ecx" | "cx" | "cl" => Some(AssemblyRegister::CX),
+            "rdx" | "edx" | "dx" | "dl" => Some(AssemblyRegister::DX),
+            "rsi" | "esi" | "si" | "sil" => Some(AssemblyRegister::SI),
+            "rdi" | "edi" | "di" | "dil" => Some(AssemblyRegister::DI),
+            "r8" | "r8d" | "r8w" | "r8b" => Some(AssemblyRegister::R8),
+            "r9" | "r9d" | "r9w" | "r9b" => Some(AssemblyRegister::R9),
+            "r10" | "r10d" | "r10w" | "r10b" => Some(AssemblyRegister::R10),
+            "r11" | "r11d" | "r11w" | "r11b" => Some(AssemblyRegister::R11),
+            "r12" | "r12d" | "r12w" | "r12b" => Some(AssemblyRegister::R12),
+            "r13" | "r13d" | "r13w" | "r13b" => Some(AssemblyRegister::R13),
+            "r14" | "r14d" | "r14w" | "r14b" => Some(AssemblyRegister::R14),
+            "r15" | "r15d" | "r15w" | "r15b" => Some(AssemblyRegister::R15),
+            _ => None,
+        }
+    }
+}
@@ -13,51 +13,108 @@
 //! <int> ::= ? A constant token ?
 //! ```
 
-#[derive(Debug, PartialEq)]
+use crate::storage::position::Position;
+
+/// ```<program> ::= { <declaration> } <function> { <declaration> }```
+#[derive(Debug, PartialEq, Clone)]
 pub enum AstProgram {
-    Program(AstFunctionDefinition)
+    Program(Vec<AstDeclaration>, AstFunctionDefinition) // Program(file scope declarations, function)
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum AstFunctionDefinition {
-    Function(String, Vec<AstBlockItem>)
+    Function(String, Vec<AstBlockItem>, Position) // Function(identifier, body, position)
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum AstBlockItem {
     Statement(AstStatement),
     Declaration(AstDeclaration)
 }
 
-#[derive(Debug, PartialEq)]
+/// ```
+/// <declaration> ::= <type> <identifier> [ "=" <exp> ] ";"
+///                 | "typedef" <type> <identifier> ";"
+///                 | "enum" [ <identifier> ] "{" <enumerator> { "," <enumerator> } [ "," ] "}" ";"
+///                 | "_Static_assert" "(" <exp> "," <string> ")" ";"
+/// ```
+/// Only `_Static_assert` is allowed at file scope.
+#[derive(Debug, PartialEq, Clone)]
 pub enum AstDeclaration {
-    Declaration(String, Option<AstExpression>)
+    Declaration(String, AstType, Option<AstExpression>, Position), // Declaration(identifier, type, init, position)
+    Typedef(String, AstType, Position), // Typedef(identifier, type, position)
+    Enum(Option<String>, Vec<AstEnumerator>, Position), // Enum(tag, enumerators, position)
+    StaticAssert(AstExpression, String, Position), // StaticAssert(condition, message, position)
+}
+
+/// ```<enumerator> ::= <identifier> [ "=" <exp> ]```
+#[derive(Debug, PartialEq, Clone)]
+pub enum AstEnumerator {
+    Enumerator(String, Option<AstExpression>, Position) // Enumerator(identifier, value, position)
+}
+
+/// ```
+/// <type> ::= { "const" } <specifier> { "const" } { "*" { "const" } }
+/// <specifier> ::= "int" | "enum" <identifier> | <typedef-name>
+/// ```
+#[derive(Debug, PartialEq, Clone)]
+pub enum AstType {
+    Int,
+    Enum(String), // Enum(tag)
+    Typedef(String), // Typedef(identifier), replaced by the aliased type during semantic analysis
+    Pointer(Box<AstType>), // Pointer(referenced type)
+    Const(Box<AstType>), // Const(qualified type), `const int *` is Pointer(Const(Int))
 }
 
-#[derive(Debug, PartialEq)]
+/// ```
+/// <statement> ::= "return" <exp> ";"
+///               | <exp> ";"
+///               | <asm>
+///               | ";"
+/// ```
+#[derive(Debug, PartialEq, Clone)]
 pub enum AstStatement {
-    Return(AstExpression),
-    Expression(AstExpression),
+    Return(AstExpression, Position), // Return(value, position)
+    Expression(AstExpression, Position), // Expression(expression, position)
+    InlineAsm(String, Vec<AstAsmOperand>, Vec<AstAsmOperand>, Vec<String>, Position), // InlineAsm(template, outputs, inputs, clobbers, position)
     Null
 }
 
-#[derive(Debug, PartialEq)]
+/// ```<operand> ::= <string> "(" <exp> ")"```
+/// Outputs are numbered before inputs when the template refers to them as `%0`, `%1`, ...
+#[derive(Debug, PartialEq, Clone)]
+pub enum AstAsmOperand {
+    Operand(String, AstExpression) // Operand(constraint, expression)
+}
+
+#[derive(Debug, PartialEq, Clone)]
 pub enum AstExpression {
     Constant(i32),
-    Var(String), // Var(identifier)
-    Unary(AstUnaryOp, Box<AstExpression>),
-    Binary(AstBinaryOp, Box<AstExpression>, Box<AstExpression>),
-    Assignment(Box<AstExpression>, Box<AstExpression>),
+    Var(String, Position), // Var(identifier, position)
+    Unary(AstUnaryOp, Box<AstExpression>, Position), // Unary(operator, expr, operator position)
+    Binary(AstBinaryOp, Box<AstExpression>, Box<AstExpression>, Position), // Binary(operator, left, right, operator position)
+    Assignment(Box<AstExpression>, Box<AstExpression>, Position), // Assignment(lvalue, expr, position)
+    CompoundAssignment(AstBinaryOp, Box<AstExpression>, Box<AstExpression>, Position), // CompoundAssignment(operator, lvalue, expr, position)
+    Increment(AstIncrementOp, Box<AstExpression>, Position), // Increment(operator, lvalue, position)
+    Parenthesized(Box<AstExpression>), // Parenthesized(expression), kept so lint can tell `(!a) == b` from `!a == b`
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum AstUnaryOp{
     Complement,
     Negate,
     Not
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+pub enum AstIncrementOp {
+    PreIncrement,
+    PreDecrement,
+    PostIncrement,
+    PostDecrement,
+}
+
+#[derive(Debug, PartialEq, Clone)]
 pub enum AstBinaryOp{
     Add,
     Subtract,
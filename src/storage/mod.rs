@@ -1,3 +1,5 @@
 pub mod ast;
 pub mod assembly;
-pub mod tacky;
\ No newline at end of file
+pub mod tacky;
+pub mod position;
+pub mod symbol_table;
@@ -0,0 +1,69 @@
+//! Everything semantic analysis found out about the program's identifiers.
+//!
+//! Resolution renames variables to unique names (`x` becomes `x.0`), the table maps those
+//! names back to the declaration they came from, so later passes and the UI don't have to
+//! take the names apart.
+
+use std::collections::HashMap;
+use crate::storage::ast::AstType;
+use crate::storage::position::Position;
+
+/// How a symbol exists while the program runs.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum StorageClass {
+    Automatic, // a local variable
+    Typedef, // only a name for a type
+    Constant(i32), // Constant(value), an enumerator
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Symbol {
+    /// The name in the source.
+    pub name: String,
+    /// The name after resolution, the same as `name` for symbols that aren't renamed.
+    pub unique_name: String,
+    pub symbol_type: AstType,
+    pub storage_class: StorageClass,
+    /// 0 for file scope, 1 for the function body.
+    pub scope_depth: usize,
+    pub position: Position,
+}
+
+/// The symbols of a program, in the order they were declared.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct SymbolTable {
+    symbols: Vec<Symbol>,
+    indices: HashMap<String, usize>,
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        SymbolTable { symbols: vec![], indices: HashMap::new() }
+    }
+
+    pub fn insert(&mut self, symbol: Symbol) {
+        self.indices.insert(symbol.unique_name.clone(), self.symbols.len());
+        self.symbols.push(symbol);
+    }
+
+    /// Adds every symbol of `other` after the ones already in the table.
+    pub fn append(&mut self, other: SymbolTable) {
+        for symbol in other.symbols {
+            self.insert(symbol);
+        }
+    }
+
+    /// Looks up a symbol by its unique name, the one the resolved AST uses.
+    pub fn get(&self, unique_name: &str) -> Option<&Symbol> {
+        self.indices.get(unique_name).map(|&index| &self.symbols[index])
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Symbol> {
+        self.symbols.iter()
+    }
+
+    /// The variables, the symbols that have storage while the program runs.
+    pub fn variables(&self) -> impl Iterator<Item = &Symbol> {
+        self.symbols.iter().filter(|symbol| symbol.storage_class == StorageClass::Automatic)
+    }
+}
@@ -32,8 +32,17 @@ pub enum FunctionDefinition {
 ///             | Jump(identifier target)
 ///             | JumpIfZero(val condition, identifier target)
 ///             | JumpIfNotZero(val condition, identifier target)
+///             | JumpIf(binary_operator comparison, val src1, val src2, identifier target)
 ///             | Label(identifier)
+///             | InlineAsm(string template, (string constraint, val)* outputs,
+///                         (string constraint, val)* inputs, string* clobbers)
+///             | CheckedUnary(unary_operator, val src, val dst, string error)
+///             | CheckedBinary(binary_operator, val src1, val src2, val dst, string error)
+///             | Trap(string error)
 /// ```
+/// The checked operations and `Trap` come from `-fsanitize=undefined`, they stop the program
+/// with `error` when the operation overflows or right away. `JumpIf` jumps when
+/// `src1 comparison src2` holds, it comes from conditions like the operands of `&&`.
 /// `dst` should be Val::Var
 #[derive(Debug, PartialEq, Clone)]
 pub enum Instruction {
@@ -44,18 +53,23 @@ pub enum Instruction {
     Jump(String),
     JumpIfZero(Val, String),
     JumpIfNotZero(Val, String),
-    Label(String)
+    JumpIf(BinaryOp, Val, Val, String), // JumpIf(comparison, src1, src2, target)
+    Label(String),
+    InlineAsm(String, Vec<(String, Val)>, Vec<(String, Val)>, Vec<String>), // outputs are always Val::Var
+    CheckedUnary(UnaryOp, Val, Val, String), // CheckedUnary(operator, src, dst, runtime error)
+    CheckedBinary(BinaryOp, Val, Val, Val, String), // CheckedBinary(operator, src1, src2, dst, runtime error)
+    Trap(String), // Trap(runtime error)
 }
 
 /// ```val = Constant(int) | Var(identifier)```
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Clone)]
 pub enum Val {
     Constant(i32),
     Var(String)
 }
 
 /// ```unary_operator = Complement | Negate```
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum UnaryOp {
     Complement,
     Negate,
@@ -63,7 +77,7 @@ pub enum UnaryOp {
 }
 
 /// ```binary_operator = Add | Subtract | Multiply | Divide | Remainder```
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum BinaryOp {
     Add,
     Subtract,
@@ -1,12 +1,12 @@
 use std::collections::HashMap;
-use std::sync::atomic::{AtomicUsize, Ordering};
 use iced::{mouse, widget, Color, Event, Pixels, Point, Rectangle, Size, Theme, Vector};
 use iced::alignment::{Horizontal, Vertical};
 use iced::mouse::Cursor;
 use iced::widget::canvas;
 use iced::widget::canvas::{Frame, Geometry, Path, Program, Stroke, Style, Text};
 use reingold_tilford::Dimensions;
-use crate::storage::ast::{AstBinaryOp, AstBlockItem, AstDeclaration, AstExpression, AstFunctionDefinition, AstProgram, AstStatement, AstUnaryOp};
+use crate::storage::ast::{AstAsmOperand, AstBinaryOp, AstBlockItem, AstDeclaration, AstEnumerator, AstExpression, AstFunctionDefinition, AstProgram, AstStatement, AstType, AstUnaryOp};
+use crate::storage::symbol_table::{StorageClass, SymbolTable};
 use crate::ui::ast_visualizer::Message;
 
 pub enum Interaction {
@@ -278,9 +278,9 @@ pub struct Node {
 impl Node {
     fn new(value: String) -> Self {
         let width = 10.0 + 7.0 * value.len() as f32;
-        static VAR_COUNTER: AtomicUsize = AtomicUsize::new(0);
         Self {
-            id: VAR_COUNTER.fetch_add(1, Ordering::Relaxed),
+            // numbered by `number_nodes` once the tree is complete
+            id: 0,
             value,
             children: vec![],
             width,
@@ -309,21 +309,59 @@ impl<'n> reingold_tilford::NodeInfo<&'n Node> for Tree {
     }
 }
 
-pub(crate) fn convert_into_ast_canvas(ast: &AstProgram) -> Node {
-    let mut root = Node::new("Program(function_definition)".to_string());
+pub(crate) fn convert_into_ast_canvas(ast: &AstProgram, symbols: &SymbolTable) -> Node {
+    let mut root = Node::new("Program(declarations, function_definition)".to_string());
+    root.children.push(convert_symbol_table(symbols));
 
     match ast {
-        AstProgram::Program(function) => {
+        AstProgram::Program(declarations, function) => {
+            for declaration in declarations {
+                root.children.push(convert_ast_declaration(declaration))
+            }
+
             root.children.push(convert_ast_function(function))
         }
     }
 
+    number_nodes(&mut root, &mut 0);
+
     root
 }
 
+/// Gives every node of the tree a distinct id in pre-order, so the layout can tell them apart
+/// and converting the same program always gives the same ids.
+fn number_nodes(node: &mut Node, next_id: &mut usize) {
+    node.id = *next_id;
+    *next_id += 1;
+
+    for child in &mut node.children {
+        number_nodes(child, next_id);
+    }
+}
+
+fn convert_symbol_table(symbols: &SymbolTable) -> Node {
+    let mut table = Node::new("SymbolTable(symbols)".to_string());
+
+    for symbol in symbols.iter() {
+        let storage_class = match symbol.storage_class {
+            StorageClass::Automatic => "auto".to_string(),
+            StorageClass::Typedef => "typedef".to_string(),
+            StorageClass::Constant(value) => format!("constant {}", value),
+        };
+
+        table.children.push(Node::new(format!(
+            "Symbol('{}', '{}', {}, {}, depth {}, {})",
+            symbol.name, symbol.unique_name, convert_ast_type(&symbol.symbol_type),
+            storage_class, symbol.scope_depth, symbol.position
+        )));
+    }
+
+    table
+}
+
 fn convert_ast_function(ast_function: &AstFunctionDefinition) -> Node {
     match ast_function {
-        AstFunctionDefinition::Function(identifier, body) => {
+        AstFunctionDefinition::Function(identifier, body, _) => {
             let mut function = Node::new(format!("Function('{}', body)", identifier));
 
             for block_item in body {
@@ -344,37 +382,100 @@ fn convert_ast_function(ast_function: &AstFunctionDefinition) -> Node {
 
 fn convert_ast_declaration(ast_declaration: &AstDeclaration) -> Node {
     match ast_declaration {
-        AstDeclaration::Declaration(identifier, expression) => {
+        AstDeclaration::Declaration(identifier, declaration_type, expression, _) => {
             match expression {
                 Some(expr) => {
-                    let mut declaration = Node::new(format!("{}=exp", identifier));
+                    let mut declaration = Node::new(format!("{} {}=exp", convert_ast_type(declaration_type), identifier));
                     declaration.children.push(convert_ast_expression(expr));
 
                     declaration
                 },
                 None => {
-                    let declaration = Node::new(format!("{}", identifier));
+                    let declaration = Node::new(format!("{} {}", convert_ast_type(declaration_type), identifier));
                     declaration
                 }
             }
         }
+        AstDeclaration::Typedef(identifier, aliased_type, _) => {
+            Node::new(format!("Typedef('{}', {})", identifier, convert_ast_type(aliased_type)))
+        }
+        AstDeclaration::Enum(tag, enumerators, _) => {
+            let mut enum_node = match tag {
+                Some(tag) => Node::new(format!("Enum('{}', enumerators)", tag)),
+                None => Node::new("Enum(enumerators)".to_string()),
+            };
+
+            for enumerator in enumerators {
+                match enumerator {
+                    AstEnumerator::Enumerator(identifier, Some(value), _) => {
+                        let mut enumerator_node = Node::new(format!("Enumerator('{}', exp)", identifier));
+                        enumerator_node.children.push(convert_ast_expression(value));
+
+                        enum_node.children.push(enumerator_node);
+                    }
+                    AstEnumerator::Enumerator(identifier, None, _) => {
+                        enum_node.children.push(Node::new(format!("Enumerator('{}')", identifier)));
+                    }
+                }
+            }
+
+            enum_node
+        }
+        AstDeclaration::StaticAssert(condition, message, _) => {
+            let mut static_assert = Node::new(format!("StaticAssert(exp, {:?})", message));
+            static_assert.children.push(convert_ast_expression(condition));
+
+            static_assert
+        }
+    }
+}
+
+fn convert_ast_type(ast_type: &AstType) -> String {
+    match ast_type {
+        AstType::Int => "int".to_string(),
+        AstType::Enum(tag) => format!("enum {}", tag),
+        AstType::Typedef(identifier) => identifier.clone(),
+        AstType::Pointer(referenced) => format!("{} *", convert_ast_type(referenced)),
+        AstType::Const(qualified) => match &**qualified {
+            AstType::Pointer(_) => format!("{} const", convert_ast_type(qualified)),
+            _ => format!("const {}", convert_ast_type(qualified)),
+        },
     }
 }
 
 fn convert_ast_statement(ast_statement: &AstStatement) -> Node {
     match ast_statement {
-        AstStatement::Return(expr) => {
+        AstStatement::Return(expr, _) => {
             let mut statement = Node::new("Return(exp)".to_string());
             statement.children.push(convert_ast_expression(expr));
 
             statement
         },
-        AstStatement::Expression(expr) => {
+        AstStatement::Expression(expr, _) => {
             let mut expression = Node::new("ExprStatement(exp)".to_string());
             expression.children.push(convert_ast_expression(expr));
 
             expression
         },
+        AstStatement::InlineAsm(template, outputs, inputs, clobbers, _) => {
+            let mut inline_asm = Node::new(format!("InlineAsm({:?}, clobbers={:?})", template, clobbers));
+
+            for AstAsmOperand::Operand(constraint, expr) in outputs {
+                let mut output = Node::new(format!("Output({:?}, exp)", constraint));
+                output.children.push(convert_ast_expression(expr));
+
+                inline_asm.children.push(output);
+            }
+
+            for AstAsmOperand::Operand(constraint, expr) in inputs {
+                let mut input = Node::new(format!("Input({:?}, exp)", constraint));
+                input.children.push(convert_ast_expression(expr));
+
+                inline_asm.children.push(input);
+            }
+
+            inline_asm
+        }
         AstStatement::Null => {
             Node::new("Null".to_string())
         }
@@ -386,7 +487,7 @@ fn convert_ast_expression(ast_expression: &AstExpression) -> Node {
         AstExpression::Constant(num) => {
             Node::new(format!("Constant({})", num))
         }
-        AstExpression::Binary(operator, left, right) => {
+        AstExpression::Binary(operator, left, right, _) => {
             let mut binary_node = Node::new("Binary(operator, left, right)".to_string());
             binary_node.children.push(convert_ast_expression(left));
 
@@ -411,7 +512,7 @@ fn convert_ast_expression(ast_expression: &AstExpression) -> Node {
 
             binary_node
         }
-        AstExpression::Unary(operator, expr) => {
+        AstExpression::Unary(operator, expr, _) => {
             let mut unary_node = Node::new("Unary(operator, expr)".into());
 
             let operator_node = match operator {
@@ -425,15 +526,34 @@ fn convert_ast_expression(ast_expression: &AstExpression) -> Node {
 
             unary_node
         },
-        AstExpression::Var(identifier) => {
+        AstExpression::Var(identifier, _) => {
             Node::new(format!("Var({})", identifier))
         },
-        AstExpression::Assignment(identifier, expression) => {
+        AstExpression::Assignment(identifier, expression, _) => {
             let mut assignment = Node::new(format!("Assignment(ident, expr)"));
             assignment.children.push(convert_ast_expression(&*identifier));
             assignment.children.push(convert_ast_expression(&*expression));
 
             assignment
         }
+        AstExpression::CompoundAssignment(operator, identifier, expression, _) => {
+            let mut assignment = Node::new(format!("CompoundAssignment({:?}, ident, expr)", operator));
+            assignment.children.push(convert_ast_expression(&*identifier));
+            assignment.children.push(convert_ast_expression(&*expression));
+
+            assignment
+        }
+        AstExpression::Increment(operator, identifier, _) => {
+            let mut increment = Node::new(format!("{:?}(ident)", operator));
+            increment.children.push(convert_ast_expression(&*identifier));
+
+            increment
+        }
+        AstExpression::Parenthesized(expression) => {
+            let mut parenthesized = Node::new("Parenthesized(expr)".to_string());
+            parenthesized.children.push(convert_ast_expression(&*expression));
+
+            parenthesized
+        }
     }
 }
\ No newline at end of file
@@ -3,18 +3,17 @@ use std::sync::Arc;
 use iced::{highlighter, widget, Center, Element, Fill, Font, Task, Theme, Vector};
 use iced::widget::{container, horizontal_space, pane_grid, pick_list, responsive, text, text_editor, toggler, PaneGrid};
 use iced::widget::text_editor::{Action, Edit};
-use crate::compiler::assembly::codegen::gen;
-use crate::compiler::assembly::instruction_fixup::fixup_program;
-use crate::compiler::assembly::replace_pseudos::replace_pseudos;
+use crate::compiler::driver::{compile, generate_assembly, CompilerOptions};
 use crate::compiler::emit::emit_assembly;
-use crate::compiler::parser::parse_program;
+use crate::compiler::parser::{parse_program_with_context, ParserContext};
+use crate::compiler::semantics::diagnostics::format_error;
 use crate::compiler::semantics::variable_resolution::resolve_program;
-use crate::compiler::tackygen::emit_tacky;
-use crate::compiler::tokenizer::tokenize;
+use crate::compiler::standard::Standard;
+use crate::compiler::tokenizer::tokenize_with_positions;
 use crate::ui::{style};
 use crate::ui::ast_canvas_converter::{convert_into_ast_canvas, ASTCanvas, Tree};
 use crate::ui::utils::{open_file, save_file, Error};
-use crate::ui::views::{action, view_canvas, view_editor, view_loader};
+use crate::ui::views::{action, view_canvas, view_editor, view_errors, view_loader};
 
 pub(crate) fn run_ui() -> iced::Result {
     iced::application("AST Visualizer", ASTVisualizer::update, ASTVisualizer::view)
@@ -38,6 +37,8 @@ struct ASTVisualizer {
 
     is_ast_valid: bool,
     ast: ASTCanvas,
+    /// Semantic errors of the current text, shown instead of the AST
+    errors: Vec<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -86,6 +87,7 @@ impl ASTVisualizer {
                     scaling: 1.0,
                     translation: Vector::default(),
                 },
+                errors: vec![],
             },
             Task::batch([Task::done(Message::NewFile), widget::focus_next()]),
         )
@@ -119,6 +121,7 @@ impl ASTVisualizer {
                 if !self.is_loading {
                     self.file = None;
                     self.is_ast_valid = false;
+                    self.errors.clear();
                     self.content = text_editor::Content::new();
                 }
 
@@ -189,13 +192,12 @@ impl ASTVisualizer {
                 if self.is_ast_valid {
                     let text = self.content.text();
 
-                    let mut tokens = tokenize(&text).unwrap();
-                    let ast_program = parse_program(&mut tokens).unwrap();
-                    let tacky_ast = emit_tacky(ast_program);
-                    let codegen_ast = gen(tacky_ast);
-                    let replace_pseudos_ast = replace_pseudos(codegen_ast);
-                    let fixup_ast = fixup_program(replace_pseudos_ast.1, replace_pseudos_ast.0);
-                    let mut assembly_source_code = emit_assembly(fixup_ast);
+                    let (mut tokens, positions) = tokenize_with_positions(&text, Standard::default()).unwrap();
+                    let ast_program = parse_program_with_context(&mut tokens, ParserContext::new(positions, Standard::default())).unwrap();
+                    let (resolved_ast, symbols) = resolve_program(ast_program, Standard::default()).unwrap();
+                    let options = CompilerOptions::default();
+                    let output = generate_assembly(compile(resolved_ast, &symbols, &options).tacky, &options);
+                    let mut assembly_source_code = emit_assembly(output.assembly);
 
                     if let Some(ending) = self.content.line_ending() {
                         if !assembly_source_code.ends_with(ending.as_str()) {
@@ -226,9 +228,10 @@ impl ASTVisualizer {
                 let text = self.content.text();
 
                 self.is_ast_valid = true;
+                self.errors.clear();
                 // tokenize it
-                let mut tokens = match tokenize(&text) {
-                    Ok(tokens) => tokens,
+                let (mut tokens, positions) = match tokenize_with_positions(&text, Standard::default()) {
+                    Ok(tokenized) => tokenized,
                     Err(_) => {
                         self.is_ast_valid = false;
                         return Task::none();
@@ -236,7 +239,7 @@ impl ASTVisualizer {
                 };
 
                 // generate ast
-                let ast = match parse_program(&mut tokens) {
+                let ast = match parse_program_with_context(&mut tokens, ParserContext::new(positions, Standard::default())) {
                     Ok(ast) => ast,
                     Err(_) => {
                         self.is_ast_valid = false;
@@ -245,16 +248,22 @@ impl ASTVisualizer {
                 };
 
                 // resolve ast semantics
-                let resolved_ast = match resolve_program(ast) {
-                    Ok(resolved_ast) => resolved_ast,
-                    Err(_) => {
+                let (resolved_ast, symbols) = match resolve_program(ast, Standard::default()) {
+                    Ok(resolved) => resolved,
+                    Err(errors) => {
+                        let file = match &self.file {
+                            Some(path) => path.display().to_string(),
+                            None => String::from("New file"),
+                        };
+
                         self.is_ast_valid = false;
+                        self.errors = errors.iter().map(|error| format_error(&file, error)).collect();
                         return Task::none();
                     }
                 };
 
                 // generate ast canvas
-                let root = convert_into_ast_canvas(&resolved_ast);
+                let root = convert_into_ast_canvas(&resolved_ast, &symbols);
 
                 let layout = Some(reingold_tilford::layout(&Tree, &root));
 
@@ -313,6 +322,8 @@ impl ASTVisualizer {
                 1 => {
                     if self.is_ast_valid {
                         view_canvas(&self.ast)
+                    } else if !self.errors.is_empty() {
+                        view_errors(&self.errors)
                     } else {
                         view_loader()
                     }
@@ -3,7 +3,7 @@ use std::path::{Path, PathBuf};
 use std::time::Duration;
 use iced::{highlighter, keyboard, Element, Fill};
 use iced::keyboard::key::Named;
-use iced::widget::{button, canvas, center, center_x, container, text, text_editor, tooltip};
+use iced::widget::{button, canvas, center, center_x, column, container, scrollable, text, text_editor, tooltip};
 use iced::widget::text_editor::Content;
 use crate::ui::ast_canvas_converter::ASTCanvas;
 use crate::ui::ast_visualizer::Message;
@@ -70,6 +70,12 @@ pub(crate) fn view_loader<'a>() -> Element<'a, Message> {
     center(loader).into()
 }
 
+pub(crate) fn view_errors(errors: &Vec<String>) -> Element<Message> {
+    let lines = errors.iter().map(|error| text(error).into());
+
+    scrollable(column(lines).spacing(5).padding(10)).height(Fill).width(Fill).into()
+}
+
 pub(crate) fn view_canvas(ast: &ASTCanvas) -> Element<Message> {
     canvas(ast).height(Fill).width(Fill).into()
 }
\ No newline at end of file